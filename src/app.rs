@@ -1,19 +1,72 @@
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 use eframe::egui;
+use crate::autosave::AutoSaveState;
+use crate::completion::CompletionState;
+use crate::diff::DiffState;
+use crate::file_tree::FileTree;
+use crate::hex::HexState;
+use crate::config::UserConfig;
+use crate::instance::InstanceState;
+use crate::lsp::LspState;
+use crate::macros::MacroState;
+use crate::notifications::NotificationState;
+use crate::outline::OutlineState;
+use crate::panes::PaneState;
+use crate::recent::RecentList;
+use crate::recovery::RecoveryState;
+use crate::search::SearchState;
+use crate::session::SessionState;
+use crate::snippets::SnippetState;
+use crate::watcher::WatchState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BottomPanel {
+    Terminal,
+    Problems,
+    References,
+    Search,
+    Notifications,
+}
 
 pub struct CodeEditorApp {
     pub text: String,
     pub file_path: Option<PathBuf>,
-    pub open_files: Vec<PathBuf>,
-    pub current_file_index: usize,
     pub show_sidebar: bool,
     pub show_terminal: bool,
     pub terminal_output: Arc<Mutex<String>>,
     pub terminal_input: String,
+    pub terminal_dir: Option<PathBuf>,
+    pub terminal_history: Vec<String>,
+    // Position in `terminal_history` while stepping through it with the arrow keys
+    pub terminal_history_index: Option<usize>,
     pub root_folder: Option<PathBuf>,
     pub file_tree_expanded: std::collections::HashMap<PathBuf, bool>,
+    pub file_tree: FileTree,
     pub breadcrumb_path: Vec<PathBuf>,
+    pub cursor: Option<(usize, usize)>,
+    pub config: UserConfig,
+    pub macros: MacroState,
+    pub snippets: SnippetState,
+    pub caret_rect: Option<egui::Rect>,
+    pub completion: CompletionState,
+    pub lsp: LspState,
+    pub bottom_panel: BottomPanel,
+    pub scroll_to_cursor: bool,
+    pub outline: OutlineState,
+    pub panes: PaneState,
+    pub diff: DiffState,
+    pub watcher: WatchState,
+    pub recovery: RecoveryState,
+    pub session: SessionState,
+    pub recent: RecentList,
+    // Documents opened with --wait; the app exits once they are all closed
+    pub waiting: Vec<usize>,
+    pub instance: InstanceState,
+    pub autosave: AutoSaveState,
+    pub notifications: NotificationState,
+    pub hex: HexState,
+    pub search: SearchState,
 }
 
 impl Default for CodeEditorApp {
@@ -21,21 +74,56 @@ impl Default for CodeEditorApp {
         Self {
             text: String::new(),
             file_path: None,
-            open_files: Vec::new(),
-            current_file_index: 0,
             show_sidebar: true,
             show_terminal: true,
             terminal_output: Arc::new(Mutex::new("🖥️ Terminal Ready\nType commands below\n> ".to_string())),
             terminal_input: String::new(),
+            terminal_dir: None,
+            terminal_history: Vec::new(),
+            terminal_history_index: None,
             root_folder: None,
             file_tree_expanded: std::collections::HashMap::new(),
+            file_tree: FileTree::default(),
             breadcrumb_path: Vec::new(),
+            cursor: None,
+            config: UserConfig::load(),
+            macros: MacroState::default(),
+            snippets: SnippetState::default(),
+            caret_rect: None,
+            completion: CompletionState::default(),
+            lsp: LspState::default(),
+            bottom_panel: BottomPanel::Terminal,
+            scroll_to_cursor: false,
+            outline: OutlineState::default(),
+            panes: PaneState::default(),
+            diff: DiffState::default(),
+            watcher: WatchState::default(),
+            recovery: RecoveryState::default(),
+            session: SessionState::default(),
+            recent: RecentList::load(),
+            waiting: Vec::new(),
+            instance: InstanceState::default(),
+            autosave: AutoSaveState::default(),
+            notifications: NotificationState::default(),
+            hex: HexState::default(),
+            search: SearchState::default(),
         }
     }
 }
 
 impl eframe::App for CodeEditorApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        crate::instance::sync(self, ctx, frame);
         crate::ui::render(self, ctx);
+        if crate::cli::wait_finished(self) {
+            frame.close();
+        }
+    }
+
+    fn on_close_event(&mut self) -> bool {
+        crate::recovery::exit(self);
+        crate::session::save(self);
+        crate::lsp::shutdown(self);
+        true
     }
 }
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::file_ops;
+use crate::panes;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutoSave {
+    #[default]
+    Off,
+    AfterDelay,
+    OnTabSwitch,
+    OnFocusLoss,
+}
+
+impl AutoSave {
+    pub const ALL: [AutoSave; 4] = [AutoSave::Off, AutoSave::AfterDelay, AutoSave::OnTabSwitch, AutoSave::OnFocusLoss];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AutoSave::Off => "Off",
+            AutoSave::AfterDelay => "After a delay",
+            AutoSave::OnTabSwitch => "When switching tabs",
+            AutoSave::OnFocusLoss => "When the window loses focus",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct AutoSaveState {
+    // Time of the last edit that has not been auto-saved yet
+    pub edited: Option<Instant>,
+    previous_doc: Option<usize>,
+    pub last_saved: Option<DateTime<Local>>,
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let active = app.panes.active_doc;
+    let switched_from = app.autosave.previous_doc.filter(|&doc| Some(doc) != active);
+    app.autosave.previous_doc = active;
+
+    match app.config.save.auto_save {
+        AutoSave::Off => app.autosave.edited = None,
+        AutoSave::AfterDelay => {
+            let Some(edited) = app.autosave.edited else { return };
+            let delay = Duration::from_millis(app.config.save.auto_save_delay_ms);
+            let elapsed = edited.elapsed();
+            if elapsed < delay {
+                ctx.request_repaint_after(delay - elapsed);
+                return;
+            }
+            app.autosave.edited = None;
+            let docs = app.panes.documents.keys().copied().collect();
+            save(app, docs);
+        }
+        AutoSave::OnTabSwitch => {
+            if let Some(doc) = switched_from {
+                save(app, vec![doc]);
+            }
+        }
+        AutoSave::OnFocusLoss => {
+            if ctx.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::WindowFocused(false)))) {
+                let docs = app.panes.documents.keys().copied().collect();
+                save(app, docs);
+            }
+        }
+    }
+}
+
+// Untitled documents, files deleted from disk and binaries opened as text are left for the user to save
+fn save(app: &mut CodeEditorApp, docs: Vec<usize>) {
+    let mut saved = false;
+    for doc in docs {
+        let has_file = app.panes.documents.get(&doc).is_some_and(|d| d.path.is_some() && !d.missing && !d.lossy);
+        if has_file && panes::is_dirty(app, doc) && file_ops::save_quietly(app, doc) {
+            saved = true;
+        }
+    }
+    if saved {
+        app.autosave.last_saved = Some(Local::now());
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::diff;
+use crate::editor;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+use crate::panes;
+
+pub const USAGE: &str = "\
+Usage: codonn [options] [path[:line[:column]]...]
+
+Opens folders as the explorer root and files as tabs.
+
+Options:
+  -                    Read standard input into an untitled tab
+  --diff <left> <right>
+                       Compare two files side by side
+  --wait               Keep running until the opened tabs are closed (for GIT_EDITOR)
+  --new-window         Open a separate, empty window even if Codonn is already running
+  -h, --help           Show this help
+  -V, --version        Show the version";
+
+// A file or folder from the command line, with an optional 1-based line and column
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Target {
+    pub path: PathBuf,
+    pub position: Option<(usize, usize)>,
+}
+
+// Sent as JSON to the running window when another launch forwards its arguments
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Args {
+    pub targets: Vec<Target>,
+    pub diff: Option<(PathBuf, PathBuf)>,
+    pub stdin: Option<String>,
+    // `-` was given; main reads standard input into `stdin` before anything is forwarded
+    #[serde(skip)]
+    pub read_stdin: bool,
+    pub wait: bool,
+    pub new_window: bool,
+}
+
+pub enum Parsed {
+    Run(Args),
+    // Help or version text to print before exiting
+    Exit(String),
+}
+
+impl Args {
+    // Plain `codonn` reopens the last session; anything more specific starts from what was asked for
+    pub fn restores_session(&self) -> bool {
+        self.targets.is_empty() && self.diff.is_none() && self.stdin.is_none() && !self.new_window
+    }
+
+    // Unsaved buffers from a crash come back unless the window was opened for a specific file
+    pub fn restores_buffers(&self) -> bool {
+        self.targets.iter().all(|t| t.path.is_dir()) && self.diff.is_none() && self.stdin.is_none() && !self.wait
+    }
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Parsed, String> {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    parse_in(&cwd, args)
+}
+
+fn parse_in(cwd: &Path, args: impl IntoIterator<Item = String>) -> Result<Parsed, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    let mut only_paths = false;
+    while let Some(arg) = args.next() {
+        if only_paths {
+            parsed.targets.push(target(cwd, &arg));
+            continue;
+        }
+        match arg.as_str() {
+            "--" => only_paths = true,
+            "-h" | "--help" => return Ok(Parsed::Exit(USAGE.to_string())),
+            "-V" | "--version" => return Ok(Parsed::Exit(format!("codonn {}", env!("CARGO_PKG_VERSION")))),
+            "--wait" | "-w" => parsed.wait = true,
+            "--new-window" | "-n" => parsed.new_window = true,
+            "--diff" | "-d" => {
+                let (Some(left), Some(right)) = (args.next(), args.next()) else {
+                    return Err("--diff needs two files".to_string());
+                };
+                parsed.diff = Some((cwd.join(left), cwd.join(right)));
+            }
+            "-" => parsed.read_stdin = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{}'", flag)),
+            path => parsed.targets.push(target(cwd, path)),
+        }
+    }
+    Ok(Parsed::Run(parsed))
+}
+
+pub fn read_stdin(args: &mut Args) -> Result<(), String> {
+    if !args.read_stdin {
+        return Ok(());
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text).map_err(|e| format!("could not read standard input: {}", e))?;
+    args.stdin = Some(text);
+    Ok(())
+}
+
+// "src/main.rs:12:5" opens main.rs at line 12, column 5, unless a file with that exact name
+// (or "main.rs:12") exists
+fn target(cwd: &Path, arg: &str) -> Target {
+    let mut path = arg;
+    let mut numbers = Vec::new();
+    while numbers.len() < 2 && !cwd.join(path).exists() {
+        let Some((rest, number)) = path.rsplit_once(':').filter(|(rest, _)| !rest.is_empty()) else { break };
+        let Ok(number) = number.parse::<usize>() else { break };
+        numbers.insert(0, number);
+        path = rest;
+    }
+    let position = match numbers[..] {
+        [line] => Some((line, 1)),
+        [line, column] => Some((line, column)),
+        _ => None,
+    };
+    Target { path: cwd.join(path), position }
+}
+
+// Opens what the command line asked for and returns the documents it opened
+pub fn apply(app: &mut CodeEditorApp, ctx: &egui::Context, args: Args) -> Vec<usize> {
+    let (folders, files): (Vec<Target>, Vec<Target>) = args.targets.into_iter().partition(|t| t.path.is_dir());
+    let mut folders = folders.into_iter();
+    if let Some(folder) = folders.next() {
+        file_ops::open_folder(app, folder.path);
+    }
+    for extra in folders {
+        let message = format!("Only one folder can be open; ignored {}", extra.path.display());
+        notifications::notify(app, Level::Warning, message, Vec::new());
+    }
+
+    let mut opened = Vec::new();
+    for file in files {
+        if let Some(doc) = open_target(app, ctx, file) {
+            opened.push(doc);
+        }
+    }
+    if let Some(text) = args.stdin {
+        let doc = panes::add_document(app, None, text);
+        panes::show(app, doc);
+        opened.push(doc);
+    }
+    if let Some((left, right)) = args.diff {
+        diff::compare_files(app, left, right);
+    }
+    opened
+}
+
+// A file that does not exist yet opens as an empty tab that creates it on save
+fn open_target(app: &mut CodeEditorApp, ctx: &egui::Context, target: Target) -> Option<usize> {
+    if target.path.exists() {
+        file_ops::open_path(app, target.path.clone());
+    } else {
+        let doc = panes::add_document(app, Some(target.path.clone()), String::new());
+        if let Some(document) = app.panes.documents.get_mut(&doc) {
+            document.saved_hash = None;
+        }
+        panes::show(app, doc);
+    }
+    // open_path has already reported why a file could not be opened
+    let doc = app.panes.find(&target.path);
+    if let (Some(_), Some((line, column))) = (doc, target.position) {
+        let start = editor::line_start_index(&app.text, line.saturating_sub(1));
+        let line_length = app.text.chars().skip(start).take_while(|&c| c != '\n').count();
+        editor::jump_to(app, ctx, start + column.saturating_sub(1).min(line_length));
+    }
+    doc
+}
+
+// With --wait the app exits once every tab it was started with has been closed
+pub fn wait_finished(app: &mut CodeEditorApp) -> bool {
+    if app.waiting.is_empty() || app.waiting.iter().any(|doc| app.panes.documents.contains_key(doc)) {
+        return false;
+    }
+    app.waiting.clear();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn run(cwd: &Path, args: &[&str]) -> Args {
+        match parse_in(cwd, args.iter().map(|a| a.to_string())) {
+            Ok(Parsed::Run(args)) => args,
+            Ok(Parsed::Exit(text)) => panic!("unexpected exit: {}", text),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    fn position(cwd: &Path, arg: &str) -> (PathBuf, Option<(usize, usize)>) {
+        let target = target(cwd, arg);
+        (target.path, target.position)
+    }
+
+    #[test]
+    fn line_and_column_suffixes() {
+        let cwd = Path::new("/nonexistent");
+        assert_eq!(position(cwd, "src/main.rs"), (cwd.join("src/main.rs"), None));
+        assert_eq!(position(cwd, "src/main.rs:12"), (cwd.join("src/main.rs"), Some((12, 1))));
+        assert_eq!(position(cwd, "src/main.rs:12:5"), (cwd.join("src/main.rs"), Some((12, 5))));
+        assert_eq!(position(cwd, "a:b:c"), (cwd.join("a:b:c"), None));
+    }
+
+    #[test]
+    fn windows_drive_letter_is_kept() {
+        let cwd = Path::new("/nonexistent");
+        assert_eq!(position(cwd, r"C:\path\file.rs:12:5"), (cwd.join(r"C:\path\file.rs"), Some((12, 5))));
+        assert_eq!(position(cwd, r"C:\path\file.rs"), (cwd.join(r"C:\path\file.rs"), None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_file_with_colon_in_its_name() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes:12"), "").unwrap();
+        assert_eq!(position(dir.path(), "notes:12"), (dir.path().join("notes:12"), None));
+        assert_eq!(position(dir.path(), "notes:12:3"), (dir.path().join("notes:12"), Some((3, 1))));
+    }
+
+    #[test]
+    fn stdin_is_only_flagged_while_parsing() {
+        let args = run(Path::new("/nonexistent"), &["-", "--wait"]);
+        assert!(args.read_stdin);
+        assert!(args.stdin.is_none());
+        assert!(args.wait);
+    }
+
+    #[test]
+    fn options_and_paths() {
+        let cwd = Path::new("/nonexistent");
+        let args = run(cwd, &["--diff", "a", "b", "--", "--not-a-flag"]);
+        assert_eq!(args.diff, Some((cwd.join("a"), cwd.join("b"))));
+        assert_eq!(args.targets, vec![Target { path: cwd.join("--not-a-flag"), position: None }]);
+        assert!(parse_in(cwd, ["--bogus".to_string()]).is_err());
+        assert!(parse_in(cwd, ["--diff".to_string(), "a".to_string()]).is_err());
+        assert!(matches!(parse_in(cwd, ["-h".to_string()]), Ok(Parsed::Exit(_))));
+    }
+}
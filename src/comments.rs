@@ -0,0 +1,228 @@
+use crate::editor;
+
+// (line, column, chars removed, text inserted)
+type LineEdit = (usize, usize, usize, String);
+
+pub fn toggle_line_comment(text: &mut String, selection: (usize, usize), token: &str) -> (usize, usize) {
+    let (first, last) = editor::selected_lines(text, selection.0, selection.1);
+    let lines: Vec<&str> = text.split('\n').collect();
+    let targets: Vec<usize> = (first..=last.min(lines.len() - 1))
+        .filter(|&l| !lines[l].trim().is_empty())
+        .collect();
+    if targets.is_empty() {
+        return selection;
+    }
+
+    let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+    let token_len = token.chars().count();
+    let all_commented = targets.iter().all(|&l| lines[l].trim_start().starts_with(token));
+
+    let edits: Vec<LineEdit> = if all_commented {
+        targets.iter()
+            .map(|&l| {
+                let column = indent(lines[l]);
+                let rest = &lines[l].trim_start()[token.len()..];
+                let removed = token_len + usize::from(rest.starts_with(' '));
+                (l, column, removed, String::new())
+            })
+            .collect()
+    } else {
+        // Comment markers line up at the shallowest indentation of the block
+        let column = targets.iter().map(|&l| indent(lines[l])).min().unwrap_or(0);
+        targets.iter().map(|&l| (l, column, 0, format!("{} ", token))).collect()
+    };
+
+    apply_edits(text, selection, &edits)
+}
+
+pub fn toggle_block_comment(text: &mut String, selection: (usize, usize), open: &str, close: &str) -> (usize, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let (mut start, mut end) = (selection.0.min(selection.1), selection.0.max(selection.1));
+    if start == end {
+        start = editor::line_start_of(text, start);
+        end = start + chars[start..].iter().take_while(|&&c| c != '\n').count();
+    }
+    while start < end && chars[start].is_whitespace() {
+        start += 1;
+    }
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+
+    let inner: String = chars[start..end].iter().collect();
+    let (open_len, close_len) = (open.chars().count(), close.chars().count());
+    let (line, column) = line_col(text, start);
+    let (end_line, end_column) = line_col(text, end);
+
+    let commented = inner.chars().count() >= open_len + close_len && inner.starts_with(open) && inner.ends_with(close);
+    let edits: Vec<LineEdit> = if commented {
+        let body: Vec<char> = inner.chars().skip(open_len).take(inner.chars().count() - open_len - close_len).collect();
+        let pad_start = usize::from(body.first() == Some(&' '));
+        let pad_end = usize::from(body.len() > pad_start && body.last() == Some(&' '));
+        vec![
+            (end_line, end_column - close_len - pad_end, close_len + pad_end, String::new()),
+            (line, column, open_len + pad_start, String::new()),
+        ]
+    } else if start == end {
+        vec![(line, column, 0, format!("{}  {}", open, close))]
+    } else {
+        vec![
+            (end_line, end_column, 0, format!(" {}", close)),
+            (line, column, 0, format!("{} ", open)),
+        ]
+    };
+
+    let new_selection = apply_edits(text, selection, &edits);
+    if start == end && selection.0 == selection.1 {
+        // Empty line: put the caret between the markers
+        let caret = editor::line_start_of(text, new_selection.0) + column + open_len + 1;
+        return (caret, caret);
+    }
+    if !commented && start != end && selection.0 != selection.1 {
+        // Select the whole comment so toggling again unwraps it
+        let end = end + open_len + close_len + 2;
+        return if selection.0 <= selection.1 { (start, end) } else { (end, start) };
+    }
+    new_selection
+}
+
+fn line_col(text: &str, index: usize) -> (usize, usize) {
+    let before: Vec<char> = text.chars().take(index).collect();
+    let line = before.iter().filter(|&&c| c == '\n').count();
+    let column = before.iter().rev().take_while(|&&c| c != '\n').count();
+    (line, column)
+}
+
+// Applies the edits (later columns first on a shared line) and maps the selection through them
+fn apply_edits(text: &mut String, selection: (usize, usize), edits: &[LineEdit]) -> (usize, usize) {
+    let mut lines: Vec<Vec<char>> = text.split('\n').map(|l| l.chars().collect()).collect();
+    let mut positions = [line_col(text, selection.0), line_col(text, selection.1)];
+
+    for (line, column, removed, inserted) in edits {
+        let inserted_len = inserted.chars().count();
+        lines[*line].splice(*column..*column + *removed, inserted.chars());
+        for (l, c) in positions.iter_mut() {
+            if *l != *line || *c < *column {
+                continue;
+            }
+            *c = if *c >= *column + *removed {
+                *c - *removed + inserted_len
+            } else {
+                *column + inserted_len
+            };
+        }
+    }
+
+    *text = lines.iter().map(|l| l.iter().collect::<String>()).collect::<Vec<_>>().join("\n");
+    let index = |(l, c): (usize, usize)| editor::line_start_index(text, l) + c;
+    (index(positions[0]), index(positions[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toggle_lines(text: &str, selection: (usize, usize)) -> (String, (usize, usize)) {
+        let mut text = text.to_string();
+        let selection = toggle_line_comment(&mut text, selection, "//");
+        (text, selection)
+    }
+
+    fn toggle_block(text: &str, selection: (usize, usize)) -> (String, (usize, usize)) {
+        let mut text = text.to_string();
+        let selection = toggle_block_comment(&mut text, selection, "/*", "*/");
+        (text, selection)
+    }
+
+    #[test]
+    fn line_comment_at_shallowest_indent() {
+        let (text, _) = toggle_lines("fn a() {\n    let x;\n        let y;\n}", (9, 35));
+        assert_eq!(text, "fn a() {\n    // let x;\n    //     let y;\n}");
+    }
+
+    #[test]
+    fn line_comment_round_trips_mixed_indentation() {
+        let original = "    let x;\n\tlet y;\n  \t let z;";
+        let (commented, selection) = toggle_lines(original, (0, original.chars().count()));
+        assert_eq!(commented, " //    let x;\n\t// let y;\n //  \t let z;");
+        let (restored, _) = toggle_lines(&commented, selection);
+        assert_eq!(restored, original);
+    }
+
+    #[test]
+    fn line_comment_skips_blank_lines() {
+        let (text, _) = toggle_lines("a\n\n   \nb", (0, 8));
+        assert_eq!(text, "// a\n\n   \n// b");
+    }
+
+    #[test]
+    fn line_comment_uncomments_only_when_all_commented() {
+        let (text, _) = toggle_lines("// a\nb", (0, 6));
+        assert_eq!(text, "// // a\n// b");
+        let (text, _) = toggle_lines("  // a\n  //b", (0, 12));
+        assert_eq!(text, "  a\n  b");
+    }
+
+    #[test]
+    fn line_comment_with_empty_selection() {
+        let (text, selection) = toggle_lines("one\n  two\nthree", (7, 7));
+        assert_eq!(text, "one\n  // two\nthree");
+        assert_eq!(selection, (10, 10));
+        let (text, selection) = toggle_lines(&text, selection);
+        assert_eq!(text, "one\n  two\nthree");
+        assert_eq!(selection, (7, 7));
+    }
+
+    #[test]
+    fn line_comment_on_blank_line_is_a_no_op() {
+        let (text, selection) = toggle_lines("a\n\nb", (2, 2));
+        assert_eq!(text, "a\n\nb");
+        assert_eq!(selection, (2, 2));
+    }
+
+    #[test]
+    fn selection_ending_at_line_start_excludes_that_line() {
+        let (text, _) = toggle_lines("a\nb\nc", (0, 4));
+        assert_eq!(text, "// a\n// b\nc");
+    }
+
+    #[test]
+    fn block_comment_wraps_selection() {
+        let (text, selection) = toggle_block("let x = 1 + 2;", (8, 13));
+        assert_eq!(text, "let x = /* 1 + 2 */;");
+        assert_eq!(selection, (8, 19));
+        let (text, selection) = toggle_block(&text, selection);
+        assert_eq!(text, "let x = 1 + 2;");
+        assert_eq!(selection, (8, 13));
+    }
+
+    #[test]
+    fn block_comment_keeps_reversed_selection() {
+        let (text, selection) = toggle_block("a b c", (3, 2));
+        assert_eq!(text, "a /* b */ c");
+        assert_eq!(selection, (9, 2));
+    }
+
+    #[test]
+    fn block_comment_with_empty_selection_wraps_line() {
+        let (text, _) = toggle_block("  call();\nnext", (4, 4));
+        assert_eq!(text, "  /* call(); */\nnext");
+        let (text, _) = toggle_block(&text, (4, 4));
+        assert_eq!(text, "  call();\nnext");
+    }
+
+    #[test]
+    fn block_comment_on_empty_line_places_caret_inside() {
+        let (text, selection) = toggle_block("a\n    \nb", (4, 4));
+        assert_eq!(text, "a\n    /*  */\nb");
+        assert_eq!(selection, (9, 9));
+    }
+
+    #[test]
+    fn block_comment_across_lines() {
+        let (text, _) = toggle_block("a\n\tb\n\tc\n", (3, 7));
+        assert_eq!(text, "a\n\t/* b\n\tc */\n");
+        let (text, _) = toggle_block(&text, (3, 13));
+        assert_eq!(text, "a\n\tb\n\tc\n");
+    }
+}
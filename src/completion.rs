@@ -0,0 +1,529 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::editor;
+use crate::excludes::{self, Excludes};
+use crate::language::Language;
+use crate::lsp;
+use crate::panes;
+use crate::snippets::{self, Snippet};
+
+const MAX_ITEMS: usize = 50;
+const MAX_WORKSPACE_FILES: usize = 2000;
+const MAX_FILE_SIZE: u64 = 512 * 1024;
+
+// Word counts for every file under a workspace root
+struct WorkspaceIndex {
+    root: PathBuf,
+    words: HashMap<String, usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompletionSettings {
+    pub enabled: bool,
+    pub min_chars: usize,
+    pub include_workspace: bool,
+}
+
+impl Default for CompletionSettings {
+    fn default() -> Self {
+        Self { enabled: true, min_chars: 2, include_workspace: false }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Source {
+    Buffer,
+    OpenTab,
+    Workspace,
+    Language,
+}
+
+impl Source {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Source::Buffer => "buffer",
+            Source::OpenTab => "tab",
+            Source::Workspace => "workspace",
+            Source::Language => "lsp",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    pub word: String,
+    pub source: Source,
+    pub count: usize,
+    // Distance in lines from the caret, for words in the current buffer
+    pub distance: Option<usize>,
+    // Language server items may show and insert something other than the matched word
+    pub label: Option<String>,
+    pub insert_text: Option<String>,
+    pub snippet: bool,
+    pub detail: Option<String>,
+}
+
+impl Candidate {
+    fn word(word: String, source: Source) -> Self {
+        Self { word, source, count: 0, distance: None, label: None, insert_text: None, snippet: false, detail: None }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompletionItem {
+    pub label: String,
+    pub source: Source,
+    pub detail: Option<String>,
+    insert_text: Option<String>,
+    snippet: bool,
+    score: f32,
+}
+
+#[derive(Default)]
+pub struct CompletionState {
+    pub active: bool,
+    pub items: Vec<CompletionItem>,
+    pub selected: usize,
+    word_start: usize,
+    // Opened on a trigger character, so an empty word keeps the popup open
+    allow_empty: bool,
+    candidates: Vec<Candidate>,
+    // Shared rather than copied each time the popup opens
+    workspace: Option<Arc<WorkspaceIndex>>,
+    indexing: Option<Receiver<WorkspaceIndex>>,
+    // Files changed since the index was built; it is still used until the new one is ready
+    workspace_stale: bool,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Identifier-like words with their char offset
+fn words(text: &str) -> Vec<(usize, String)> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    for (i, c) in text.chars().enumerate() {
+        if is_word_char(c) {
+            if current.is_empty() {
+                start = i;
+            }
+            current.push(c);
+        } else if !current.is_empty() {
+            words.push((start, std::mem::take(&mut current)));
+        }
+    }
+    if !current.is_empty() {
+        words.push((start, current));
+    }
+    words.retain(|(_, w)| w.chars().count() > 1 && !w.chars().next().unwrap().is_ascii_digit());
+    words
+}
+
+fn count_words(text: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for (_, word) in words(text) {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+
+// Subsequence match, case-insensitive; rewards prefix, consecutive and word-boundary hits
+pub fn fuzzy_score(query: &str, word: &str) -> Option<f32> {
+    let query: Vec<char> = query.chars().flat_map(|c| c.to_lowercase()).collect();
+    let chars: Vec<char> = word.chars().collect();
+    let mut score = 0.0;
+    let mut qi = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if qi == query.len() {
+            break;
+        }
+        if c.to_lowercase().eq(std::iter::once(query[qi])) {
+            score += 1.0;
+            if i == 0 {
+                score += 10.0;
+            }
+            if last_match == Some(i.wrapping_sub(1)) {
+                score += 5.0;
+            }
+            let boundary = i > 0 && (chars[i - 1] == '_' || (chars[i - 1].is_lowercase() && c.is_uppercase()));
+            if boundary {
+                score += 3.0;
+            }
+            if let Some(last) = last_match {
+                score -= (i - last - 1) as f32 * 0.5;
+            }
+            last_match = Some(i);
+            qi += 1;
+        }
+    }
+
+    if qi < query.len() {
+        return None;
+    }
+    // Shorter words are closer to what was typed
+    Some(score - (chars.len() - query.len()) as f32 * 0.1)
+}
+
+fn rank(candidates: &[Candidate], query: &str) -> Vec<CompletionItem> {
+    let mut items: Vec<CompletionItem> = candidates.iter()
+        .filter(|c| c.word != query)
+        .filter_map(|c| {
+            let fuzzy = fuzzy_score(query, &c.word)?;
+            let proximity = c.distance.map_or(0.0, |d| 20.0 / (1.0 + d as f32));
+            let frequency = (c.count.min(20) as f32) * 0.5;
+            let source = match c.source {
+                Source::Buffer => 5.0,
+                Source::OpenTab => 2.0,
+                Source::Workspace => 0.0,
+                Source::Language => 8.0,
+            };
+            Some(CompletionItem {
+                label: c.label.clone().unwrap_or_else(|| c.word.clone()),
+                source: c.source,
+                detail: c.detail.clone(),
+                insert_text: c.insert_text.clone(),
+                snippet: c.snippet,
+                score: fuzzy + proximity + frequency + source,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.label.cmp(&b.label)));
+    items.truncate(MAX_ITEMS);
+    items
+}
+
+fn collect_candidates(app: &mut CodeEditorApp, cursor: usize) -> Vec<Candidate> {
+    let mut by_word: HashMap<String, Candidate> = HashMap::new();
+    let cursor_line = app.text.chars().take(cursor).filter(|&c| c == '\n').count();
+
+    // Offsets of line starts so word positions can be turned into line numbers
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(app.text.chars().enumerate().filter(|&(_, c)| c == '\n').map(|(i, _)| i + 1))
+        .collect();
+    for (start, word) in words(&app.text) {
+        let end = start + word.chars().count();
+        if start <= cursor && cursor <= end {
+            continue;
+        }
+        let line = line_starts.partition_point(|&s| s <= start) - 1;
+        let distance = line.abs_diff(cursor_line);
+        let entry = by_word.entry(word.clone()).or_insert(Candidate {
+            distance: Some(distance),
+            ..Candidate::word(word, Source::Buffer)
+        });
+        entry.count += 1;
+        entry.distance = entry.distance.map(|d| d.min(distance));
+    }
+
+    for (id, document) in &app.panes.documents {
+        if app.panes.active_doc == Some(*id) {
+            continue;
+        }
+        for (word, count) in count_words(&document.text) {
+            let entry = by_word.entry(word.clone()).or_insert_with(|| Candidate::word(word, Source::OpenTab));
+            entry.count += count;
+        }
+    }
+
+    if app.config.completion.include_workspace {
+        if let Some(workspace) = workspace_words(app) {
+            for (word, &count) in &workspace.words {
+                let entry = by_word.entry(word.clone()).or_insert_with(|| Candidate::word(word.clone(), Source::Workspace));
+                entry.count += count;
+            }
+        }
+    }
+
+    by_word.into_values().collect()
+}
+
+// Returns the workspace index if it is ready; starts building it in the background when
+// there is none or the files changed
+fn workspace_words(app: &mut CodeEditorApp) -> Option<Arc<WorkspaceIndex>> {
+    let root = app.root_folder.clone()?;
+    if let Some(indexing) = &app.completion.indexing {
+        match indexing.try_recv() {
+            Ok(index) => {
+                app.completion.workspace = Some(Arc::new(index));
+                app.completion.indexing = None;
+            }
+            Err(TryRecvError::Disconnected) => app.completion.indexing = None,
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+    let current = app.completion.workspace.clone().filter(|index| index.root == root);
+    if (current.is_none() || app.completion.workspace_stale) && app.completion.indexing.is_none() {
+        start_indexing(app, root);
+    }
+    current
+}
+
+fn start_indexing(app: &mut CodeEditorApp, root: PathBuf) {
+    let (sender, receiver) = channel();
+    app.completion.indexing = Some(receiver);
+    app.completion.workspace_stale = false;
+    let settings = app.config.excludes.clone();
+    thread::spawn(move || {
+        let mut words: HashMap<String, usize> = HashMap::new();
+        let files = excludes::workspace_files(&root, &settings)
+            .filter(|path| indexed_language(path))
+            .filter(|path| fs::metadata(path).map(|m| m.len() <= MAX_FILE_SIZE).unwrap_or(false))
+            .take(MAX_WORKSPACE_FILES);
+        for path in files {
+            if let Ok(content) = fs::read_to_string(&path) {
+                for (word, count) in count_words(&content) {
+                    *words.entry(word).or_insert(0) += count;
+                }
+            }
+        }
+        let _ = sender.send(WorkspaceIndex { root, words });
+    });
+}
+
+fn indexed_language(path: &Path) -> bool {
+    !matches!(Language::from_path(path), Language::Unknown | Language::Lock)
+}
+
+// Rebuilds the index the next time it is used if a file it covers was created, changed or deleted
+pub fn files_changed(app: &mut CodeEditorApp, paths: &BTreeSet<PathBuf>) {
+    let Some(root) = app.completion.workspace.as_ref().map(|index| index.root.clone()) else { return };
+    let mut excludes = Excludes::new(&root, &app.config.excludes);
+    let changed = paths.iter().filter(|path| path.starts_with(&root)).any(|path| {
+        excludes::is_ignore_file(path) || (indexed_language(path) && !excludes.is_ignored(path, false))
+    });
+    if changed {
+        app.completion.workspace_stale = true;
+    }
+}
+
+// Called after the exclude settings were edited
+pub fn settings_changed(app: &mut CodeEditorApp) {
+    app.completion.workspace_stale = true;
+}
+
+fn word_before_cursor(app: &CodeEditorApp) -> Option<(usize, String)> {
+    let (primary, secondary) = app.cursor?;
+    if primary != secondary {
+        return None;
+    }
+    let before: Vec<char> = app.text.chars().take(primary).collect();
+    let start = before.iter().rposition(|&c| !is_word_char(c)).map_or(0, |i| i + 1);
+    Some((start, before[start..].iter().collect()))
+}
+
+// Member access triggers language server completion before any word is typed
+fn after_trigger(text: &str, index: usize) -> bool {
+    let before: Vec<char> = text.chars().take(index).collect();
+    before.ends_with(&['.']) || before.ends_with(&[':', ':'])
+}
+
+pub fn open(app: &mut CodeEditorApp) {
+    let Some((start, word)) = word_before_cursor(app) else { return };
+    let cursor = start + word.chars().count();
+    app.completion.candidates = collect_candidates(app, cursor);
+    app.completion.word_start = start;
+    app.completion.items = rank(&app.completion.candidates, &word);
+    app.completion.selected = 0;
+    app.completion.active = !app.completion.items.is_empty();
+    lsp::request_completion(app);
+}
+
+// Merges language server results into the popup, opening it if needed
+pub fn extend(app: &mut CodeEditorApp, candidates: Vec<Candidate>) {
+    let Some((start, word)) = word_before_cursor(app) else { return };
+    if !app.completion.active || app.completion.word_start != start {
+        app.completion.candidates.clear();
+        app.completion.word_start = start;
+        app.completion.selected = 0;
+    }
+    app.completion.allow_empty = word.is_empty();
+    app.completion.candidates.retain(|c| c.source != Source::Language);
+    app.completion.candidates.extend(candidates);
+    app.completion.items = rank(&app.completion.candidates, &word);
+    app.completion.selected = app.completion.selected.min(app.completion.items.len().saturating_sub(1));
+    app.completion.active = !app.completion.items.is_empty();
+}
+
+pub fn close(app: &mut CodeEditorApp) {
+    app.completion.active = false;
+    app.completion.allow_empty = false;
+    app.completion.items.clear();
+    app.completion.candidates.clear();
+}
+
+pub fn accept(app: &mut CodeEditorApp, ctx: &egui::Context, index: usize) {
+    let Some(item) = app.completion.items.get(index).cloned() else { return };
+    let Some((primary, _)) = app.cursor else { return };
+    let start = app.completion.word_start;
+    close(app);
+    let text = item.insert_text.unwrap_or(item.label);
+    if item.snippet {
+        let snippet = Snippet { name: String::new(), prefix: String::new(), body: text, description: String::new() };
+        snippets::insert(app, ctx, &snippet, start, primary);
+        return;
+    }
+    editor::replace_chars(&mut app.text, start, primary, &text);
+    panes::mark_active_edited(app);
+    let end = start + text.chars().count();
+    editor::select(app, ctx, end, end);
+}
+
+// Handles navigation keys before the text edit sees them
+pub fn handle_keys(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Space)) {
+        open(app);
+        return;
+    }
+    if !app.completion.active {
+        return;
+    }
+
+    let count = app.completion.items.len();
+    let none = egui::Modifiers::NONE;
+    if ctx.input_mut(|i| i.consume_key(none, egui::Key::ArrowDown)) {
+        app.completion.selected = (app.completion.selected + 1) % count;
+    }
+    if ctx.input_mut(|i| i.consume_key(none, egui::Key::ArrowUp)) {
+        app.completion.selected = (app.completion.selected + count - 1) % count;
+    }
+    if ctx.input_mut(|i| i.consume_key(none, egui::Key::Escape)) {
+        close(app);
+        return;
+    }
+    if ctx.input_mut(|i| i.consume_key(none, egui::Key::Tab) || i.consume_key(none, egui::Key::Enter)) {
+        accept(app, ctx, app.completion.selected);
+    }
+}
+
+// Called after the text edit: refilters while typing and opens the popup once enough chars are typed
+pub fn after_edit(app: &mut CodeEditorApp, typed: bool) {
+    let word = word_before_cursor(app);
+    if app.completion.active {
+        match word {
+            Some((start, word)) if start == app.completion.word_start && (!word.is_empty() || app.completion.allow_empty) => {
+                app.completion.items = rank(&app.completion.candidates, &word);
+                app.completion.selected = app.completion.selected.min(app.completion.items.len().saturating_sub(1));
+                if app.completion.items.is_empty() {
+                    close(app);
+                }
+            }
+            _ => close(app),
+        }
+    } else if typed && app.config.completion.enabled {
+        if let Some((start, word)) = word {
+            if word.chars().count() >= app.config.completion.min_chars.max(1) {
+                open(app);
+            } else if word.is_empty() && after_trigger(&app.text, start) {
+                lsp::request_completion(app);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn words_are_identifiers_with_char_offsets() {
+        let found = words("let café_2 = x + 42 * foo_bar(ü1);");
+        assert_eq!(found, vec![
+            (0, "let".to_string()),
+            (4, "café_2".to_string()),
+            (22, "foo_bar".to_string()),
+            (30, "ü1".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn prefix_matches_beat_subsequences() {
+        let prefix = fuzzy_score("fo", "foo_bar").unwrap();
+        let boundary = fuzzy_score("ba", "foo_bar").unwrap();
+        let scattered = fuzzy_score("fr", "foo_bar").unwrap();
+        assert!(prefix > boundary && boundary > scattered);
+        assert!(fuzzy_score("FB", "foo_bar").is_some());
+        assert_eq!(fuzzy_score("xyz", "foo_bar"), None);
+        assert_eq!(fuzzy_score("rab", "foo_bar"), None);
+    }
+
+    #[test]
+    fn rank_skips_the_typed_word_and_orders_by_score() {
+        let candidates = vec![
+            Candidate::word("format".to_string(), Source::Workspace),
+            Candidate::word("fo".to_string(), Source::Buffer),
+            Candidate::word("buffer_of".to_string(), Source::Buffer),
+            Candidate::word("for_each".to_string(), Source::Buffer),
+        ];
+        assert_eq!(labels(&rank(&candidates, "fo")), vec!["for_each", "format", "buffer_of"]);
+    }
+
+    #[test]
+    fn words_from_every_source_are_listed_once() {
+        let mut app = CodeEditorApp::default();
+        let other = panes::add_document(&mut app, None, "shared_name other_tab shared_name".to_string());
+        let active = panes::add_document(&mut app, None, String::new());
+        panes::show(&mut app, active);
+        app.text = "shared_name\nlocal_name\n".to_string();
+        assert_ne!(app.panes.active_doc, Some(other));
+
+        let end = app.text.chars().count();
+        let mut candidates = collect_candidates(&mut app, end);
+        candidates.sort_by(|a, b| a.word.cmp(&b.word));
+        let summary: Vec<(&str, Source, usize)> = candidates.iter().map(|c| (c.word.as_str(), c.source, c.count)).collect();
+        assert_eq!(summary, vec![
+            ("local_name", Source::Buffer, 1),
+            ("other_tab", Source::OpenTab, 1),
+            ("shared_name", Source::Buffer, 3),
+        ]);
+    }
+
+    fn wait_for_index(app: &mut CodeEditorApp, done: impl Fn(&WorkspaceIndex) -> bool) -> Arc<WorkspaceIndex> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(index) = workspace_words(app).filter(|index| done(index)) {
+                return index;
+            }
+            assert!(Instant::now() < deadline, "the workspace index was not built");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn workspace_index_is_shared_and_rebuilt_when_files_change() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn alpha_word() {}").unwrap();
+        let mut app = CodeEditorApp { root_folder: Some(dir.path().to_path_buf()), ..Default::default() };
+        let index = wait_for_index(&mut app, |_| true);
+        assert!(index.words.contains_key("alpha_word"));
+        assert!(Arc::ptr_eq(&index, &workspace_words(&mut app).unwrap()));
+
+        // Files the index does not read leave it alone
+        files_changed(&mut app, &BTreeSet::from([dir.path().join("Cargo.lock"), PathBuf::from("/elsewhere/b.rs")]));
+        assert!(!app.completion.workspace_stale);
+
+        fs::write(dir.path().join("b.rs"), "fn beta_word() {}").unwrap();
+        files_changed(&mut app, &BTreeSet::from([dir.path().join("b.rs")]));
+        assert!(app.completion.workspace_stale);
+        // The old index is used while the new one is built
+        assert!(Arc::ptr_eq(&index, &workspace_words(&mut app).unwrap()));
+        let rebuilt = wait_for_index(&mut app, |index| index.words.contains_key("beta_word"));
+        assert!(rebuilt.words.contains_key("alpha_word"));
+    }
+}
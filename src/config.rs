@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::completion::CompletionSettings;
+use crate::excludes::ExcludeSettings;
+use crate::file_ops::SaveSettings;
+use crate::lsp::LspSettings;
+use crate::macros::Macro;
+
+// User settings stored as JSON in the platform config directory
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserConfig {
+    pub macros: Vec<Macro>,
+    pub completion: CompletionSettings,
+    pub lsp: LspSettings,
+    pub excludes: ExcludeSettings,
+    pub save: SaveSettings,
+}
+
+impl UserConfig {
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, content)
+    }
+}
+
+#[cfg(not(test))]
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("codonn"))
+}
+
+// Tests never read or write the user's settings; each test thread gets an empty folder of its own
+#[cfg(test)]
+pub fn config_dir() -> Option<PathBuf> {
+    thread_local! {
+        static DIR: tempfile::TempDir = tempfile::tempdir().unwrap();
+    }
+    Some(DIR.with(|dir| dir.path().to_path_buf()))
+}
+
+fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("settings.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::CodeEditorApp;
+    use crate::recent::{self, RecentKind};
+
+    #[test]
+    fn tests_use_a_config_folder_of_their_own() {
+        let dir = config_dir().unwrap();
+        assert!(!dirs::config_dir().is_some_and(|user| dir.starts_with(user)));
+        assert_ne!(std::thread::spawn(|| config_dir().unwrap()).join().unwrap(), dir);
+
+        let mut app = CodeEditorApp::default();
+        assert!(app.recent.files.is_empty());
+        recent::add(&mut app, RecentKind::File, &dir.join("a.rs"));
+        assert!(dir.join("recent.json").exists());
+    }
+}
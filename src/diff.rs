@@ -0,0 +1,559 @@
+use std::fs;
+use std::ops::Range;
+use std::path::PathBuf;
+use crate::app::CodeEditorApp;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+use crate::panes;
+
+// Past this many edits a changed region is shown as one replaced block
+const MAX_EDIT_DISTANCE: usize = 4000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// Myers' O(ND) diff in linear space; Equal consumes one item of each side, Delete one of `a`, Insert one of `b`
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Op> {
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    diff_into(a, b, &mut ops);
+    ops
+}
+
+fn diff_into<T: PartialEq>(a: &[T], b: &[T], ops: &mut Vec<Op>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    ops.extend(vec![Op::Equal; prefix]);
+    match split_point(a_mid, b_mid) {
+        Some((x, y)) => {
+            diff_into(&a_mid[..x], &b_mid[..y], ops);
+            diff_into(&a_mid[x..], &b_mid[y..], ops);
+        }
+        None => {
+            ops.extend(vec![Op::Delete; a_mid.len()]);
+            ops.extend(vec![Op::Insert; b_mid.len()]);
+        }
+    }
+    ops.extend(vec![Op::Equal; suffix]);
+}
+
+// Runs the search from both ends until the paths overlap and returns a point on the
+// shortest edit script in between, keeping only one row of furthest x per direction.
+// None when one side is empty or the sides are too far apart to be worth aligning.
+fn split_point<T: PartialEq>(a: &[T], b: &[T]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    if n == 0 || m == 0 {
+        return None;
+    }
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let len = 2 * max_d + 2;
+    // Furthest x per diagonal, counted from the start going forward and from the end going backward
+    let mut forward = vec![-1isize; len as usize];
+    let mut backward = vec![-1isize; len as usize];
+    forward[offset as usize + 1] = 0;
+    backward[offset as usize + 1] = 0;
+    let delta = n - m;
+    // With an odd delta the forward paths reach the overlap first
+    let front = delta & 1 != 0;
+    // Diagonals trimmed once their paths ran off the edge of the grid
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        if d as usize > MAX_EDIT_DISTANCE / 2 {
+            return None;
+        }
+        for k1 in (-d + k1_start..=d - k1_end).step_by(2) {
+            let at = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[at - 1] < forward[at + 1]) {
+                forward[at + 1]
+            } else {
+                forward[at - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[at] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if front {
+                let other = offset + delta - k1;
+                if (0..len).contains(&other) && backward[other as usize] != -1 && x1 >= n - backward[other as usize] {
+                    return split(n, m, x1, y1);
+                }
+            }
+        }
+        for k2 in (-d + k2_start..=d - k2_end).step_by(2) {
+            let at = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[at - 1] < backward[at + 1]) {
+                backward[at + 1]
+            } else {
+                backward[at - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[at] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !front {
+                let other = offset + delta - k2;
+                if (0..len).contains(&other) && forward[other as usize] != -1 {
+                    let x1 = forward[other as usize];
+                    let y1 = offset + x1 - other;
+                    if x1 >= n - x2 {
+                        return split(n, m, x1, y1);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// A split at either corner would not shrink the problem
+fn split(n: isize, m: isize, x: isize, y: isize) -> Option<(usize, usize)> {
+    let inside = (0..=n).contains(&x) && (0..=m).contains(&y) && (x, y) != (0, 0) && (x, y) != (n, m);
+    inside.then_some((x as usize, y as usize))
+}
+
+#[derive(Clone, Debug)]
+pub struct Hunk {
+    // Line ranges on the left (old) and right (new) side
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RowKind {
+    Equal,
+    Change,
+    Delete,
+    Insert,
+}
+
+// One display row; side-by-side rows pair lines, inline rows show one line with its counterpart kept for highlights
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub kind: RowKind,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub hunk: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub enum DiffSide {
+    Document(usize),
+    File(PathBuf),
+    Clipboard(String),
+}
+
+pub struct DiffView {
+    pub left_title: String,
+    pub right_title: String,
+    left: DiffSide,
+    right: DiffSide,
+    old_text: String,
+    new_text: String,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+    pub hunks: Vec<Hunk>,
+    pub rows: Vec<Row>,
+    pub inline_rows: Vec<Row>,
+    pub side_by_side: bool,
+    pub current: usize,
+    pub scroll_to: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct DiffState {
+    pub view: Option<DiffView>,
+    // File picked in the tree as the left side of a comparison
+    pub compare_selection: Option<PathBuf>,
+}
+
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+fn display_lines(text: &str) -> Vec<String> {
+    split_lines(text).iter().map(|l| l.trim_end_matches(['\n', '\r']).to_string()).collect()
+}
+
+fn read_side(app: &CodeEditorApp, side: &DiffSide) -> Option<String> {
+    match side {
+        DiffSide::Document(doc) => app.panes.documents.contains_key(doc).then(|| panes::text_of(app, *doc).to_string()),
+        DiffSide::File(path) => fs::read_to_string(path).ok(),
+        DiffSide::Clipboard(text) => Some(text.clone()),
+    }
+}
+
+impl DiffView {
+    fn new(left_title: String, left: DiffSide, right_title: String, right: DiffSide) -> Self {
+        Self {
+            left_title,
+            right_title,
+            left,
+            right,
+            old_text: String::new(),
+            new_text: String::new(),
+            old_lines: Vec::new(),
+            new_lines: Vec::new(),
+            hunks: Vec::new(),
+            rows: Vec::new(),
+            inline_rows: Vec::new(),
+            side_by_side: true,
+            current: 0,
+            scroll_to: None,
+        }
+    }
+
+    fn compute(&mut self, old_text: String, new_text: String) {
+        let (old, new) = (split_lines(&old_text), split_lines(&new_text));
+        let ops = diff(&old, &new);
+
+        let mut hunks = Vec::new();
+        let mut rows = Vec::new();
+        let (mut i, mut j, mut k) = (0, 0, 0);
+        while k < ops.len() {
+            if ops[k] == Op::Equal {
+                rows.push(Row { kind: RowKind::Equal, left: Some(i), right: Some(j), hunk: None });
+                i += 1;
+                j += 1;
+                k += 1;
+                continue;
+            }
+            let (old_start, new_start) = (i, j);
+            while k < ops.len() && ops[k] != Op::Equal {
+                match ops[k] {
+                    Op::Delete => i += 1,
+                    _ => j += 1,
+                }
+                k += 1;
+            }
+            let hunk = hunks.len();
+            let (deleted, inserted) = (i - old_start, j - new_start);
+            for t in 0..deleted.max(inserted) {
+                let left = (t < deleted).then_some(old_start + t);
+                let right = (t < inserted).then_some(new_start + t);
+                let kind = match (left, right) {
+                    (Some(_), Some(_)) => RowKind::Change,
+                    (Some(_), None) => RowKind::Delete,
+                    _ => RowKind::Insert,
+                };
+                rows.push(Row { kind, left, right, hunk: Some(hunk) });
+            }
+            hunks.push(Hunk { old: old_start..i, new: new_start..j });
+        }
+
+        // Inline: each hunk's removed lines first, then its added lines
+        let mut inline_rows = Vec::new();
+        let mut r = 0;
+        while r < rows.len() {
+            let Some(hunk) = rows[r].hunk else {
+                inline_rows.push(rows[r].clone());
+                r += 1;
+                continue;
+            };
+            let block: Vec<&Row> = rows[r..].iter().take_while(|row| row.hunk == Some(hunk)).collect();
+            for row in block.iter().filter(|row| row.left.is_some()) {
+                inline_rows.push(Row { kind: RowKind::Delete, ..(*row).clone() });
+            }
+            for row in block.iter().filter(|row| row.right.is_some()) {
+                inline_rows.push(Row { kind: RowKind::Insert, ..(*row).clone() });
+            }
+            r += block.len();
+        }
+
+        self.old_lines = display_lines(&old_text);
+        self.new_lines = display_lines(&new_text);
+        self.old_text = old_text;
+        self.new_text = new_text;
+        self.hunks = hunks;
+        self.rows = rows;
+        self.inline_rows = inline_rows;
+        self.current = self.current.min(self.hunks.len().saturating_sub(1));
+    }
+
+    pub fn visible_rows(&self) -> &[Row] {
+        if self.side_by_side { &self.rows } else { &self.inline_rows }
+    }
+
+    fn hunk_row(&self, hunk: usize) -> Option<usize> {
+        self.visible_rows().iter().position(|row| row.hunk == Some(hunk))
+    }
+
+    pub fn hunk_start(&self, row: usize) -> bool {
+        let rows = self.visible_rows();
+        row == 0 || rows[row - 1].hunk != rows[row].hunk
+    }
+
+    pub fn go_to_hunk(&mut self, hunk: usize) {
+        if hunk < self.hunks.len() {
+            self.current = hunk;
+            self.scroll_to = self.hunk_row(hunk);
+        }
+    }
+}
+
+fn open_view(app: &mut CodeEditorApp, mut view: DiffView) {
+    let (Some(old), Some(new)) = (read_side(app, &view.left), read_side(app, &view.right)) else {
+        notifications::notify(app, Level::Error, "Could not read both sides of the comparison".to_string(), Vec::new());
+        return;
+    };
+    view.compute(old, new);
+    view.go_to_hunk(0);
+    app.diff.view = Some(view);
+}
+
+pub fn compare_with_saved(app: &mut CodeEditorApp) {
+    let (Some(doc), Some(path)) = (app.panes.active_doc, app.file_path.clone()) else { return };
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let view = DiffView::new(format!("{} (on disk)", name), DiffSide::File(path), format!("{} (buffer)", name), DiffSide::Document(doc));
+    open_view(app, view);
+}
+
+pub fn compare_with_clipboard(app: &mut CodeEditorApp) {
+    let Some(doc) = app.panes.active_doc else { return };
+    let clipboard = arboard::Clipboard::new().and_then(|mut c| c.get_text());
+    match clipboard {
+        Ok(text) => {
+            let name = app.panes.documents.get(&doc).map(|d| d.name()).unwrap_or_default();
+            let view = DiffView::new("Clipboard".to_string(), DiffSide::Clipboard(text), name, DiffSide::Document(doc));
+            open_view(app, view);
+        }
+        Err(e) => notifications::notify(app, Level::Error, format!("Could not read the clipboard: {}", e), Vec::new()),
+    }
+}
+
+pub fn compare_files(app: &mut CodeEditorApp, left: PathBuf, right: PathBuf) {
+    let title = |p: &PathBuf| p.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let view = DiffView::new(title(&left), DiffSide::File(left), title(&right), DiffSide::File(right));
+    open_view(app, view);
+}
+
+// Rereads both sides; the buffer side is followed live, files and clipboard on request
+pub fn refresh(app: &mut CodeEditorApp, reread_all: bool) {
+    let Some(view) = app.diff.view.as_ref() else { return };
+    let new = read_side(app, &view.right);
+    let old = if reread_all { read_side(app, &view.left) } else { Some(view.old_text.clone()) };
+    let Some(view) = app.diff.view.as_mut() else { return };
+    match (old, new) {
+        (Some(old), Some(new)) if reread_all || new != view.new_text => view.compute(old, new),
+        (_, None) => app.diff.view = None,
+        _ => {}
+    }
+}
+
+pub fn shows_document(app: &CodeEditorApp, doc: usize) -> bool {
+    app.diff.view.as_ref().is_some_and(|view| {
+        [&view.left, &view.right].iter().any(|side| matches!(side, DiffSide::Document(d) if *d == doc))
+    })
+}
+
+// Replaces the right side's lines of a hunk with the left side's
+pub fn revert_hunk(app: &mut CodeEditorApp, hunk: usize) {
+    let Some(view) = app.diff.view.as_ref() else { return };
+    let Some(h) = view.hunks.get(hunk).cloned() else { return };
+    let (old, new) = (split_lines(&view.old_text), split_lines(&view.new_text));
+    let reverted = [&new[..h.new.start], &old[h.old.clone()], &new[h.new.end..]].concat().concat();
+
+    let doc = match view.right.clone() {
+        DiffSide::Document(doc) => doc,
+        DiffSide::File(path) => {
+            // Reverting into a file that is not open edits it in a new tab
+            file_ops::open_path(app, path.clone());
+            let Some(doc) = app.panes.find(&path) else { return };
+            if let Some(view) = app.diff.view.as_mut() {
+                view.right = DiffSide::Document(doc);
+            }
+            doc
+        }
+        DiffSide::Clipboard(_) => return,
+    };
+    if app.panes.active_doc == Some(doc) {
+        app.text = reverted;
+    } else if let Some(document) = app.panes.documents.get_mut(&doc) {
+        document.text = reverted;
+    }
+    panes::mark_edited(app, doc);
+    refresh(app, false);
+}
+
+// Splits a changed line into runs, flagging the characters that differ from its counterpart
+pub fn changed_spans(line: &str, counterpart: &str, old_side: bool) -> Vec<(String, bool)> {
+    let (line_chars, other): (Vec<char>, Vec<char>) = (line.chars().collect(), counterpart.chars().collect());
+    if line_chars.len() + other.len() > 1000 {
+        return vec![(line.to_string(), false)];
+    }
+    let ops = if old_side { diff(&line_chars, &other) } else { diff(&other, &line_chars) };
+    let own = if old_side { Op::Delete } else { Op::Insert };
+
+    let mut spans: Vec<(String, bool)> = Vec::new();
+    let mut chars = line_chars.into_iter();
+    for op in ops.into_iter().filter(|&op| op == Op::Equal || op == own) {
+        let Some(c) = chars.next() else { break };
+        let changed = op == own;
+        match spans.last_mut() {
+            Some((text, flag)) if *flag == changed => text.push(c),
+            _ => spans.push((c.to_string(), changed)),
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rebuilds `b` from `a` and the ops, checking every op is consistent with the inputs
+    fn apply<T: PartialEq + Clone + std::fmt::Debug>(a: &[T], b: &[T], ops: &[Op]) -> Vec<T> {
+        let (mut i, mut j) = (0, 0);
+        let mut out = Vec::new();
+        for op in ops {
+            match op {
+                Op::Equal => {
+                    assert_eq!(a[i], b[j]);
+                    out.push(a[i].clone());
+                    i += 1;
+                    j += 1;
+                }
+                Op::Delete => i += 1,
+                Op::Insert => {
+                    out.push(b[j].clone());
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (a.len(), b.len()));
+        out
+    }
+
+    fn lcs_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let above = row[j + 1];
+                row[j + 1] = if x == y { diagonal + 1 } else { above.max(row[j]) };
+                diagonal = above;
+            }
+        }
+        row[b.len()]
+    }
+
+    fn equal_count(ops: &[Op]) -> usize {
+        ops.iter().filter(|&&op| op == Op::Equal).count()
+    }
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn identical_and_empty_inputs() {
+        assert_eq!(diff(&chars("abc"), &chars("abc")), vec![Op::Equal; 3]);
+        assert_eq!(diff::<char>(&[], &[]), Vec::new());
+        assert_eq!(diff(&chars(""), &chars("ab")), vec![Op::Insert; 2]);
+        assert_eq!(diff(&chars("ab"), &chars("")), vec![Op::Delete; 2]);
+    }
+
+    #[test]
+    fn single_edits() {
+        use Op::*;
+        assert_eq!(diff(&chars("abc"), &chars("abxc")), vec![Equal, Equal, Insert, Equal]);
+        assert_eq!(diff(&chars("abc"), &chars("ac")), vec![Equal, Delete, Equal]);
+        assert_eq!(diff(&chars("abc"), &chars("axc")), vec![Equal, Delete, Insert, Equal]);
+    }
+
+    #[test]
+    fn classic_example_is_minimal() {
+        let (a, b) = (chars("ABCABBA"), chars("CBABAC"));
+        let ops = diff(&a, &b);
+        assert_eq!(apply(&a, &b, &ops), b);
+        assert_eq!(equal_count(&ops), 4);
+        assert_eq!(ops.len() - equal_count(&ops), 5);
+    }
+
+    #[test]
+    fn line_diff() {
+        let a = ["fn main() {", "    one();", "    two();", "}"];
+        let b = ["fn main() {", "    one();", "    three();", "    two();", "}"];
+        let ops = diff(&a, &b);
+        assert_eq!(ops, vec![Op::Equal, Op::Equal, Op::Insert, Op::Equal, Op::Equal]);
+    }
+
+    #[test]
+    fn random_inputs_match_the_longest_common_subsequence() {
+        // Small alphabet so there are plenty of partial matches
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |limit: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % limit
+        };
+        for _ in 0..500 {
+            let a: Vec<u64> = (0..next(40)).map(|_| next(4)).collect();
+            let b: Vec<u64> = (0..next(40)).map(|_| next(4)).collect();
+            let ops = diff(&a, &b);
+            assert_eq!(apply(&a, &b, &ops), b, "{:?} -> {:?}", a, b);
+            assert_eq!(equal_count(&ops), lcs_len(&a, &b), "{:?} -> {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn large_inputs_stay_within_memory() {
+        // Completely different sides far past the edit cap fall back to one replaced block
+        let a: Vec<u32> = (0..200_000).collect();
+        let b: Vec<u32> = (200_000..400_000).collect();
+        let ops = diff(&a, &b);
+        assert_eq!(ops.len(), 400_000);
+        assert_eq!(apply(&a, &b, &ops), b);
+
+        // Scattered edits in a big file are still aligned exactly
+        let mut c = a.clone();
+        for i in (0..c.len()).step_by(1000) {
+            c[i] += 1_000_000;
+        }
+        let ops = diff(&a, &c);
+        assert_eq!(equal_count(&ops), a.len() - 200);
+        assert_eq!(apply(&a, &c, &ops), c);
+    }
+
+    #[test]
+    fn changed_spans_flag_differing_characters() {
+        assert_eq!(
+            changed_spans("let x = 1;", "let y = 1;", true),
+            vec![("let ".to_string(), false), ("x".to_string(), true), (" = 1;".to_string(), false)],
+        );
+        assert_eq!(
+            changed_spans("let y = 10;", "let x = 1;", false),
+            vec![("let ".to_string(), false), ("y".to_string(), true), (" = 1".to_string(), false), ("0".to_string(), true), (";".to_string(), false)],
+        );
+    }
+
+    #[test]
+    fn shows_document_matches_either_side() {
+        let mut app = CodeEditorApp::default();
+        assert!(!shows_document(&app, 1));
+        app.diff.view = Some(DiffView::new(String::new(), DiffSide::File(PathBuf::from("a")), String::new(), DiffSide::Document(1)));
+        assert!(shows_document(&app, 1));
+        assert!(!shows_document(&app, 2));
+        app.diff.view = Some(DiffView::new(String::new(), DiffSide::Document(2), String::new(), DiffSide::Clipboard(String::new())));
+        assert!(shows_document(&app, 2));
+    }
+}
@@ -1,55 +1,363 @@
 use eframe::egui;
+use egui::text::CCursor;
+use egui::text_edit::{CCursorRange, TextEditState};
+use serde::{Deserialize, Serialize};
 use crate::app::CodeEditorApp;
+use crate::comments;
+use crate::completion;
+use crate::language::Language;
+use crate::lsp;
+use crate::macros;
+use crate::panes;
+use crate::snippets;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EditorCommand {
+    ToggleLineComment,
+    ToggleBlockComment,
+}
+
+// The focused editor: the active group showing the active document
+pub fn editor_id(app: &CodeEditorApp) -> egui::Id {
+    let group = &app.panes.groups[app.panes.active_group];
+    pane_id(group.id, group.current())
+}
+
+// Each group keeps its own cursor and scroll position per document
+pub fn pane_id(group: usize, doc: Option<usize>) -> egui::Id {
+    egui::Id::new("code_editor").with(group).with(doc)
+}
+
+pub fn run_command(app: &mut CodeEditorApp, ctx: &egui::Context, command: EditorCommand) {
+    macros::record_command(app, command);
+
+    let language = app.file_path.as_deref().map(Language::from_path).unwrap_or(Language::Unknown);
+    let selection = app.cursor.unwrap_or((0, 0));
+    let new_selection = match command {
+        EditorCommand::ToggleLineComment => match (language.line_comment(), language.block_comment()) {
+            (Some(token), _) => comments::toggle_line_comment(&mut app.text, selection, token),
+            (None, Some((open, close))) => {
+                // Languages without line comments wrap the whole lines instead
+                let (first, last) = selected_lines(&app.text, selection.0, selection.1);
+                let start = line_start_index(&app.text, first);
+                let end = line_start_index(&app.text, last + 1);
+                let end = if end > start && app.text.chars().nth(end - 1) == Some('\n') { end - 1 } else { end };
+                comments::toggle_block_comment(&mut app.text, (start, end), open, close)
+            }
+            (None, None) => return,
+        },
+        EditorCommand::ToggleBlockComment => match language.block_comment() {
+            Some((open, close)) => comments::toggle_block_comment(&mut app.text, selection, open, close),
+            None => return,
+        },
+    };
+    panes::mark_active_edited(app);
+    select(app, ctx, new_selection.0, new_selection.1);
+}
+
+fn handle_shortcuts(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    // egui has no key code for '/', so Ctrl+/ arrives as the typed character with Ctrl held
+    let line_comment = ctx.input_mut(|i| {
+        if !i.modifiers.command {
+            return false;
+        }
+        let count = i.events.len();
+        i.events.retain(|e| !matches!(e, egui::Event::Text(t) if t == "/"));
+        i.events.len() != count
+    });
+    if line_comment {
+        run_command(app, ctx, EditorCommand::ToggleLineComment);
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::A)) {
+        run_command(app, ctx, EditorCommand::ToggleBlockComment);
+    }
+
+    // Language server navigation and refactoring
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F12)) {
+        lsp::goto_definition(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::F12)) {
+        lsp::find_references(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F2)) {
+        lsp::start_rename(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::ALT, egui::Key::Enter)) {
+        lsp::request_code_actions(app);
+    }
+    if app.lsp.signature.is_some() && ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.lsp.signature = None;
+    }
+}
 
 pub fn render_editor(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
-    let lines: Vec<&str> = app.text.lines().collect();
-    let max_line_number = lines.len().max(1).to_string().len();
+    let editor_id = editor_id(app);
+    let has_focus = ui.memory(|m| m.has_focus(editor_id));
 
-    let line_numbers = lines
-        .iter()
-        .enumerate()
-        .map(|(i, _)| format!("{: >width$}", i + 1, width = max_line_number))
-        .collect::<Vec<_>>()
-        .join("\n");
+    if has_focus {
+        handle_shortcuts(app, ui.ctx());
+    }
+    let playback = macros::process_input(app, ui.ctx(), has_focus);
+    if let Some(line) = playback.goto_line {
+        let index = line_start_index(&app.text, line);
+        select(app, ui.ctx(), index, index);
+    }
+    if let Some(command) = playback.command {
+        run_command(app, ui.ctx(), command);
+    }
+    if macros::is_playing(app) {
+        ui.memory_mut(|m| m.request_focus(editor_id));
+        ui.ctx().request_repaint();
+    }
+    if has_focus || macros::is_playing(app) {
+        completion::handle_keys(app, ui.ctx());
+        snippets::handle_keys(app, ui.ctx());
+    }
+    let typed = ui.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Text(_))));
+
+    let line_numbers = line_numbers(&app.text);
 
-    egui::ScrollArea::both()
-        .auto_shrink([false; 2])
-        .show(ui, |ui| {
+    let mut scroll_area = egui::ScrollArea::both()
+        .id_source(editor_id)
+        .auto_shrink([false; 2]);
+    if let Some(offset) = app.session.restore_scroll.remove(&editor_id) {
+        scroll_area = scroll_area.scroll_offset(offset);
+    }
+    let scroll = scroll_area.show(ui, |ui| {
             ui.horizontal(|ui| {
-                ui.add(
-                    egui::Label::new(
-                        egui::RichText::new(line_numbers)
-                            .monospace()
-                            .color(egui::Color32::from_rgb(89, 98, 120))
-                            .size(13.0)
-                    )
-                    .wrap(false)
-                );
-                ui.add_space(10.0);
+                render_gutter(ui, line_numbers);
 
                 let text_edit = egui::TextEdit::multiline(&mut app.text)
+                    .id(editor_id)
                     .font(egui::TextStyle::Monospace)
                     .text_color(egui::Color32::from_rgb(229, 229, 229))
                     .desired_width(f32::INFINITY)
                     .desired_rows(25);
                 
-                let _response = ui.add(text_edit);
+                let output = text_edit.show(ui);
+                if output.response.changed() {
+                    panes::mark_active_edited(app);
+                }
+                app.cursor = output.cursor_range.map(|range| {
+                    let range = range.as_ccursor_range();
+                    (range.primary.index, range.secondary.index)
+                });
+                app.caret_rect = output.cursor_range.map(|range| {
+                    output.galley.pos_from_cursor(&range.primary)
+                        .translate(output.text_draw_pos.to_vec2())
+                });
+                if app.scroll_to_cursor {
+                    if let Some(rect) = app.caret_rect {
+                        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                    }
+                    app.scroll_to_cursor = false;
+                }
+                show_hover(app, ui, &output);
                 
                 // Auto-completion on text change
-                auto_complete_brackets(&mut app.text);
+                if auto_complete_brackets(&mut app.text) {
+                    panes::mark_active_edited(app);
+                }
+                snippets::after_edit(app, ui.ctx());
+                if output.response.has_focus() {
+                    completion::after_edit(app, typed);
+                    if typed {
+                        update_signature_help(app);
+                    }
+                } else if app.completion.active {
+                    completion::close(app);
+                }
             });
         });
+    app.session.scroll.insert(editor_id, scroll.state.offset);
+}
+
+// Squiggles plus a tooltip with diagnostics and hover info for the text under the pointer
+fn show_hover(app: &mut CodeEditorApp, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+    let diagnostic = lsp::paint_diagnostics(app, ui, output);
+    let pointer = ui.input(|i| i.pointer.hover_pos()).filter(|_| output.response.hovered());
+    let Some(pointer) = pointer else {
+        lsp::clear_hover(app);
+        return;
+    };
+
+    let cursor = output.galley.cursor_from_pos(pointer - output.text_draw_pos);
+    let glyph = output.galley.pos_from_cursor(&cursor).translate(output.text_draw_pos.to_vec2());
+    // Only ask about positions actually on a line, not the empty space past its end
+    if (pointer.x - glyph.left()).abs() > 12.0 {
+        lsp::clear_hover(app);
+    } else if ui.input(|i| i.pointer.is_still()) {
+        lsp::request_hover(app, cursor.ccursor.index);
+    }
+
+    let hover = app.lsp.hover.as_ref().map(|(_, text)| text.clone());
+    if diagnostic.is_none() && hover.is_none() {
+        return;
+    }
+    egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("lsp_hover"), |ui| {
+        ui.set_max_width(500.0);
+        if let Some(message) = &diagnostic {
+            ui.label(egui::RichText::new(message).color(egui::Color32::from_rgb(255, 121, 198)));
+        }
+        if let Some(text) = &hover {
+            if diagnostic.is_some() {
+                ui.separator();
+            }
+            ui.label(egui::RichText::new(text).monospace());
+        }
+    });
+}
+
+// Signature help opens on '(' or ',' and closes with the call's ')'
+fn update_signature_help(app: &mut CodeEditorApp) {
+    let Some((primary, _)) = app.cursor else { return };
+    match primary.checked_sub(1).and_then(|i| app.text.chars().nth(i)) {
+        Some('(') | Some(',') => lsp::request_signature_help(app),
+        Some(')') => app.lsp.signature = None,
+        _ => {}
+    }
+}
+
+// Groups other than the active one; clicking into one makes it the active group
+pub fn render_pane(app: &mut CodeEditorApp, ui: &mut egui::Ui, group: usize) {
+    let (group_id, doc) = {
+        let g = &app.panes.groups[group];
+        (g.id, g.current())
+    };
+    let Some(doc) = doc else {
+        ui.colored_label(egui::Color32::from_rgb(98, 114, 164), "Empty group");
+        return;
+    };
+    let id = pane_id(group_id, Some(doc));
+    let line_numbers = line_numbers(panes::text_of(app, doc));
+
+    let mut scroll_area = egui::ScrollArea::both()
+        .id_source(id)
+        .auto_shrink([false; 2]);
+    if let Some(offset) = app.session.restore_scroll.remove(&id) {
+        scroll_area = scroll_area.scroll_offset(offset);
+    }
+    let scroll = scroll_area.show(ui, |ui| {
+            ui.horizontal(|ui| {
+                render_gutter(ui, line_numbers);
+                let text = match app.panes.documents.get_mut(&doc) {
+                    Some(document) if app.panes.active_doc != Some(doc) => &mut document.text,
+                    _ => &mut app.text,
+                };
+                let response = egui::TextEdit::multiline(text)
+                    .id(id)
+                    .font(egui::TextStyle::Monospace)
+                    .text_color(egui::Color32::from_rgb(229, 229, 229))
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(25)
+                    .show(ui)
+                    .response;
+                if response.changed() {
+                    panes::mark_edited(app, doc);
+                }
+                response.has_focus()
+            })
+            .inner
+        });
+    app.session.scroll.insert(id, scroll.state.offset);
+
+    if scroll.inner {
+        app.panes.active_group = group;
+        panes::activate(app);
+    }
+}
+
+fn line_numbers(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let max_line_number = lines.len().max(1).to_string().len();
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, _)| format!("{: >width$}", i + 1, width = max_line_number))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn auto_complete_brackets(text: &mut String) {
+fn render_gutter(ui: &mut egui::Ui, line_numbers: String) {
+    ui.add(
+        egui::Label::new(
+            egui::RichText::new(line_numbers)
+                .monospace()
+                .color(egui::Color32::from_rgb(89, 98, 120))
+                .size(13.0)
+        )
+        .wrap(false)
+    );
+    ui.add_space(10.0);
+}
+
+pub fn set_cursor(ctx: &egui::Context, id: egui::Id, primary: usize, secondary: usize) {
+    let mut state = TextEditState::load(ctx, id).unwrap_or_default();
+    state.set_ccursor_range(Some(CCursorRange::two(CCursor::new(secondary), CCursor::new(primary))));
+    state.store(ctx, id);
+}
+
+pub fn select(app: &mut CodeEditorApp, ctx: &egui::Context, primary: usize, secondary: usize) {
+    set_cursor(ctx, editor_id(app), primary, secondary);
+    app.cursor = Some((primary, secondary));
+}
+
+// Moves the caret and scrolls it into view on the next frame
+pub fn jump_to(app: &mut CodeEditorApp, ctx: &egui::Context, index: usize) {
+    select(app, ctx, index, index);
+    app.scroll_to_cursor = true;
+    ctx.memory_mut(|m| m.request_focus(editor_id(app)));
+}
+
+// First and last line touched by a selection; a selection ending at column 0 leaves that line out
+pub fn selected_lines(text: &str, a: usize, b: usize) -> (usize, usize) {
+    let (start, end) = (a.min(b), a.max(b));
+    let line_of = |index: usize| text.chars().take(index).filter(|&c| c == '\n').count();
+    let (first, last) = (line_of(start), line_of(end));
+    if last > first && text.chars().nth(end - 1) == Some('\n') {
+        (first, last - 1)
+    } else {
+        (first, last)
+    }
+}
+
+pub fn line_start_of(text: &str, index: usize) -> usize {
+    let before: Vec<char> = text.chars().take(index).collect();
+    before.iter().rposition(|&c| c == '\n').map_or(0, |i| i + 1)
+}
+
+pub fn byte_index(text: &str, char_index: usize) -> usize {
+    text.char_indices().nth(char_index).map_or(text.len(), |(i, _)| i)
+}
+
+pub fn replace_chars(text: &mut String, start: usize, end: usize, with: &str) {
+    let (start, end) = (byte_index(text, start), byte_index(text, end));
+    text.replace_range(start..end, with);
+}
+
+pub fn line_start_index(text: &str, line: usize) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    text.chars()
+        .enumerate()
+        .filter(|&(_, c)| c == '\n')
+        .nth(line - 1)
+        .map(|(i, _)| i + 1)
+        .unwrap_or_else(|| text.chars().count())
+}
+
+fn auto_complete_brackets(text: &mut String) -> bool {
     let chars: Vec<char> = text.chars().collect();
     let len = chars.len();
     
     if len < 1 {
-        return;
+        return false;
     }
     
     let last_char = chars[len - 1];
+    let before = text.len();
     
     match last_char {
         '(' => {
@@ -81,4 +389,5 @@ fn auto_complete_brackets(text: &mut String) {
         }
         _ => {}
     }
+    text.len() != before
 }
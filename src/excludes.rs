@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+
+// Which files the explorer and workspace searches leave out
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExcludeSettings {
+    // Honour .gitignore, .ignore, .git/info/exclude and the global git excludes file
+    pub respect_gitignore: bool,
+    // Extra patterns in .gitignore syntax
+    pub globs: Vec<String>,
+    // Show ignored entries dimmed in the explorer instead of hiding them
+    pub show_ignored: bool,
+    pub show_hidden: bool,
+}
+
+impl Default for ExcludeSettings {
+    fn default() -> Self {
+        Self {
+            respect_gitignore: true,
+            globs: vec![".git/".to_string(), ".DS_Store".to_string()],
+            show_ignored: false,
+            show_hidden: false,
+        }
+    }
+}
+
+// Ignore rules for one workspace, with per-folder ignore files read as folders are visited
+pub struct Excludes {
+    root: PathBuf,
+    respect_gitignore: bool,
+    user: Gitignore,
+    global: Gitignore,
+    folders: HashMap<PathBuf, Gitignore>,
+    ignored: HashMap<PathBuf, bool>,
+}
+
+impl Excludes {
+    pub fn new(root: &Path, settings: &ExcludeSettings) -> Self {
+        let mut user = GitignoreBuilder::new(root);
+        for glob in &settings.globs {
+            let _ = user.add_line(None, glob);
+        }
+        let global = if settings.respect_gitignore { Gitignore::global().0 } else { Gitignore::empty() };
+        Self {
+            root: root.to_path_buf(),
+            respect_gitignore: settings.respect_gitignore,
+            user: user.build().unwrap_or_else(|_| Gitignore::empty()),
+            global,
+            folders: HashMap::new(),
+            ignored: HashMap::new(),
+        }
+    }
+
+    // True if the path or any folder above it is excluded
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        if path == self.root || !path.starts_with(&self.root) {
+            return false;
+        }
+        if let Some(&ignored) = self.ignored.get(path) {
+            return ignored;
+        }
+        let parent_ignored = path.parent().is_some_and(|parent| self.is_ignored(parent, true));
+        let ignored = parent_ignored
+            || self.user.matched(path, is_dir).is_ignore()
+            || (self.respect_gitignore && self.gitignored(path, is_dir));
+        self.ignored.insert(path.to_path_buf(), ignored);
+        ignored
+    }
+
+    // The deepest ignore file with a matching rule decides, as in git
+    fn gitignored(&mut self, path: &Path, is_dir: bool) -> bool {
+        let folders: Vec<PathBuf> = path.ancestors().skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in folders {
+            let matched = self.folder_rules(&dir).matched(path, is_dir);
+            if !matched.is_none() {
+                return matched.is_ignore();
+            }
+        }
+        self.global.matched(path, is_dir).is_ignore()
+    }
+
+    fn folder_rules(&mut self, dir: &Path) -> &Gitignore {
+        let root = self.root.clone();
+        self.folders.entry(dir.to_path_buf()).or_insert_with(|| {
+            let mut builder = GitignoreBuilder::new(dir);
+            if dir == root {
+                builder.add(dir.join(".git").join("info").join("exclude"));
+            }
+            builder.add(dir.join(".gitignore"));
+            builder.add(dir.join(".ignore"));
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        })
+    }
+}
+
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == ".gitignore" || name == ".ignore")
+}
+
+// Files under `root` that searches should look at
+pub fn workspace_files(root: &Path, settings: &ExcludeSettings) -> impl Iterator<Item = PathBuf> {
+    let mut user = GitignoreBuilder::new(root);
+    for glob in &settings.globs {
+        let _ = user.add_line(None, glob);
+    }
+    let user = user.build().unwrap_or_else(|_| Gitignore::empty());
+    let respect = settings.respect_gitignore;
+    WalkBuilder::new(root)
+        .hidden(!settings.show_hidden)
+        .git_ignore(respect)
+        .git_global(respect)
+        .git_exclude(respect)
+        .ignore(respect)
+        .parents(respect)
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            !user.matched(entry.path(), is_dir).is_ignore()
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
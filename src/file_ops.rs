@@ -1,33 +1,518 @@
+use std::collections::BTreeSet;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use rfd::FileDialog;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
 use crate::app::CodeEditorApp;
+use crate::autosave::AutoSave;
+use crate::completion;
+use crate::file_tree;
+use crate::hex;
+use crate::lsp;
+use crate::notifications::{self, Action, Level};
+use crate::panes;
+use crate::recent::{self, RecentKind};
+use crate::search;
+use crate::session;
+use crate::watcher;
+
+// Files are read whole into memory, so anything larger is refused
+const MAX_OPEN_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveSettings {
+    // Copy the previous version to "<name>.bak" before each save
+    pub keep_backup: bool,
+    pub auto_save: AutoSave,
+    // Quiet time after the last edit before an `AfterDelay` auto-save
+    pub auto_save_delay_ms: u64,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            keep_backup: false,
+            auto_save: AutoSave::Off,
+            auto_save_delay_ms: 1000,
+        }
+    }
+}
+
+pub fn new_file(app: &mut CodeEditorApp) {
+    let doc = panes::add_document(app, None, String::new());
+    panes::show(app, doc);
+}
+
+pub fn open_folder(app: &mut CodeEditorApp, path: PathBuf) {
+    recent::add(app, RecentKind::Folder, &path);
+    if app.root_folder.as_ref() != Some(&path) {
+        session::switch_workspace(app, path);
+    }
+}
+
+pub fn pick_folder(app: &mut CodeEditorApp) {
+    if let Some(path) = FileDialog::new().pick_folder() {
+        open_folder(app, path);
+    }
+}
+
+// Files dropped on the window open as tabs; a dropped folder becomes the explorer root
+pub fn open_dropped(app: &mut CodeEditorApp, paths: Vec<PathBuf>) {
+    for path in paths {
+        if path.is_dir() {
+            open_folder(app, path);
+        } else {
+            open_path(app, path);
+        }
+    }
+}
 
 pub fn open_file(app: &mut CodeEditorApp) {
     if let Some(path) = FileDialog::new().pick_file() {
-        if let Ok(content) = fs::read_to_string(&path) {
-            app.text = content;
-            app.file_path = Some(path.clone());
-            if !app.open_files.contains(&path) {
-                app.open_files.push(path);
-                app.current_file_index = app.open_files.len() - 1;
+        open_path(app, path);
+    }
+}
+
+pub fn save_file(app: &mut CodeEditorApp) {
+    let doc = active_document(app);
+    save_document(app, doc);
+}
+
+pub fn save_file_as(app: &mut CodeEditorApp) {
+    let doc = active_document(app);
+    save_document_as(app, doc);
+}
+
+// Untitled documents, and text converted from a binary, are asked for a path
+pub fn save_document(app: &mut CodeEditorApp, doc: usize) {
+    match app.panes.documents.get(&doc).filter(|d| !d.lossy).and_then(|d| d.path.clone()) {
+        Some(path) => {
+            write_document(app, doc, path, true);
+        }
+        None => save_document_as(app, doc),
+    }
+}
+
+fn save_document_as(app: &mut CodeEditorApp, doc: usize) {
+    let Some(document) = app.panes.documents.get(&doc) else { return };
+    let mut dialog = FileDialog::new().set_file_name(&document.name());
+    if let Some(dir) = document.path.as_ref().and_then(|p| p.parent()).or(app.root_folder.as_deref()) {
+        dialog = dialog.set_directory(dir);
+    }
+    if let Some(path) = dialog.save_file() {
+        write_document(app, doc, path, true);
+    }
+}
+
+// Saves every modified buffer; untitled ones are shown and asked for a name in turn
+pub fn save_all(app: &mut CodeEditorApp) {
+    let dirty: Vec<usize> = app.panes.documents.keys().copied().filter(|&doc| panes::is_dirty(app, doc)).collect();
+    for doc in dirty {
+        match app.panes.documents.get(&doc).and_then(|d| d.path.clone()) {
+            Some(path) => {
+                write_document(app, doc, path, true);
+            }
+            None => {
+                panes::show(app, doc);
+                save_file_as(app);
             }
         }
     }
 }
 
-pub fn save_file(app: &mut CodeEditorApp) {
-    if let Some(path) = &app.file_path {
-        if fs::write(path, &app.text).is_ok() {
-            let mut output = app.terminal_output.lock().unwrap();
-            output.push_str(&format!("✓ Saved: {}\n> ", path.display()));
+// Text typed with no tab open becomes an untitled document so it can be saved
+fn active_document(app: &mut CodeEditorApp) -> usize {
+    if let Some(doc) = app.panes.active_doc {
+        return doc;
+    }
+    let text = std::mem::take(&mut app.text);
+    let doc = panes::add_document(app, None, text);
+    panes::show(app, doc);
+    doc
+}
+
+// Refuses to overwrite the document's own file if something else changed it since it was loaded,
+// or if its text was decoded with replacement characters
+fn write_document(app: &mut CodeEditorApp, doc: usize, path: PathBuf, announce: bool) -> bool {
+    let own_file = app.panes.documents.get(&doc).is_some_and(|d| d.path.as_ref() == Some(&path));
+    if own_file && app.panes.documents.get(&doc).is_some_and(|d| d.lossy) {
+        if announce {
+            let message = format!("{} is not valid UTF-8 and saving the text would change its bytes; use Save As to write it elsewhere", path.display());
+            notifications::notify(app, Level::Warning, message, Vec::new());
+        }
+        return false;
+    }
+    if own_file && watcher::changed_on_disk(app, doc) {
+        if !app.watcher.blocked_saves.contains(&doc) {
+            app.watcher.blocked_saves.push(doc);
+        }
+        return false;
+    }
+    write_unchecked(app, doc, path, announce)
+}
+
+// Saves a document to its own file without logging success, for auto-save
+pub fn save_quietly(app: &mut CodeEditorApp, doc: usize) -> bool {
+    let Some(path) = app.panes.documents.get(&doc).and_then(|d| d.path.clone()) else { return false };
+    write_document(app, doc, path, false)
+}
+
+// Tries a failed save again, unless the tab was closed in the meantime
+pub fn save_to(app: &mut CodeEditorApp, doc: usize, path: PathBuf) {
+    if app.panes.documents.contains_key(&doc) {
+        write_document(app, doc, path, true);
+    }
+}
+
+// Saves over an externally changed file once the user has confirmed it
+pub fn overwrite(app: &mut CodeEditorApp, doc: usize) {
+    if let Some(path) = app.panes.documents.get(&doc).and_then(|d| d.path.clone()) {
+        write_unchecked(app, doc, path, true);
+    }
+}
+
+fn write_unchecked(app: &mut CodeEditorApp, doc: usize, path: PathBuf, announce: bool) -> bool {
+    match write_atomic(&path, panes::contents_of(app, doc), app.config.save.keep_backup) {
+        Ok(()) => {
+            panes::mark_saved(app, doc, path.clone());
+            watcher::resolved(app, doc);
+            if announce {
+                app.terminal_output.lock().unwrap().push_str(&format!("✓ Saved: {}\n> ", path.display()));
+            }
+            lsp::did_save(app, &path);
+            true
+        }
+        Err(e) => {
+            let message = format!("Could not save {}: {} (the file on disk was left unchanged)", path.display(), e);
+            notifications::notify(app, Level::Error, message, vec![Action::RetrySave(doc, path)]);
+            false
+        }
+    }
+}
+
+// Writes a temporary file in the same folder, flushes it to disk and renames it over the
+// original, so a crash or a full disk leaves either the old or the new file, never a truncated one
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>, keep_backup: bool) -> io::Result<()> {
+    // Saving through a symlink replaces the file it points to and keeps the link
+    let is_link = fs::symlink_metadata(path).map(|m| m.file_type().is_symlink()).unwrap_or(false);
+    let target = if is_link { fs::canonicalize(path)? } else { path.to_path_buf() };
+    let dir = target.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let existed = target.exists();
+    // The temporary file is private to the user, so a new file takes the permissions
+    // of an empty placeholder created the normal way
+    if !existed {
+        fs::OpenOptions::new().write(true).create_new(true).open(&target)?;
+    }
+    let result = replace(&target, dir, contents.as_ref(), keep_backup && existed);
+    if result.is_err() && !existed {
+        let _ = fs::remove_file(&target);
+    }
+    result
+}
+
+fn replace(target: &Path, dir: &Path, contents: &[u8], keep_backup: bool) -> io::Result<()> {
+    let permissions = fs::metadata(target)?.permissions();
+    let mut temp = tempfile::Builder::new().prefix(".codonn-save").tempfile_in(dir)?;
+    temp.write_all(contents)?;
+    temp.as_file().sync_all()?;
+    fs::set_permissions(temp.path(), permissions)?;
+    if keep_backup {
+        let mut name = target.file_name().unwrap_or_default().to_os_string();
+        name.push(".bak");
+        fs::copy(target, target.with_file_name(name))?;
+    }
+    temp.persist(target).map_err(|e| e.error)?;
+    sync_dir(dir);
+    Ok(())
+}
+
+// Makes the rename itself durable
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
+// Shows the file in the active group, reusing its document if it is already open
+pub fn open_path(app: &mut CodeEditorApp, path: PathBuf) {
+    if let Some(doc) = app.panes.find(&path) {
+        recent::add(app, RecentKind::File, &path);
+        panes::show(app, doc);
+        return;
+    }
+    if let Ok(metadata) = fs::metadata(&path) {
+        if metadata.len() > MAX_OPEN_SIZE {
+            let message = format!("{} is too large to open ({} MiB, the limit is {} MiB)", path.display(), metadata.len() / (1024 * 1024), MAX_OPEN_SIZE / (1024 * 1024));
+            return notifications::notify(app, Level::Error, message, Vec::new());
         }
     }
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let message = format!("Could not open {}: {}", path.display(), e);
+            return notifications::notify(app, Level::Error, message, vec![Action::RetryOpen(path)]);
+        }
+    };
+    recent::add(app, RecentKind::File, &path);
+    let previous = app.panes.active_doc;
+    // Files that are not text open in the hex view
+    let doc = match String::from_utf8(bytes) {
+        Ok(text) if !hex::looks_binary(text.as_bytes()) => panes::add_document(app, Some(path), text),
+        Ok(text) => panes::add_binary_document(app, path, text.into_bytes()),
+        Err(e) => panes::add_binary_document(app, path, e.into_bytes()),
+    };
+    panes::show(app, doc);
+    // An empty untitled buffer is replaced rather than kept as an extra tab
+    if let Some(previous) = previous {
+        let unused = app.panes.documents.get(&previous).is_some_and(|d| d.path.is_none()) && !panes::is_dirty(app, previous);
+        if unused {
+            panes::close_document(app, previous);
+        }
+    }
+}
+
+fn report(app: &mut CodeEditorApp, action: &str, path: &Path, e: impl std::fmt::Display) {
+    notifications::notify(app, Level::Error, format!("Could not {} {}: {}", action, path.display(), e), Vec::new());
+}
+
+// Shows the change in the explorer now instead of waiting for the file watcher
+fn refresh_tree(app: &mut CodeEditorApp, paths: &[&Path]) {
+    let changed: BTreeSet<PathBuf> = paths.iter().map(|p| p.to_path_buf()).collect();
+    file_tree::apply_changes(app, &changed);
+    search::files_changed(app, &changed);
+    completion::files_changed(app, &changed);
+    recent::paths_changed(app, &changed);
 }
 
-pub fn load_file(app: &mut CodeEditorApp, path: PathBuf) {
-    if let Ok(content) = fs::read_to_string(&path) {
-        app.text = content;
-        app.file_path = Some(path);
+pub fn create_file(app: &mut CodeEditorApp, path: &Path) {
+    let created = path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::OpenOptions::new().write(true).create_new(true).open(path));
+    match created {
+        Ok(_) => {
+            refresh_tree(app, &[path]);
+            open_path(app, path.to_path_buf());
+        }
+        Err(e) => report(app, "create", path, e),
+    }
+}
+
+pub fn create_folder(app: &mut CodeEditorApp, path: &Path) {
+    if path.exists() {
+        return report(app, "create", path, "it already exists");
+    }
+    match fs::create_dir_all(path) {
+        Ok(()) => refresh_tree(app, &[path]),
+        Err(e) => report(app, "create", path, e),
+    }
+}
+
+// Renames or moves a file or folder; open tabs and explorer state follow it
+pub fn rename_path(app: &mut CodeEditorApp, from: &Path, to: &Path) -> bool {
+    if to.exists() && !same_file(from, to) {
+        report(app, "move to", to, "it already exists");
+        return false;
+    }
+    if let Err(e) = fs::rename(from, to) {
+        report(app, "rename", from, e);
+        return false;
+    }
+    panes::rename_path(app, from, to);
+    file_tree::renamed(app, from, to);
+    refresh_tree(app, &[from, to]);
+    true
+}
+
+// A case-only rename on a case-insensitive file system finds the file itself at `to`
+fn same_file(from: &Path, to: &Path) -> bool {
+    matches!((fs::canonicalize(from), fs::canonicalize(to)), (Ok(from), Ok(to)) if from == to)
+}
+
+// Copies next to the original as "name copy.ext", "name copy 2.ext", ...
+pub fn duplicate_path(app: &mut CodeEditorApp, path: &Path) {
+    let Some(parent) = path.parent() else { return };
+    let (stem, extension) = match (path.is_file(), path.file_stem(), path.extension()) {
+        (true, Some(stem), Some(ext)) => (stem.to_string_lossy().to_string(), format!(".{}", ext.to_string_lossy())),
+        _ => (path.file_name().unwrap_or_default().to_string_lossy().to_string(), String::new()),
+    };
+    let target = (1..)
+        .map(|n| match n {
+            1 => parent.join(format!("{} copy{}", stem, extension)),
+            n => parent.join(format!("{} copy {}{}", stem, n, extension)),
+        })
+        .find(|p| !p.exists())
+        .unwrap();
+    match copy_recursive(path, &target) {
+        Ok(()) => {
+            refresh_tree(app, &[&target]);
+            app.file_tree.selected = Some(target);
+        }
+        Err(e) => report(app, "duplicate", path, e),
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_file() {
+        return fs::copy(from, to).map(|_| ());
+    }
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+// Moves the entry to the system trash; the last one can be restored with `undo_delete`
+pub fn delete_path(app: &mut CodeEditorApp, path: &Path) {
+    match trash::delete(path) {
+        Ok(()) => {
+            let undo = if CAN_RESTORE { vec![Action::UndoDelete] } else { Vec::new() };
+            notifications::notify(app, Level::Info, format!("Moved {} to the trash", path.display()), undo);
+            app.file_tree.trashed = Some(path.to_path_buf());
+            refresh_tree(app, &[path]);
+        }
+        Err(e) => report(app, "delete", path, e),
+    }
+}
+
+pub fn undo_delete(app: &mut CodeEditorApp) {
+    let Some(path) = app.file_tree.trashed.take() else { return };
+    match restore_from_trash(&path) {
+        Ok(()) => {
+            notifications::notify(app, Level::Info, format!("Restored {}", path.display()), Vec::new());
+            refresh_tree(app, &[&path]);
+        }
+        Err(e) => report(app, "restore", &path, e),
+    }
+}
+
+pub const CAN_RESTORE: bool = cfg!(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+));
+
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+fn restore_from_trash(path: &Path) -> Result<(), String> {
+    let items = trash::os_limited::list().map_err(|e| e.to_string())?;
+    let item = items.into_iter()
+        .filter(|item| item.original_path() == path)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or("it is no longer in the trash")?;
+    trash::os_limited::restore_all([item]).map_err(|e| e.to_string())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+fn restore_from_trash(_path: &Path) -> Result<(), String> {
+    Err("restoring from the trash is not supported on this platform".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leftovers(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with(".codonn-save"))
+            .collect()
+    }
+
+    #[test]
+    fn atomic_write_creates_a_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+        write_atomic(&path, "hello", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello");
+        // There was nothing to back up
+        assert!(!dir.path().join("new.txt.bak").exists());
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn atomic_write_keeps_a_backup_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, "new", false).unwrap();
+        assert!(!dir.path().join("a.txt.bak").exists());
+        write_atomic(&path, "newer", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "newer");
+        assert_eq!(fs::read_to_string(dir.path().join("a.txt.bak")).unwrap(), "new");
+    }
+
+    #[test]
+    fn failed_atomic_write_leaves_the_file_and_no_temporary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "old").unwrap();
+        // The backup cannot be written over a folder, which fails the save after the temporary exists
+        fs::create_dir(dir.path().join("a.txt.bak")).unwrap();
+        assert!(write_atomic(&path, "new", true).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(leftovers(dir.path()).is_empty());
+
+        let missing = dir.path().join("missing").join("b.txt");
+        assert!(write_atomic(&missing, "new", false).is_err());
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.sh");
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o751)).unwrap();
+        write_atomic(&path, "#!/bin/sh\necho hi\n", false).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o751);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_through_a_symlink_replaces_the_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("real.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        write_atomic(&link, "new", false).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(leftovers(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn rename_refuses_another_file_but_not_the_file_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.rs"), dir.path().join("b.rs"));
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+        let mut app = CodeEditorApp::default();
+        assert!(!rename_path(&mut app, &a, &b));
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+
+        // What a case-insensitive file system reports for foo.rs -> Foo.rs
+        assert!(same_file(&a, &dir.path().join(".").join("a.rs")));
+        assert!(!same_file(&a, &b));
+        assert!(rename_path(&mut app, &a, &dir.path().join(".").join("a.rs")));
+        assert!(a.exists());
     }
 }
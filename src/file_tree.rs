@@ -1,82 +1,938 @@
 use eframe::egui;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
 use crate::app::CodeEditorApp;
+use crate::diff;
+use crate::editor;
+use crate::excludes::{self, ExcludeSettings, Excludes};
 use crate::file_ops;
+use crate::language::Language;
+use crate::notifications::{self, Level};
+use crate::search;
+use crate::terminal;
 
-pub fn render_tree(app: &mut CodeEditorApp, ui: &mut egui::Ui, path: &Path) {
-    let mut entries: Vec<_> = WalkDir::new(path)
-        .min_depth(1)
-        .max_depth(4)
-        .into_iter()
+#[derive(Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    // Matched by .gitignore or an exclude pattern; shown dimmed when ignored files are shown
+    pub ignored: bool,
+}
+
+#[derive(Clone)]
+pub struct Row {
+    pub entry: Entry,
+    pub depth: usize,
+    // Name field for an entry being created inside `entry.path`
+    pub new_entry: bool,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum EditTarget {
+    Rename(PathBuf),
+    NewFile(PathBuf),
+    NewFolder(PathBuf),
+}
+
+// A name typed in place of a tree row
+pub struct InlineEdit {
+    pub target: EditTarget,
+    pub name: String,
+    focus: bool,
+}
+
+// Folder contents read from disk once, on first expand
+#[derive(Default)]
+pub struct FileTree {
+    root: Option<PathBuf>,
+    excludes: Option<Excludes>,
+    children: HashMap<PathBuf, Vec<Entry>>,
+    // Visible rows in display order, rebuilt only when expansion or contents change
+    rows: Vec<Row>,
+    rows_dirty: bool,
+    pub selected: Option<PathBuf>,
+    pub edit: Option<InlineEdit>,
+    // Last entry moved to the trash, offered for undo
+    pub trashed: Option<PathBuf>,
+    scroll_to: Option<PathBuf>,
+    // Entry being dragged to another folder
+    dragged: Option<PathBuf>,
+    // (from, to) waiting for the user to confirm the move
+    pending_move: Option<(PathBuf, PathBuf)>,
+    // Narrows the tree to entries whose name contains it, with their folders
+    pub filter: String,
+    // Arrow keys move the selection while set; cleared by clicking elsewhere
+    focused: bool,
+    // Scroll position and height of the list on the last frame
+    offset: f32,
+    viewport: f32,
+}
+
+impl FileTree {
+    fn reset(&mut self, root: &Path, settings: &ExcludeSettings) {
+        self.root = Some(root.to_path_buf());
+        self.excludes = Some(Excludes::new(root, settings));
+        self.children.clear();
+        self.rows.clear();
+        self.rows_dirty = true;
+        self.selected = None;
+        self.edit = None;
+    }
+
+    fn children(&mut self, dir: &Path) -> &[Entry] {
+        if !self.children.contains_key(dir) {
+            let entries = read_dir_sorted(dir, self.excludes.as_mut());
+            self.children.insert(dir.to_path_buf(), entries);
+        }
+        &self.children[dir]
+    }
+
+    // New ignore rules change which entries are ignored, so every loaded folder is read again
+    fn reload_excludes(&mut self, settings: &ExcludeSettings) {
+        let Some(root) = &self.root else { return };
+        self.excludes = Some(Excludes::new(root, settings));
+        self.children.clear();
+        self.rows_dirty = true;
+    }
+
+    // Flattens the expanded part of the tree, loading folders as they are reached
+    fn rebuild_rows(&mut self, expanded: &HashMap<PathBuf, bool>, settings: &ExcludeSettings, index: Option<&[PathBuf]>) {
+        self.rows.clear();
+        let Some(root) = self.root.clone() else { return };
+        let creating_in = match self.edit.as_ref().map(|e| &e.target) {
+            Some(EditTarget::NewFile(dir)) => Some((dir.clone(), false)),
+            Some(EditTarget::NewFolder(dir)) => Some((dir.clone(), true)),
+            _ => None,
+        };
+        let new_entry_row = |dir: &Path, is_dir: bool, depth: usize| Row {
+            entry: Entry { path: dir.to_path_buf(), is_dir, ignored: false },
+            depth,
+            new_entry: true,
+        };
+
+        if !self.filter.is_empty() {
+            let query = self.filter.to_lowercase();
+            self.rows = index.map(|files| filtered_rows(&root, files, &query)).unwrap_or_default();
+            self.rows_dirty = false;
+            return;
+        }
+
+        if let Some((dir, is_dir)) = &creating_in {
+            if *dir == root {
+                self.rows.push(new_entry_row(dir, *is_dir, 1));
+            }
+        }
+        let visible = |entry: &&Entry| is_visible(entry, settings);
+        let mut stack: Vec<Row> = self.children(&root).iter().rev()
+            .filter(visible)
+            .map(|entry| Row { entry: entry.clone(), depth: 1, new_entry: false })
+            .collect();
+        while let Some(row) = stack.pop() {
+            let depth = row.depth + 1;
+            if row.entry.is_dir && expanded.get(&row.entry.path).copied().unwrap_or(false) {
+                let path = row.entry.path.clone();
+                stack.extend(self.children(&path).iter().rev().filter(visible).map(|entry| Row { entry: entry.clone(), depth, new_entry: false }));
+            }
+            let creating_here = creating_in.as_ref()
+                .filter(|(dir, _)| *dir == row.entry.path)
+                .map(|(dir, is_dir)| new_entry_row(dir, *is_dir, depth));
+            self.rows.push(row);
+            self.rows.extend(creating_here);
+        }
+        self.rows_dirty = false;
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        let selected = self.selected.as_ref()?;
+        self.rows.iter().position(|r| !r.new_entry && r.entry.path == *selected)
+    }
+}
+
+// Matching entries with the folders leading to them, regardless of expansion. They come from
+// the sorted workspace index rather than the disk, so ignored files are left out
+fn filtered_rows(root: &Path, files: &[PathBuf], query: &str) -> Vec<Row> {
+    let matches = |path: &Path| path.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().contains(query));
+    let below_root = |path: &&Path| *path != root;
+    let mut shown: BTreeSet<&Path> = BTreeSet::new();
+    for file in files.iter().filter(|f| f.starts_with(root)) {
+        let Some(deepest) = file.ancestors().take_while(below_root).find(|p| matches(p)) else { continue };
+        for path in deepest.ancestors().take_while(below_root) {
+            // Its folders were added along with it
+            if !shown.insert(path) {
+                break;
+            }
+        }
+    }
+
+    let is_dir = |path: &Path| files.binary_search_by(|f| f.as_path().cmp(path)).is_err();
+    let mut children: BTreeMap<&Path, Vec<(bool, &Path)>> = BTreeMap::new();
+    for path in shown {
+        children.entry(path.parent().unwrap_or(root)).or_default().push((is_dir(path), path));
+    }
+    // Folders first, then files, each alphabetically
+    for entries in children.values_mut() {
+        entries.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.file_name().cmp(&b.1.file_name())));
+    }
+
+    let mut rows = Vec::new();
+    let mut stack: Vec<(bool, &Path, usize)> = children.get(root).into_iter().flatten().rev().map(|&(d, p)| (d, p, 1)).collect();
+    while let Some((is_dir, path, depth)) = stack.pop() {
+        rows.push(Row { entry: Entry { path: path.to_path_buf(), is_dir, ignored: false }, depth, new_entry: false });
+        stack.extend(children.get(path).into_iter().flatten().rev().map(|&(d, p)| (d, p, depth + 1)));
+    }
+    rows
+}
+
+// Whether the rows after the one at `index` are inside it
+fn shows_children(rows: &[Row], index: usize) -> bool {
+    rows.get(index + 1).is_some_and(|next| next.depth > rows[index].depth)
+}
+
+fn is_visible(entry: &Entry, settings: &ExcludeSettings) -> bool {
+    (settings.show_hidden || !excludes::is_hidden(&entry.path)) && (settings.show_ignored || !entry.ignored)
+}
+
+// Folders first, then files, each alphabetically
+fn read_dir_sorted(dir: &Path, mut excludes: Option<&mut Excludes>) -> Vec<Entry> {
+    let Ok(read) = fs::read_dir(dir) else { return Vec::new() };
+    let mut entries: Vec<Entry> = read
         .filter_map(|e| e.ok())
+        .map(|e| {
+            let path = e.path();
+            let is_dir = e.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            let ignored = excludes.as_mut().is_some_and(|x| x.is_ignored(&path, is_dir));
+            Entry { path, is_dir, ignored }
+        })
         .collect();
-    
-    entries.sort_by(|a, b| {
-        let a_is_dir = a.file_type().is_dir();
-        let b_is_dir = b.file_type().is_dir();
-        
-        if a_is_dir != b_is_dir {
-            b_is_dir.cmp(&a_is_dir)
-        } else {
-            a.file_name().cmp(b.file_name())
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.file_name().cmp(&b.path.file_name())));
+    entries
+}
+
+// Re-reads the loaded folders touched by file system events. Expansion and selection are
+// keyed by path, so they carry over to the new entries
+pub fn apply_changes(app: &mut CodeEditorApp, paths: &BTreeSet<PathBuf>) {
+    let tree = &mut app.file_tree;
+    if paths.iter().any(|p| excludes::is_ignore_file(p)) {
+        tree.reload_excludes(&app.config.excludes);
+    }
+    let mut dirs = BTreeSet::new();
+    for path in paths {
+        if let Some(parent) = path.parent() {
+            dirs.insert(parent.to_path_buf());
+        }
+        if !path.exists() {
+            tree.children.retain(|dir, _| !dir.starts_with(path));
+            if tree.selected.as_ref().is_some_and(|s| s.starts_with(path)) {
+                tree.selected = None;
+            }
+        }
+    }
+    for dir in dirs {
+        let Some(entries) = tree.children.get_mut(&dir) else { continue };
+        let fresh = read_dir_sorted(&dir, tree.excludes.as_mut());
+        if *entries != fresh {
+            *entries = fresh;
+            tree.rows_dirty = true;
+        }
+    }
+}
+
+// Called after the exclude settings were edited
+pub fn settings_changed(app: &mut CodeEditorApp, rules_changed: bool) {
+    if rules_changed {
+        app.file_tree.reload_excludes(&app.config.excludes);
+    }
+    app.file_tree.rows_dirty = true;
+}
+
+pub fn set_expanded(app: &mut CodeEditorApp, path: &Path, expanded: bool) {
+    app.file_tree_expanded.insert(path.to_path_buf(), expanded);
+    app.file_tree.rows_dirty = true;
+}
+
+// Carries expansion and selection over to a renamed or moved entry
+pub fn renamed(app: &mut CodeEditorApp, from: &Path, to: &Path) {
+    let moved = |path: &Path| path.strip_prefix(from).ok().map(|rest| {
+        if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) }
+    });
+    let expanded: Vec<(PathBuf, bool)> = app.file_tree_expanded.iter()
+        .filter_map(|(path, open)| moved(path).map(|p| (p, *open)))
+        .collect();
+    app.file_tree_expanded.retain(|path, _| !path.starts_with(from));
+    app.file_tree_expanded.extend(expanded);
+    if let Some(selected) = app.file_tree.selected.as_deref().and_then(moved) {
+        app.file_tree.selected = Some(selected);
+    }
+    app.file_tree.rows_dirty = true;
+}
+
+// Expands the folders above the file, then selects it and scrolls it into view
+pub fn reveal(app: &mut CodeEditorApp, path: &Path) {
+    let Some(root) = app.root_folder.clone() else { return };
+    if !path.starts_with(&root) {
+        return;
+    }
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != root) {
+        app.file_tree_expanded.insert(dir.to_path_buf(), true);
+    }
+    app.file_tree.selected = Some(path.to_path_buf());
+    app.file_tree.scroll_to = Some(path.to_path_buf());
+    app.file_tree.rows_dirty = true;
+}
+
+fn start_edit(app: &mut CodeEditorApp, target: EditTarget) {
+    let name = match &target {
+        EditTarget::Rename(path) => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        EditTarget::NewFile(dir) | EditTarget::NewFolder(dir) => {
+            set_expanded(app, dir, true);
+            String::new()
+        }
+    };
+    app.file_tree.edit = Some(InlineEdit { target, name, focus: true });
+    app.file_tree.rows_dirty = true;
+}
+
+fn commit_edit(app: &mut CodeEditorApp) {
+    let Some(edit) = app.file_tree.edit.take() else { return };
+    app.file_tree.rows_dirty = true;
+    let name = edit.name.trim();
+    if name.is_empty() {
+        return;
+    }
+    if !is_plain_name(name) {
+        let message = format!("'{}' is not a valid name; names cannot contain / or \\, or be . or ..", name);
+        notifications::notify(app, Level::Warning, message, Vec::new());
+        return;
+    }
+    match edit.target {
+        EditTarget::Rename(path) => {
+            let to = path.with_file_name(name);
+            if to != path && file_ops::rename_path(app, &path, &to) {
+                app.file_tree.selected = Some(to);
+            }
+        }
+        EditTarget::NewFile(dir) => {
+            let path = dir.join(name);
+            file_ops::create_file(app, &path);
+            app.file_tree.selected = Some(path);
+        }
+        EditTarget::NewFolder(dir) => {
+            let path = dir.join(name);
+            file_ops::create_folder(app, &path);
+            app.file_tree.selected = Some(path);
+        }
+    }
+}
+
+// A single file or folder name, so typing one never reaches outside the folder it is typed in
+fn is_plain_name(name: &str) -> bool {
+    !name.contains(['/', '\\']) && name != "." && name != ".."
+}
+
+fn cancel_edit(app: &mut CodeEditorApp) {
+    app.file_tree.edit = None;
+    app.file_tree.rows_dirty = true;
+}
+
+// Folder that new entries go into: the selected folder, the selected file's folder, or the root
+fn target_folder(app: &CodeEditorApp, root: &Path) -> PathBuf {
+    match &app.file_tree.selected {
+        Some(path) if path.is_dir() => path.clone(),
+        Some(path) => path.parent().map_or_else(|| root.to_path_buf(), Path::to_path_buf),
+        None => root.to_path_buf(),
+    }
+}
+
+pub fn render_tree(app: &mut CodeEditorApp, ui: &mut egui::Ui, path: &Path) {
+    if app.file_tree.root.as_deref() != Some(path) {
+        app.file_tree.reset(path, &app.config.excludes);
+    }
+    render_toolbar(app, ui, path);
+    let index = search::workspace_index(app);
+    if app.file_tree.rows_dirty {
+        app.file_tree.rebuild_rows(&app.file_tree_expanded, &app.config.excludes, index.as_ref().map(|files| files.as_slice()));
+    }
+    if !app.file_tree.filter.is_empty() && app.file_tree.rows.is_empty() {
+        let message = if index.is_some() { "No matching files" } else { "Listing files…" };
+        ui.label(egui::RichText::new(message).color(ui.visuals().weak_text_color()));
+    }
+
+    // Only the rows in view are laid out, so very large folders stay cheap to draw
+    let row_height = ui.spacing().interact_size.y;
+    let total = app.file_tree.rows.len();
+    let mut scroll_area = egui::ScrollArea::vertical()
+        .id_source("file_tree")
+        .max_height(400.0)
+        .auto_shrink([false, true]);
+    // Scrolls just far enough to bring the target row into view
+    if let Some(target) = app.file_tree.scroll_to.take() {
+        if let Some(index) = app.file_tree.rows.iter().position(|r| r.entry.path == target && !r.new_entry) {
+            let row_step = row_height + ui.spacing().item_spacing.y;
+            let top = index as f32 * row_step;
+            let tree = &app.file_tree;
+            if top < tree.offset {
+                scroll_area = scroll_area.vertical_scroll_offset(top);
+            } else if top + row_height > tree.offset + tree.viewport {
+                scroll_area = scroll_area.vertical_scroll_offset(top + row_height - tree.viewport);
+            }
+        }
+    }
+    // Folder each visible row accepts drops into
+    let mut drop_targets = Vec::new();
+    let output = scroll_area.show_rows(ui, row_height, total, |ui, range| {
+        let rows = app.file_tree.rows[range.clone()].to_vec();
+        for (index, row) in range.zip(rows) {
+            let folder = if row.entry.is_dir { row.entry.path.clone() } else { row.entry.path.parent().unwrap_or(path).to_path_buf() };
+            let top = ui.cursor().top();
+            let renaming = !row.new_entry && app.file_tree.edit.as_ref()
+                .is_some_and(|e| e.target == EditTarget::Rename(row.entry.path.clone()));
+            if row.new_entry || renaming {
+                let icon = if row.entry.is_dir && !renaming { "📁" } else { "📄" };
+                render_inline_edit(app, ui, row.depth, icon);
+            } else if row.entry.is_dir {
+                let has_children = shows_children(&app.file_tree.rows, index);
+                render_folder(app, ui, &row.entry, row.depth, has_children);
+            } else {
+                render_file(app, ui, &row.entry, row.depth);
+            }
+            let rect = egui::Rect::from_x_y_ranges(ui.max_rect().x_range(), top..=ui.min_rect().bottom());
+            drop_targets.push((rect, folder));
         }
     });
-    
-    for entry in entries {
-        let path_buf = entry.path().to_path_buf();
-        let depth = entry.depth();
-        
-        if entry.file_type().is_dir() {
-            render_folder(app, ui, &path_buf, depth);
+    app.file_tree.offset = output.state.offset.y;
+    app.file_tree.viewport = output.inner_rect.height();
+
+    let ctx = ui.ctx().clone();
+    let pressed_outside = ctx.input(|i| {
+        i.pointer.any_pressed() && !i.pointer.interact_pos().is_some_and(|p| output.inner_rect.contains(p))
+    });
+    if pressed_outside {
+        app.file_tree.focused = false;
+    }
+    handle_tree_keys(app, &ctx);
+    handle_tree_drop(app, &ctx, &drop_targets, output.inner_rect, path);
+    render_move_confirmation(app, &ctx);
+}
+
+// Moves keyboard control to the tree, e.g. when leaving the filter field with ↓ or Enter
+pub fn focus_tree(app: &mut CodeEditorApp) {
+    app.file_tree.focused = true;
+    if app.file_tree.selected_index().is_none() {
+        let first = app.file_tree.rows.iter().find(|r| !r.new_entry).map(|r| r.entry.path.clone());
+        select(app, first);
+    }
+}
+
+// The filtered tree is built from the workspace index, so it follows each new listing
+pub fn index_changed(app: &mut CodeEditorApp) {
+    if !app.file_tree.filter.is_empty() {
+        app.file_tree.rows_dirty = true;
+    }
+}
+
+pub fn set_filter(app: &mut CodeEditorApp, filter: String) {
+    app.file_tree.filter = filter;
+    app.file_tree.rows_dirty = true;
+}
+
+fn select(app: &mut CodeEditorApp, path: Option<PathBuf>) {
+    app.file_tree.scroll_to = path.clone();
+    app.file_tree.selected = path;
+}
+
+// ↑/↓ move, → expands or steps into a folder, ← collapses or steps out, Enter opens,
+// F2 renames and Delete moves to the trash; only while the tree has focus and no text field does
+fn handle_tree_keys(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let text_focused = ctx.memory(|m| m.focus().is_some());
+    if !app.file_tree.focused || text_focused || app.file_tree.edit.is_some() || app.file_tree.pending_move.is_some() {
+        return;
+    }
+    let none = egui::Modifiers::NONE;
+    let pressed = |key| ctx.input_mut(|i| i.consume_key(none, key));
+    let rows = &app.file_tree.rows;
+    let current = app.file_tree.selected_index();
+    let row_at = |i: usize| rows.get(i).filter(|r| !r.new_entry).map(|r| r.entry.clone());
+    let Some(index) = current else {
+        if pressed(egui::Key::ArrowDown) || pressed(egui::Key::ArrowUp) {
+            let first = row_at(0).map(|e| e.path);
+            select(app, first);
+        }
+        return;
+    };
+    let Some(entry) = row_at(index) else { return };
+    let expanded = app.file_tree_expanded.get(&entry.path).copied().unwrap_or(false);
+
+    if pressed(egui::Key::ArrowDown) {
+        let next = (index + 1..rows.len()).find_map(row_at).map(|e| e.path);
+        if next.is_some() {
+            select(app, next);
+        }
+    } else if pressed(egui::Key::ArrowUp) {
+        let previous = (0..index).rev().find_map(row_at).map(|e| e.path);
+        if previous.is_some() {
+            select(app, previous);
+        }
+    } else if pressed(egui::Key::Home) {
+        let first = (0..rows.len()).find_map(row_at).map(|e| e.path);
+        select(app, first);
+    } else if pressed(egui::Key::End) {
+        let last = (0..rows.len()).rev().find_map(row_at).map(|e| e.path);
+        select(app, last);
+    } else if pressed(egui::Key::ArrowRight) {
+        if entry.is_dir && !expanded {
+            set_expanded(app, &entry.path, true);
+        } else if entry.is_dir {
+            let child = row_at(index + 1).filter(|e| e.path.parent() == Some(entry.path.as_path())).map(|e| e.path);
+            if child.is_some() {
+                select(app, child);
+            }
+        }
+    } else if pressed(egui::Key::ArrowLeft) {
+        if entry.is_dir && expanded {
+            set_expanded(app, &entry.path, false);
+        } else {
+            let parent = entry.path.parent().filter(|p| Some(*p) != app.file_tree.root.as_deref()).map(Path::to_path_buf);
+            if parent.is_some() {
+                select(app, parent);
+            }
+        }
+    } else if pressed(egui::Key::Enter) {
+        if entry.is_dir {
+            set_expanded(app, &entry.path, !expanded);
         } else {
-            render_file(app, ui, &path_buf, depth);
+            app.file_tree.focused = false;
+            file_ops::open_path(app, entry.path);
+            ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+        }
+    } else if pressed(egui::Key::F2) {
+        start_edit(app, EditTarget::Rename(entry.path));
+    } else if pressed(egui::Key::Delete) {
+        let next = (index + 1..rows.len()).find_map(row_at)
+            .filter(|e| !e.path.starts_with(&entry.path))
+            .or_else(|| (0..index).rev().find_map(row_at))
+            .map(|e| e.path);
+        file_ops::delete_path(app, &entry.path);
+        select(app, next);
+    } else if pressed(egui::Key::Escape) {
+        app.file_tree.focused = false;
+    }
+}
+
+// Dropping an entry on a folder, or on a file inside it, offers to move it there;
+// empty space below the rows stands for the root folder
+fn handle_tree_drop(app: &mut CodeEditorApp, ctx: &egui::Context, targets: &[(egui::Rect, PathBuf)], area: egui::Rect, root: &Path) {
+    let Some(dragged) = app.file_tree.dragged.clone() else { return };
+    let pointer = ctx.input(|i| i.pointer.hover_pos());
+    let target = pointer.filter(|p| area.contains(*p)).and_then(|p| {
+        targets.iter().find(|(rect, _)| rect.contains(p))
+            .map(|(rect, folder)| (*rect, folder.clone()))
+            .or_else(|| Some((area, root.to_path_buf())))
+    });
+    let target = target.filter(|(_, folder)| {
+        !folder.starts_with(&dragged) && dragged.parent() != Some(folder.as_path())
+    });
+
+    if ctx.input(|i| i.pointer.any_released()) {
+        app.file_tree.dragged = None;
+        if let Some((_, folder)) = target {
+            let to = folder.join(dragged.file_name().unwrap_or_default());
+            app.file_tree.pending_move = Some((dragged, to));
+        }
+        return;
+    }
+
+    if let Some((rect, _)) = target {
+        ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("tree_drop")))
+            .rect_stroke(rect, 2.0, egui::Stroke::new(2.0, ctx.style().visuals.selection.stroke.color));
+    }
+    let name = dragged.file_name().unwrap_or_default().to_string_lossy().to_string();
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("dragged_entry"), |ui| {
+        ui.label(format!("{} {}", if dragged.is_dir() { "📁" } else { "📄" }, name));
+    });
+}
+
+fn render_move_confirmation(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some((from, to)) = app.file_tree.pending_move.clone() else { return };
+    let root = app.root_folder.clone().unwrap_or_default();
+    let name = from.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let destination = to.parent().map(|p| p.strip_prefix(&root).unwrap_or(p).display().to_string()).unwrap_or_default();
+
+    let mut confirm = false;
+    let mut cancel = false;
+    egui::Window::new("📦 Move")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            let destination = if destination.is_empty() { "the project root".to_string() } else { format!("'{}'", destination) };
+            ui.label(format!("Move '{}' into {}?", name, destination));
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                if ui.button("Move").clicked() {
+                    confirm = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if confirm {
+        app.file_tree.pending_move = None;
+        if file_ops::rename_path(app, &from, &to) {
+            app.file_tree.selected = Some(to);
         }
+    } else if cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.file_tree.pending_move = None;
     }
 }
 
-fn render_folder(app: &mut CodeEditorApp, ui: &mut egui::Ui, path: &PathBuf, depth: usize) {
+fn render_toolbar(app: &mut CodeEditorApp, ui: &mut egui::Ui, root: &Path) {
+    ui.horizontal(|ui| {
+        if ui.small_button("📄+").on_hover_text("New File").clicked() {
+            let dir = target_folder(app, root);
+            start_edit(app, EditTarget::NewFile(dir));
+        }
+        if ui.small_button("📁+").on_hover_text("New Folder").clicked() {
+            let dir = target_folder(app, root);
+            start_edit(app, EditTarget::NewFolder(dir));
+        }
+        if ui.small_button("🎯").on_hover_text("Reveal Active File").clicked() {
+            if let Some(path) = app.file_path.clone() {
+                reveal(app, &path);
+            }
+        }
+        if file_ops::CAN_RESTORE {
+            if let Some(trashed) = app.file_tree.trashed.clone() {
+                let name = trashed.file_name().unwrap_or_default().to_string_lossy().to_string();
+                if ui.small_button(format!("↶ Undo Delete '{}'", name)).clicked() {
+                    file_ops::undo_delete(app);
+                }
+            }
+        }
+    });
+}
+
+fn render_inline_edit(app: &mut CodeEditorApp, ui: &mut egui::Ui, depth: usize, icon: &str) {
+    let Some(edit) = app.file_tree.edit.as_mut() else { return };
+    let indent = "    ".repeat(depth - 1);
+    let mut commit = false;
+    let mut cancel = false;
+    ui.horizontal(|ui| {
+        ui.label(format!("{}  {}", indent, icon));
+        let response = ui.text_edit_singleline(&mut edit.name);
+        if edit.focus {
+            response.request_focus();
+            edit.focus = false;
+        }
+        // Enter creates or renames; Escape or clicking elsewhere leaves things as they were
+        if response.lost_focus() {
+            if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                commit = true;
+            } else {
+                cancel = true;
+            }
+        }
+    });
+    if commit {
+        commit_edit(app);
+    } else if cancel {
+        cancel_edit(app);
+    }
+}
+
+fn entry_text(ui: &egui::Ui, text: String, entry: &Entry) -> egui::RichText {
+    let text = egui::RichText::new(text);
+    if entry.ignored { text.color(ui.visuals().weak_text_color()) } else { text }
+}
+
+fn render_folder(app: &mut CodeEditorApp, ui: &mut egui::Ui, entry: &Entry, depth: usize, has_children: bool) {
+    let path = entry.path.as_path();
     let is_expanded = app.file_tree_expanded.get(path).copied().unwrap_or(false);
     let indent = "    ".repeat(depth - 1);
-    let arrow = if is_expanded { "▼" } else { "▶" };
+    // A filtered tree shows the folders leading to a match open, and a folder that matched itself closed
+    let open = if app.file_tree.filter.is_empty() { is_expanded } else { has_children };
+    let arrow = if open { "▼" } else { "▶" };
     let folder_icon = "📁";
-    let folder_name = path.file_name().unwrap().to_string_lossy();
-    
+    let folder_name = path.file_name().unwrap_or_default().to_string_lossy();
+
     let button_text = format!("{}{} {} {}", indent, arrow, folder_icon, folder_name);
-    
-    if ui.button(button_text).clicked() {
-        app.file_tree_expanded.insert(path.clone(), !is_expanded);
+
+    let is_selected = app.file_tree.selected.as_deref() == Some(path);
+    let response = ui.selectable_label(is_selected, entry_text(ui, button_text, entry)).interact(egui::Sense::drag());
+    if response.clicked() {
+        app.file_tree.selected = Some(path.to_path_buf());
+        app.file_tree.focused = true;
+        set_expanded(app, path, !is_expanded);
+    }
+    if response.drag_started() {
+        app.file_tree.dragged = Some(path.to_path_buf());
     }
+    response.context_menu(|ui| render_entry_menu(app, ui, path, true));
 }
 
-fn render_file(app: &mut CodeEditorApp, ui: &mut egui::Ui, path: &PathBuf, depth: usize) {
+fn render_file(app: &mut CodeEditorApp, ui: &mut egui::Ui, entry: &Entry, depth: usize) {
+    let path = entry.path.as_path();
     let indent = "    ".repeat(depth - 1);
-    let icon = get_file_icon(&path.file_name().unwrap().to_string_lossy());
-    let file_name = path.file_name().unwrap().to_string_lossy();
-    
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let icon = get_file_icon(&file_name);
+
     let button_text = format!("{}  {} {}", indent, icon, file_name);
-    
-    if ui.button(button_text).clicked() {
-        file_ops::load_file(app, path.clone());
-        if !app.open_files.contains(path) {
-            app.open_files.push(path.clone());
-            app.current_file_index = app.open_files.len() - 1;
+
+    let is_selected = app.file_tree.selected.as_deref() == Some(path);
+    let response = ui.selectable_label(is_selected, entry_text(ui, button_text, entry)).interact(egui::Sense::drag());
+    if response.clicked() {
+        app.file_tree.selected = Some(path.to_path_buf());
+        app.file_tree.focused = true;
+        file_ops::open_path(app, path.to_path_buf());
+    }
+    if response.drag_started() {
+        app.file_tree.dragged = Some(path.to_path_buf());
+    }
+    response.context_menu(|ui| render_entry_menu(app, ui, path, false));
+}
+
+fn render_entry_menu(app: &mut CodeEditorApp, ui: &mut egui::Ui, path: &Path, is_dir: bool) {
+    app.file_tree.selected = Some(path.to_path_buf());
+    let folder = if is_dir { path.to_path_buf() } else { path.parent().unwrap_or(path).to_path_buf() };
+
+    if ui.button("📄 New File").clicked() {
+        start_edit(app, EditTarget::NewFile(folder.clone()));
+        ui.close_menu();
+    }
+    if ui.button("📁 New Folder").clicked() {
+        start_edit(app, EditTarget::NewFolder(folder.clone()));
+        ui.close_menu();
+    }
+    ui.separator();
+    if ui.button("✏ Rename").clicked() {
+        start_edit(app, EditTarget::Rename(path.to_path_buf()));
+        ui.close_menu();
+    }
+    if ui.button("⧉ Duplicate").clicked() {
+        file_ops::duplicate_path(app, path);
+        ui.close_menu();
+    }
+    if ui.button("🗑 Delete").clicked() {
+        file_ops::delete_path(app, path);
+        ui.close_menu();
+    }
+    ui.separator();
+    if ui.button("📋 Copy Path").clicked() {
+        let text = path.display().to_string();
+        ui.output_mut(|o| o.copied_text = text);
+        ui.close_menu();
+    }
+    if ui.button("📋 Copy Relative Path").clicked() {
+        let root = app.root_folder.clone().unwrap_or_default();
+        let text = path.strip_prefix(&root).unwrap_or(path).display().to_string();
+        ui.output_mut(|o| o.copied_text = text);
+        ui.close_menu();
+    }
+    if ui.button("🖥️ Open in Terminal").clicked() {
+        terminal::set_directory(app, folder);
+        ui.close_menu();
+    }
+    if ui.add_enabled(app.file_path.is_some(), egui::Button::new("🎯 Reveal Active File")).clicked() {
+        if let Some(active) = app.file_path.clone() {
+            reveal(app, &active);
+        }
+        ui.close_menu();
+    }
+
+    if !is_dir {
+        ui.separator();
+        if ui.button("⇄ Select for Compare").clicked() {
+            app.diff.compare_selection = Some(path.to_path_buf());
+            ui.close_menu();
+        }
+        if let Some(selected) = app.diff.compare_selection.clone().filter(|s| s != path) {
+            let name = selected.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if ui.button(format!("⇄ Compare with '{}'", name)).clicked() {
+                diff::compare_files(app, selected, path.to_path_buf());
+                ui.close_menu();
+            }
         }
     }
 }
 
 fn get_file_icon(filename: &str) -> &'static str {
-    match Path::new(filename).extension().and_then(|s| s.to_str()) {
-        Some("rs") => "🦀",
-        Some("py") => "🐍",
-        Some("js") | Some("jsx") | Some("ts") | Some("tsx") => "📜",
-        Some("html") => "🌐",
-        Some("css") | Some("scss") => "🎨",
-        Some("json") => "📦",
-        Some("md") => "📝",
-        Some("txt") => "📄",
-        Some("toml") | Some("yaml") | Some("yml") => "⚙️",
-        Some("lock") => "🔒",
-        _ => "📋",
+    Language::from_path(Path::new(filename)).icon()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tree over a temporary folder, shown the way the explorer lists it
+    struct Fixture {
+        dir: tempfile::TempDir,
+        app: CodeEditorApp,
+    }
+
+    impl Fixture {
+        fn new(files: &[&str]) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            for file in files {
+                let path = dir.path().join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+            let mut app = CodeEditorApp { root_folder: Some(dir.path().to_path_buf()), ..Default::default() };
+            app.file_tree.reset(dir.path(), &app.config.excludes);
+            Self { dir, app }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        // "  name/" for each row, indented by depth
+        fn rows(&mut self) -> Vec<String> {
+            let app = &mut self.app;
+            if app.file_tree.rows_dirty || app.file_tree.rows.is_empty() {
+                app.file_tree.rebuild_rows(&app.file_tree_expanded, &app.config.excludes, None);
+            }
+            app.file_tree.rows.iter().map(|row| {
+                let name = row.entry.path.file_name().unwrap().to_string_lossy();
+                format!("{}{}{}", "  ".repeat(row.depth - 1), name, if row.entry.is_dir { "/" } else { "" })
+            }).collect()
+        }
+
+        fn loaded(&self, name: &str) -> bool {
+            self.app.file_tree.children.contains_key(&self.path(name))
+        }
+
+        fn changed(&mut self, names: &[&str]) {
+            let paths = names.iter().map(|name| self.path(name)).collect();
+            apply_changes(&mut self.app, &paths);
+        }
+    }
+
+    #[test]
+    fn folders_are_read_when_first_expanded() {
+        let mut fixture = Fixture::new(&["src/ui/deep/x/y/z.rs", "src/main.rs", "README.md"]);
+        assert_eq!(fixture.rows(), vec!["src/", "README.md"]);
+        assert!(!fixture.loaded("src"));
+
+        for folder in ["src", "src/ui", "src/ui/deep", "src/ui/deep/x", "src/ui/deep/x/y"] {
+            let path = fixture.path(folder);
+            set_expanded(&mut fixture.app, &path, true);
+        }
+        assert_eq!(fixture.rows(), vec!["src/", "  ui/", "    deep/", "      x/", "        y/", "          z.rs", "  main.rs", "README.md"]);
+        assert!(fixture.loaded("src/ui/deep/x/y"));
+
+        // Collapsing keeps what was read
+        let src = fixture.path("src");
+        set_expanded(&mut fixture.app, &src, false);
+        assert_eq!(fixture.rows(), vec!["src/", "README.md"]);
+        assert!(fixture.loaded("src/ui"));
+    }
+
+    #[test]
+    fn created_and_deleted_entries_update_loaded_folders() {
+        let mut fixture = Fixture::new(&["src/main.rs", "docs/guide.md"]);
+        let src = fixture.path("src");
+        set_expanded(&mut fixture.app, &src, true);
+        assert_eq!(fixture.rows(), vec!["docs/", "src/", "  main.rs"]);
+
+        fs::write(fixture.path("src/lib.rs"), "").unwrap();
+        fs::write(fixture.path("docs/api.md"), "").unwrap();
+        fixture.changed(&["src/lib.rs", "docs/api.md"]);
+        // docs was never read, so there is nothing to refresh there
+        assert!(!fixture.loaded("docs"));
+        assert_eq!(fixture.rows(), vec!["docs/", "src/", "  lib.rs", "  main.rs"]);
+
+        fixture.app.file_tree.selected = Some(fixture.path("src/main.rs"));
+        fs::remove_dir_all(&src).unwrap();
+        // A removed folder reports only its own path
+        fixture.changed(&["src"]);
+        assert_eq!(fixture.rows(), vec!["docs/"]);
+        assert!(!fixture.loaded("src"));
+        assert!(fixture.app.file_tree.selected.is_none());
+    }
+
+    #[test]
+    fn renames_keep_expansion_selection_and_open_tabs() {
+        let mut fixture = Fixture::new(&["src/ui/panel.rs", "src/main.rs"]);
+        for folder in ["src", "src/ui"] {
+            let path = fixture.path(folder);
+            set_expanded(&mut fixture.app, &path, true);
+        }
+        let panel = fixture.path("src/ui/panel.rs");
+        fixture.app.file_tree.selected = Some(panel.clone());
+        let doc = crate::panes::add_document(&mut fixture.app, Some(panel), String::new());
+        assert_eq!(fixture.rows(), vec!["src/", "  ui/", "    panel.rs", "  main.rs"]);
+
+        let (src, lib) = (fixture.path("src"), fixture.path("lib"));
+        assert!(file_ops::rename_path(&mut fixture.app, &src, &lib));
+        assert_eq!(fixture.rows(), vec!["lib/", "  ui/", "    panel.rs", "  main.rs"]);
+        assert_eq!(fixture.app.file_tree.selected, Some(fixture.path("lib/ui/panel.rs")));
+        assert!(!fixture.app.file_tree_expanded.contains_key(&src));
+        assert_eq!(fixture.app.panes.documents[&doc].path, Some(fixture.path("lib/ui/panel.rs")));
+
+        // Moving into another folder is a rename to a deeper path
+        fs::create_dir(fixture.path("lib/views")).unwrap();
+        let (ui, moved) = (fixture.path("lib/ui"), fixture.path("lib/views/ui"));
+        let views = fixture.path("lib/views");
+        set_expanded(&mut fixture.app, &views, true);
+        assert!(file_ops::rename_path(&mut fixture.app, &ui, &moved));
+        assert_eq!(fixture.rows(), vec!["lib/", "  views/", "    ui/", "      panel.rs", "  main.rs"]);
+        assert_eq!(fixture.app.panes.documents[&doc].path, Some(fixture.path("lib/views/ui/panel.rs")));
+    }
+
+    #[test]
+    fn typed_names_stay_in_their_folder() {
+        assert!(is_plain_name("main.rs"));
+        assert!(is_plain_name("..hidden"));
+        assert!(!is_plain_name("../main.rs"));
+        assert!(!is_plain_name("src/main.rs"));
+        assert!(!is_plain_name("src\\main.rs"));
+        assert!(!is_plain_name(".."));
+        assert!(!is_plain_name("."));
+    }
+
+    fn rows(files: &[&str], query: &str) -> Vec<(String, bool, usize)> {
+        let root = Path::new("/ws");
+        let mut files: Vec<PathBuf> = files.iter().map(|f| root.join(f)).collect();
+        files.sort();
+        filtered_rows(root, &files, &query.to_lowercase()).into_iter()
+            .map(|r| (r.entry.path.strip_prefix(root).unwrap().display().to_string(), r.entry.is_dir, r.depth))
+            .collect()
+    }
+
+    #[test]
+    fn filter_keeps_folders_of_matches() {
+        let found = rows(&["src/main.rs", "src/ui/main_menu.rs", "src/ui/panel.rs", "README.md"], "main");
+        assert_eq!(found, vec![
+            ("src".to_string(), true, 1),
+            ("src/ui".to_string(), true, 2),
+            ("src/ui/main_menu.rs".to_string(), false, 3),
+            ("src/main.rs".to_string(), false, 2),
+        ]);
+    }
+
+    #[test]
+    fn matching_folder_shows_without_its_other_files() {
+        let found = rows(&["docs/guide.md", "docs/api/index.md", "src/lib.rs"], "DOC");
+        assert_eq!(found, vec![("docs".to_string(), true, 1)]);
+    }
+
+    #[test]
+    fn only_folders_leading_to_a_match_show_open() {
+        let root = Path::new("/ws");
+        let files: Vec<PathBuf> = ["docs/guide.md", "src/docs.rs"].iter().map(|f| root.join(f)).collect();
+        let filtered = filtered_rows(root, &files, "doc");
+        let open: Vec<(String, bool)> = (0..filtered.len())
+            .filter(|&i| filtered[i].entry.is_dir)
+            .map(|i| (filtered[i].entry.path.strip_prefix(root).unwrap().display().to_string(), shows_children(&filtered, i)))
+            .collect();
+        assert_eq!(open, vec![("docs".to_string(), false), ("src".to_string(), true)]);
+    }
+
+    #[test]
+    fn filter_with_no_match_is_empty() {
+        assert!(rows(&["a.rs", "b/c.rs"], "zzz").is_empty());
     }
 }
@@ -0,0 +1,471 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use eframe::egui;
+use crate::app::CodeEditorApp;
+use crate::editor;
+use crate::panes;
+
+const BYTES_PER_ROW: usize = 16;
+// Like git, a NUL byte near the start marks a file as binary
+const SNIFF_LENGTH: usize = 8000;
+// Larger files are shown read-only, since every inserted byte would shift the rest in memory
+pub const MAX_EDIT_SIZE: usize = 16 * 1024 * 1024;
+
+const COLOR_OFFSET: egui::Color32 = egui::Color32::from_rgb(98, 114, 164);
+const COLOR_BYTE: egui::Color32 = egui::Color32::from_rgb(229, 229, 229);
+const COLOR_ZERO: egui::Color32 = egui::Color32::from_rgb(98, 114, 164);
+const COLOR_CURSOR: egui::Color32 = egui::Color32::from_rgb(139, 233, 253);
+const COLOR_CURSOR_OTHER: egui::Color32 = egui::Color32::from_rgb(68, 71, 90);
+const COLOR_MATCH: egui::Color32 = egui::Color32::from_rgb(241, 250, 140);
+const COLOR_CURSOR_TEXT: egui::Color32 = egui::Color32::from_rgb(28, 30, 43);
+const COLOR_MESSAGE: egui::Color32 = egui::Color32::from_rgb(255, 121, 198);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Column {
+    #[default]
+    Hex,
+    Ascii,
+}
+
+#[derive(Default)]
+pub struct HexView {
+    // Byte under the cursor; equal to the length at the end of the file
+    pub cursor: usize,
+    // The high digit of the byte under the cursor has been typed
+    half: bool,
+    pub insert: bool,
+    pub column: Column,
+    goto: String,
+    search: String,
+    // Start and length of the last search match
+    found: Option<(usize, usize)>,
+    message: Option<String>,
+    // Rows drawn last frame, to tell when the cursor moves out of view
+    visible: Range<usize>,
+    scroll_to: Option<usize>,
+}
+
+// Cursor and search state of each binary document, shared by the groups showing it
+#[derive(Default)]
+pub struct HexState {
+    pub views: HashMap<usize, HexView>,
+}
+
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(SNIFF_LENGTH)].contains(&0)
+}
+
+// "0x1F0" is read as hex, anything else as decimal
+fn parse_offset(text: &str) -> Option<usize> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// "DE AD be ef" searches for those bytes; quoted or other text for its UTF-8 bytes
+fn parse_pattern(text: &str) -> Vec<u8> {
+    if let Some(quoted) = text.strip_prefix('"') {
+        return quoted.strip_suffix('"').unwrap_or(quoted).as_bytes().to_vec();
+    }
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let pairs = digits.chunks_exact(2);
+    if !digits.is_empty() && pairs.remainder().is_empty() && digits.iter().all(u8::is_ascii_hexdigit) {
+        return pairs
+            .filter_map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+            .collect();
+    }
+    text.as_bytes().to_vec()
+}
+
+// First match at or after `from`, wrapping around to the start
+fn find(bytes: &[u8], pattern: &[u8], from: usize) -> Option<usize> {
+    if pattern.is_empty() || pattern.len() > bytes.len() {
+        return None;
+    }
+    let last = bytes.len() - pattern.len();
+    (from..=last).chain(0..from.min(last + 1)).find(|&i| bytes[i..].starts_with(pattern))
+}
+
+fn find_next(bytes: &[u8], view: &mut HexView) {
+    let pattern = parse_pattern(&view.search);
+    let from = match view.found {
+        Some((start, _)) if start == view.cursor => view.cursor + 1,
+        _ => view.cursor,
+    };
+    match find(bytes, &pattern, from) {
+        Some(start) => {
+            view.found = Some((start, pattern.len()));
+            view.message = None;
+            move_to(view, start);
+        }
+        None => {
+            view.found = None;
+            view.message = Some("Not found".to_string());
+        }
+    }
+}
+
+fn move_to(view: &mut HexView, offset: usize) {
+    view.cursor = offset;
+    view.half = false;
+    let row = offset / BYTES_PER_ROW;
+    let shown = view.visible.len().max(1);
+    if row < view.visible.start {
+        view.scroll_to = Some(row);
+    } else if row >= view.visible.end {
+        view.scroll_to = Some((row + 1).saturating_sub(shown));
+    }
+}
+
+fn type_digit(bytes: &mut Vec<u8>, view: &mut HexView, digit: u8) {
+    if !view.half {
+        if view.insert || view.cursor >= bytes.len() {
+            bytes.insert(view.cursor, 0);
+        }
+        bytes[view.cursor] = (bytes[view.cursor] & 0x0F) | (digit << 4);
+        view.half = true;
+    } else {
+        bytes[view.cursor] = (bytes[view.cursor] & 0xF0) | digit;
+        let next = view.cursor + 1;
+        move_to(view, next);
+    }
+}
+
+fn type_byte(bytes: &mut Vec<u8>, view: &mut HexView, byte: u8) {
+    if view.insert || view.cursor >= bytes.len() {
+        bytes.insert(view.cursor, byte);
+    } else {
+        bytes[view.cursor] = byte;
+    }
+    let next = view.cursor + 1;
+    move_to(view, next);
+}
+
+// The file only changes size in insert mode; in overwrite mode Backspace just moves back
+fn delete(bytes: &mut Vec<u8>, view: &mut HexView, backward: bool) -> bool {
+    view.half = false;
+    if backward {
+        if view.cursor == 0 {
+            return false;
+        }
+        let previous = view.cursor - 1;
+        move_to(view, previous);
+    }
+    if !view.insert || view.cursor >= bytes.len() {
+        return false;
+    }
+    bytes.remove(view.cursor);
+    true
+}
+
+// Returns whether the bytes were edited; a read-only view only moves the cursor
+fn handle_keys(ctx: &egui::Context, bytes: &mut Vec<u8>, view: &mut HexView, read_only: bool) -> bool {
+    let events = ctx.input(|i| i.events.clone());
+    let page = view.visible.len().max(1) * BYTES_PER_ROW;
+    let mut edited = false;
+    for event in events {
+        let row_start = view.cursor - view.cursor % BYTES_PER_ROW;
+        match event {
+            egui::Event::Key { key, pressed: true, modifiers, .. } => {
+                let target = match key {
+                    egui::Key::ArrowLeft => Some(view.cursor.saturating_sub(1)),
+                    egui::Key::ArrowRight => Some(view.cursor + 1),
+                    egui::Key::ArrowUp => Some(view.cursor.saturating_sub(BYTES_PER_ROW)),
+                    egui::Key::ArrowDown => Some(view.cursor + BYTES_PER_ROW),
+                    egui::Key::PageUp => Some(view.cursor.saturating_sub(page)),
+                    egui::Key::PageDown => Some(view.cursor + page),
+                    egui::Key::Home if modifiers.command => Some(0),
+                    egui::Key::End if modifiers.command => Some(bytes.len()),
+                    egui::Key::Home => Some(row_start),
+                    egui::Key::End => Some(row_start + BYTES_PER_ROW - 1),
+                    _ => None,
+                };
+                if let Some(target) = target {
+                    move_to(view, target.min(bytes.len()));
+                    continue;
+                }
+                match key {
+                    egui::Key::Tab => {
+                        view.column = if view.column == Column::Hex { Column::Ascii } else { Column::Hex };
+                        view.half = false;
+                    }
+                    _ if read_only => {}
+                    egui::Key::Insert => view.insert = !view.insert,
+                    egui::Key::Backspace => edited |= delete(bytes, view, true),
+                    egui::Key::Delete => edited |= delete(bytes, view, false),
+                    _ => {}
+                }
+            }
+            egui::Event::Text(text) if !read_only => {
+                for c in text.chars() {
+                    match view.column {
+                        Column::Hex => {
+                            if let Some(digit) = c.to_digit(16) {
+                                type_digit(bytes, view, digit as u8);
+                                edited = true;
+                            }
+                        }
+                        Column::Ascii => {
+                            if c.is_ascii() && !c.is_ascii_control() {
+                                type_byte(bytes, view, c as u8);
+                                edited = true;
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    edited
+}
+
+// Points the cursor back inside the file after it was reloaded from disk
+pub fn reloaded(app: &mut CodeEditorApp, doc: usize) {
+    let len = app.panes.documents.get(&doc).and_then(|d| d.bytes.as_ref()).map_or(0, Vec::len);
+    if let Some(view) = app.hex.views.get_mut(&doc) {
+        view.cursor = view.cursor.min(len);
+        view.half = false;
+        view.found = None;
+    }
+}
+
+// Shows the file as text, replacing bytes that are not valid UTF-8; unsaved hex edits stay unsaved.
+// A replaced file can only be saved elsewhere
+pub fn open_as_text(app: &mut CodeEditorApp, doc: usize) {
+    let dirty = panes::is_dirty(app, doc);
+    let Some(document) = app.panes.documents.get_mut(&doc) else { return };
+    let Some(bytes) = document.bytes.take() else { return };
+    document.lossy = std::str::from_utf8(&bytes).is_err();
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    if !dirty {
+        document.saved_hash = Some(panes::hash_text(&text));
+    }
+    if app.panes.active_doc == Some(doc) {
+        app.text = text;
+    } else {
+        document.text = text;
+    }
+    panes::mark_edited(app, doc);
+    app.hex.views.remove(&doc);
+}
+
+fn cell(ui: &mut egui::Ui, text: String, view: &HexView, offset: usize, column: Column, zero: bool) -> egui::Response {
+    let mut text = egui::RichText::new(text).monospace().color(if zero { COLOR_ZERO } else { COLOR_BYTE });
+    if offset == view.cursor {
+        let background = if view.column == column { COLOR_CURSOR } else { COLOR_CURSOR_OTHER };
+        text = text.background_color(background).color(COLOR_CURSOR_TEXT);
+    } else if view.found.is_some_and(|(start, len)| (start..start + len).contains(&offset)) {
+        text = text.background_color(COLOR_MATCH).color(COLOR_CURSOR_TEXT);
+    }
+    ui.add(egui::Label::new(text).sense(egui::Sense::click()))
+}
+
+// Offset, hex and ASCII columns of a binary document; the grid shares its id with the text editor
+// it replaces, so tab switches and group focus work the same way
+pub fn render(app: &mut CodeEditorApp, ui: &mut egui::Ui, group: usize, doc: usize) {
+    let id = editor::pane_id(app.panes.groups[group].id, Some(doc));
+    let Some(bytes) = app.panes.documents.get_mut(&doc).and_then(|d| d.bytes.as_mut()) else { return };
+    let view = app.hex.views.entry(doc).or_default();
+    let has_focus = ui.memory(|m| m.has_focus(id));
+    let read_only = bytes.len() > MAX_EDIT_SIZE;
+    let mut edited = false;
+    let mut clicked = false;
+    let mut as_text = false;
+
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("Go to").color(COLOR_OFFSET));
+        let goto = ui.add(egui::TextEdit::singleline(&mut view.goto).hint_text("0x1F0 or 496").desired_width(100.0));
+        if goto.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            match parse_offset(&view.goto).filter(|&offset| offset <= bytes.len()) {
+                Some(offset) => {
+                    view.message = None;
+                    move_to(view, offset);
+                    ui.memory_mut(|m| m.request_focus(id));
+                }
+                None => view.message = Some(format!("Offset must be between 0 and {}", bytes.len())),
+            }
+        }
+        ui.separator();
+        ui.label(egui::RichText::new("Find").color(COLOR_OFFSET));
+        let search = ui.add(egui::TextEdit::singleline(&mut view.search).hint_text("DE AD BE EF or \"text\"").desired_width(160.0));
+        let submitted = search.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if submitted || ui.button("Next").clicked() {
+            find_next(bytes, view);
+            if submitted {
+                search.request_focus();
+            }
+        }
+        ui.separator();
+        if read_only {
+            let limit = format!("Files over {} MiB are read-only", MAX_EDIT_SIZE / (1024 * 1024));
+            ui.label(egui::RichText::new("READ-ONLY").color(COLOR_MESSAGE)).on_hover_text(limit);
+        } else {
+            let mode = if view.insert { "INS" } else { "OVR" };
+            if ui.selectable_label(view.insert, mode).on_hover_text("Insert or overwrite bytes (Insert key)").clicked() {
+                view.insert = !view.insert;
+            }
+        }
+        let open_as_text = ui.add_enabled(!read_only, egui::Button::new("📝 Open as Text"));
+        if open_as_text.on_hover_text("Show this file in the text editor").clicked() {
+            as_text = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        let status = format!("Offset 0x{:08X} ({}) · {} bytes", view.cursor, view.cursor, bytes.len());
+        ui.label(egui::RichText::new(status).monospace().size(11.0).color(COLOR_OFFSET));
+        if let Some(message) = &view.message {
+            ui.label(egui::RichText::new(message).size(11.0).color(COLOR_MESSAGE));
+        }
+    });
+    ui.separator();
+
+    if has_focus {
+        // Tab switches between the hex and ASCII columns instead of moving focus
+        ui.memory_mut(|m| m.lock_focus(id, true));
+        edited = handle_keys(ui.ctx(), bytes, view, read_only);
+    }
+
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    // One row past the last full one, so there is a place to append
+    let rows = bytes.len() / BYTES_PER_ROW + 1;
+    let mut scroll_area = egui::ScrollArea::both()
+        .id_source(id)
+        .auto_shrink([false; 2]);
+    if let Some(row) = view.scroll_to.take() {
+        scroll_area = scroll_area.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+    } else if let Some(offset) = app.session.restore_scroll.remove(&id) {
+        scroll_area = scroll_area.scroll_offset(offset);
+    }
+    let scroll = scroll_area.show_rows(ui, row_height, rows, |ui, range| {
+        view.visible = range.clone();
+        for row in range {
+            let start = row * BYTES_PER_ROW;
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 6.0;
+                ui.label(egui::RichText::new(format!("{:08X}", start)).monospace().color(COLOR_OFFSET));
+                ui.add_space(6.0);
+                for offset in start..start + BYTES_PER_ROW {
+                    let response = match bytes.get(offset) {
+                        Some(&byte) => cell(ui, format!("{:02X}", byte), view, offset, Column::Hex, byte == 0),
+                        None if offset == bytes.len() => cell(ui, "  ".to_string(), view, offset, Column::Hex, false),
+                        None => ui.label(egui::RichText::new("  ").monospace()),
+                    };
+                    if response.clicked() {
+                        view.column = Column::Hex;
+                        move_to(view, offset);
+                        clicked = true;
+                    }
+                    if offset % BYTES_PER_ROW == BYTES_PER_ROW / 2 - 1 {
+                        ui.add_space(6.0);
+                    }
+                }
+                ui.add_space(12.0);
+                ui.spacing_mut().item_spacing.x = 0.0;
+                for offset in start..(start + BYTES_PER_ROW).min(bytes.len() + 1) {
+                    let shown = match bytes.get(offset) {
+                        Some(&byte) if byte.is_ascii_graphic() || byte == b' ' => (byte as char).to_string(),
+                        Some(_) => ".".to_string(),
+                        None => " ".to_string(),
+                    };
+                    if cell(ui, shown, view, offset, Column::Ascii, false).clicked() {
+                        view.column = Column::Ascii;
+                        move_to(view, offset);
+                        clicked = true;
+                    }
+                }
+            });
+        }
+    });
+    // Keeps the focus alive between frames; clicks go to the cells above
+    ui.interact(scroll.inner_rect, id, egui::Sense::hover());
+    app.session.scroll.insert(id, scroll.state.offset);
+
+    if clicked {
+        ui.memory_mut(|m| m.request_focus(id));
+        if group != app.panes.active_group {
+            app.panes.active_group = group;
+            panes::activate(app);
+        }
+    }
+    if edited {
+        panes::mark_edited(app, doc);
+    }
+    if as_text {
+        open_as_text(app, doc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::file_ops;
+
+    #[test]
+    fn patterns_are_hex_pairs_or_text() {
+        assert_eq!(parse_pattern("DE AD be ef"), vec![0xDE, 0xAD, 0xBE, 0xEF]);
+        assert_eq!(parse_pattern("\"ab 12\""), b"ab 12".to_vec());
+        // An odd number of digits cannot be bytes, so it is searched as text
+        assert_eq!(parse_pattern("abc"), b"abc".to_vec());
+        assert_eq!(parse_pattern("zz"), b"zz".to_vec());
+    }
+
+    #[test]
+    fn offsets_are_hex_or_decimal() {
+        assert_eq!(parse_offset(" 0x1F0 "), Some(0x1F0));
+        assert_eq!(parse_offset("496"), Some(496));
+        assert_eq!(parse_offset("0xZZ"), None);
+    }
+
+    #[test]
+    fn find_wraps_around() {
+        let bytes = b"ab..ab..";
+        assert_eq!(find(bytes, b"ab", 1), Some(4));
+        assert_eq!(find(bytes, b"ab", 5), Some(0));
+        assert_eq!(find(bytes, b"..", 7), Some(2));
+        assert_eq!(find(bytes, b"zz", 0), None);
+        assert_eq!(find(b"a", b"ab", 0), None);
+    }
+
+    #[test]
+    fn only_a_nul_near_the_start_marks_a_file_binary() {
+        assert!(looks_binary(b"text\0more"));
+        assert!(!looks_binary("plain text ü".as_bytes()));
+        let mut late = vec![b'a'; SNIFF_LENGTH];
+        late.push(0);
+        assert!(!looks_binary(&late));
+    }
+
+    #[test]
+    fn typing_and_deleting_follow_the_mode() {
+        let mut bytes = vec![0x00, 0x11];
+        let mut view = HexView::default();
+        type_digit(&mut bytes, &mut view, 0xA);
+        type_digit(&mut bytes, &mut view, 0xB);
+        assert_eq!(bytes, vec![0xAB, 0x11]);
+        assert!(!delete(&mut bytes, &mut view, false));
+
+        view.insert = true;
+        type_byte(&mut bytes, &mut view, b'x');
+        assert_eq!(bytes, vec![0xAB, b'x', 0x11]);
+        assert!(delete(&mut bytes, &mut view, true));
+        assert_eq!(bytes, vec![0xAB, 0x11]);
+    }
+
+    #[test]
+    fn text_converted_from_invalid_utf8_is_not_saved_over_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        fs::write(&path, [0xFF, 0x00, b'a']).unwrap();
+        let mut app = CodeEditorApp::default();
+        let doc = panes::add_binary_document(&mut app, path.clone(), fs::read(&path).unwrap());
+        open_as_text(&mut app, doc);
+        assert!(app.panes.documents[&doc].lossy);
+
+        assert!(!file_ops::save_quietly(&mut app, doc));
+        assert_eq!(fs::read(&path).unwrap(), vec![0xFF, 0x00, b'a']);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use eframe::egui;
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::{ListenerOptions, Name, Stream};
+use crate::app::CodeEditorApp;
+use crate::cli::{self, Args};
+
+// Replies sent back to a forwarding process, one per line
+const ACCEPTED: &str = "ok";
+const CLOSED: &str = "closed";
+
+#[derive(Default)]
+pub struct InstanceState {
+    requests: Option<Receiver<(Args, Stream)>>,
+    // Connections of `--wait` callers, answered once their documents are closed
+    waiting: Vec<(Vec<usize>, Stream)>,
+}
+
+pub enum Startup {
+    // A running window took the arguments, so this process is done
+    Forwarded,
+    // This process opens the window, listening for later launches when it could
+    Primary(Option<LocalSocketListener>),
+}
+
+// One socket per user. On Unix it is a file in a folder only the user can enter, since anyone
+// who could connect would be able to open files in the window; Windows uses a named pipe
+#[cfg(unix)]
+fn socket_name() -> io::Result<(Name<'static>, Option<PathBuf>)> {
+    let path = private_dir()?.join("codonn.sock");
+    Ok((path.clone().to_fs_name::<interprocess::local_socket::GenericFilePath>()?, Some(path)))
+}
+
+#[cfg(not(unix))]
+fn socket_name() -> io::Result<(Name<'static>, Option<PathBuf>)> {
+    let user = std::env::var("USERNAME").or_else(|_| std::env::var("USER")).unwrap_or_default();
+    let name = format!("codonn-{}.sock", user);
+    Ok((name.to_ns_name::<interprocess::local_socket::GenericNamespaced>()?, None))
+}
+
+// $XDG_RUNTIME_DIR, or a folder of our own in the temp dir, checked to belong to the user
+// and to be closed to everyone else
+#[cfg(unix)]
+fn private_dir() -> io::Result<PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    // getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from).filter(|dir| dir.is_absolute());
+    let dir = match runtime_dir {
+        Some(dir) => dir,
+        None => {
+            let dir = std::env::temp_dir().join(format!("codonn-{}", uid));
+            match fs::DirBuilder::new().mode(0o700).create(&dir) {
+                Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+                _ => dir,
+            }
+        }
+    };
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        let message = format!("{} is not private to this user", dir.display());
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+    }
+    Ok(dir)
+}
+
+// Hands the arguments to a running window, or becomes the window later launches hand theirs to
+pub fn start(args: &Args) -> Startup {
+    if args.new_window {
+        return Startup::Primary(None);
+    }
+    let Ok((name, file)) = socket_name() else { return Startup::Primary(None) };
+    if let Ok(stream) = Stream::connect(name.clone()) {
+        match forward(stream, args) {
+            Ok(()) => return Startup::Forwarded,
+            Err(e) => eprintln!("codonn: could not reach the running window, opening a new one: {}", e),
+        }
+    }
+
+    let mut listener = ListenerOptions::new().name(name.clone()).create_sync();
+    if let (Err(e), Some(path)) = (&listener, &file) {
+        // Nobody answered, so the socket file was left behind by a crash and can be claimed
+        if e.kind() == io::ErrorKind::AddrInUse {
+            let _ = fs::remove_file(path);
+            listener = ListenerOptions::new().name(name).create_sync();
+        }
+    }
+    match listener {
+        Ok(listener) => Startup::Primary(Some(listener)),
+        Err(e) => {
+            eprintln!("codonn: later launches will open their own window: {}", e);
+            Startup::Primary(None)
+        }
+    }
+}
+
+fn forward(stream: Stream, args: &Args) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut request = serde_json::to_string(args)?;
+    request.push('\n');
+    stream.get_mut().write_all(request.as_bytes())?;
+
+    let mut reply = String::new();
+    stream.read_line(&mut reply)?;
+    if reply.trim() != ACCEPTED {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the running window did not accept the request"));
+    }
+    if args.wait {
+        // Returns when the tabs are closed, or when the window goes away
+        reply.clear();
+        let _ = stream.read_line(&mut reply);
+    }
+    Ok(())
+}
+
+// Accepts forwarded arguments on a background thread and wakes the UI for each. Every
+// connection is read on its own thread, so a client that never sends its line blocks nobody
+pub fn serve(app: &mut CodeEditorApp, ctx: &egui::Context, listener: Option<LocalSocketListener>) {
+    let Some(listener) = listener else { return };
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(Result::ok) {
+            let sender = sender.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || receive(stream, &sender, &ctx));
+        }
+    });
+    app.instance.requests = Some(receiver);
+}
+
+fn receive(stream: Stream, sender: &Sender<(Args, Stream)>, ctx: &egui::Context) {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    if stream.read_line(&mut line).is_err() {
+        return;
+    }
+    let Ok(args) = serde_json::from_str::<Args>(&line) else { return };
+    let mut stream = stream.into_inner();
+    if stream.write_all(format!("{}\n", ACCEPTED).as_bytes()).is_err() {
+        return;
+    }
+    if sender.send((args, stream)).is_ok() {
+        ctx.request_repaint();
+    }
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    let mut received = Vec::new();
+    if let Some(requests) = &app.instance.requests {
+        while let Ok(request) = requests.try_recv() {
+            received.push(request);
+        }
+    }
+    for (args, stream) in received {
+        let wait = args.wait;
+        let opened = cli::apply(app, ctx, args);
+        frame.set_minimized(false);
+        frame.focus();
+        // Dropping the connection releases a caller that has nothing to wait for
+        if wait && !opened.is_empty() {
+            app.instance.waiting.push((opened, stream));
+        }
+    }
+
+    let documents = &app.panes.documents;
+    app.instance.waiting.retain_mut(|(docs, stream)| {
+        if docs.iter().any(|doc| documents.contains_key(doc)) {
+            return true;
+        }
+        let _ = stream.write_all(format!("{}\n", CLOSED).as_bytes());
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[cfg(unix)]
+    #[test]
+    fn stalled_connection_does_not_block_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sock");
+        let name = path.clone().to_fs_name::<interprocess::local_socket::GenericFilePath>().unwrap();
+        let listener = ListenerOptions::new().name(name.clone()).create_sync().unwrap();
+        let mut app = CodeEditorApp::default();
+        serve(&mut app, &egui::Context::default(), Some(listener));
+
+        // Connects and never sends its request
+        let _stalled = Stream::connect(name.clone()).unwrap();
+        let args = Args { targets: vec![cli::Target { path: PathBuf::from("/a.rs"), position: Some((3, 1)) }], ..Args::default() };
+        let forwarder = thread::spawn(move || forward(Stream::connect(name).unwrap(), &args));
+
+        let requests = app.instance.requests.as_ref().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let received = loop {
+            if let Ok((args, _)) = requests.try_recv() {
+                break args;
+            }
+            assert!(Instant::now() < deadline, "request was not received");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(received.targets[0].position, Some((3, 1)));
+        forwarder.join().unwrap().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_folder_is_private() {
+        use std::os::unix::fs::MetadataExt;
+        let dir = private_dir().unwrap();
+        assert_eq!(fs::metadata(dir).unwrap().mode() & 0o077, 0);
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Html,
+    Css,
+    Json,
+    Markdown,
+    PlainText,
+    Toml,
+    Yaml,
+    Lock,
+    Unknown,
+}
+
+impl Language {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("rs") => Language::Rust,
+            Some("py") => Language::Python,
+            Some("js") | Some("jsx") => Language::JavaScript,
+            Some("ts") | Some("tsx") => Language::TypeScript,
+            Some("html") => Language::Html,
+            Some("css") | Some("scss") => Language::Css,
+            Some("json") => Language::Json,
+            Some("md") => Language::Markdown,
+            Some("txt") => Language::PlainText,
+            Some("toml") => Language::Toml,
+            Some("yaml") | Some("yml") => Language::Yaml,
+            Some("lock") => Language::Lock,
+            _ => Language::Unknown,
+        }
+    }
+
+    // Identifier used for per-language files such as `snippets/<id>.json`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Html => "html",
+            Language::Css => "css",
+            Language::Json => "json",
+            Language::Markdown => "markdown",
+            Language::PlainText => "plaintext",
+            Language::Toml => "toml",
+            Language::Yaml => "yaml",
+            Language::Lock => "lock",
+            Language::Unknown => "unknown",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Language::Rust => "🦀",
+            Language::Python => "🐍",
+            Language::JavaScript | Language::TypeScript => "📜",
+            Language::Html => "🌐",
+            Language::Css => "🎨",
+            Language::Json => "📦",
+            Language::Markdown => "📝",
+            Language::PlainText => "📄",
+            Language::Toml | Language::Yaml => "⚙️",
+            Language::Lock => "🔒",
+            Language::Unknown => "📋",
+        }
+    }
+
+    pub fn line_comment(&self) -> Option<&'static str> {
+        match self {
+            Language::Rust | Language::JavaScript | Language::TypeScript => Some("//"),
+            Language::Python | Language::Toml | Language::Yaml => Some("#"),
+            _ => None,
+        }
+    }
+
+    pub fn block_comment(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Language::Rust | Language::JavaScript | Language::TypeScript | Language::Css => Some(("/*", "*/")),
+            Language::Python => Some(("\"\"\"", "\"\"\"")),
+            Language::Html | Language::Markdown => Some(("<!--", "-->")),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,1501 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use eframe::egui;
+use egui::text::CCursor;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::app::CodeEditorApp;
+use crate::completion::{self, Candidate, Source};
+use crate::editor;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+use crate::panes;
+
+// How long a server gets to answer `shutdown` and then to exit before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// Language servers keyed by file extension
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LspSettings {
+    pub enabled: bool,
+    pub servers: BTreeMap<String, ServerConfig>,
+}
+
+impl Default for LspSettings {
+    fn default() -> Self {
+        let server = |command: &str, args: &[&str]| ServerConfig {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        };
+        let mut servers = BTreeMap::new();
+        servers.insert("rs".to_string(), server("rust-analyzer", &[]));
+        servers.insert("py".to_string(), server("pyright-langserver", &["--stdio"]));
+        for ext in ["js", "jsx", "ts", "tsx"] {
+            servers.insert(ext.to_string(), server("typescript-language-server", &["--stdio"]));
+        }
+        Self { enabled: true, servers }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠",
+            Severity::Information => "ℹ",
+            Severity::Hint => "💡",
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            Severity::Error => egui::Color32::from_rgb(255, 85, 85),
+            Severity::Warning => egui::Color32::from_rgb(241, 250, 140),
+            Severity::Information => egui::Color32::from_rgb(139, 233, 253),
+            Severity::Hint => egui::Color32::from_rgb(98, 114, 164),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<String>,
+    raw: Value,
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+#[derive(Clone, Debug)]
+pub struct CodeAction {
+    pub title: String,
+    raw: Value,
+}
+
+pub struct SignatureHelp {
+    pub label: String,
+    pub active_parameter: Option<(usize, usize)>,
+}
+
+pub struct RenameState {
+    pub new_name: String,
+    index: usize,
+}
+
+enum Pending {
+    Initialize,
+    Hover { path: PathBuf, index: usize },
+    Completion { path: PathBuf },
+    SignatureHelp,
+    Definition,
+    References,
+    Rename,
+    CodeActions,
+    Other,
+}
+
+// ---------------------------------------------------------------------------
+// JSON-RPC transport
+
+pub struct LspClient {
+    writer: Box<dyn Write + Send>,
+    child: Option<Child>,
+    receiver: Receiver<Option<Value>>,
+    next_id: i64,
+    pending: HashMap<i64, Pending>,
+    initialized: bool,
+    queued: Vec<Value>,
+    pub running: bool,
+}
+
+impl LspClient {
+    pub fn spawn(config: &ServerConfig, root: Option<&Path>, ctx: &egui::Context) -> io::Result<Self> {
+        let mut command = Command::new(&config.command);
+        command.args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        if let Some(root) = root {
+            command.current_dir(root);
+        }
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("no stdout"))?;
+        let mut client = Self::from_streams(stdout, stdin, ctx);
+        client.child = Some(child);
+        client.initialize(root);
+        Ok(client)
+    }
+
+    // Any pair of streams speaking LSP framing works, which keeps the client
+    // usable against an in-process or mock server
+    pub fn from_streams(
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        ctx: &egui::Context,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Some(message) = read_message(&mut reader) {
+                if sender.send(Some(message)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            let _ = sender.send(None);
+            ctx.request_repaint();
+        });
+
+        Self {
+            writer: Box::new(writer),
+            child: None,
+            receiver,
+            next_id: 1,
+            pending: HashMap::new(),
+            initialized: false,
+            queued: Vec::new(),
+            running: true,
+        }
+    }
+
+    pub fn initialize(&mut self, root: Option<&Path>) {
+        let root_uri = root.map(path_to_uri);
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": root_uri,
+            "workspaceFolders": root.map(|r| json!([{ "uri": path_to_uri(r), "name": r.file_name().map(|n| n.to_string_lossy().to_string()) }])),
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "didSave": true },
+                    "publishDiagnostics": { "relatedInformation": false },
+                    "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    "completion": { "completionItem": { "snippetSupport": true } },
+                    "signatureHelp": { "signatureInformation": { "parameterInformation": { "labelOffsetSupport": true } } },
+                    "definition": {},
+                    "references": {},
+                    "rename": {},
+                    "codeAction": {
+                        "codeActionLiteralSupport": {
+                            "codeActionKind": { "valueSet": ["", "quickfix", "refactor", "source"] }
+                        }
+                    }
+                },
+                "workspace": {
+                    "applyEdit": true,
+                    "workspaceEdit": { "documentChanges": true },
+                    "workspaceFolders": true,
+                    "configuration": true,
+                }
+            }
+        });
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, Pending::Initialize);
+        self.write(&json!({ "jsonrpc": "2.0", "id": id, "method": "initialize", "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value, pending: Pending) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, pending);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn respond(&mut self, id: Value, result: Value) {
+        self.write(&json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    // Messages wait until the server has answered `initialize`
+    fn send(&mut self, message: Value) {
+        if self.initialized {
+            self.write(&message);
+        } else {
+            self.queued.push(message);
+        }
+    }
+
+    fn write(&mut self, message: &Value) {
+        let body = message.to_string();
+        let framed = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        if self.writer.write_all(framed.as_bytes()).and_then(|_| self.writer.flush()).is_err() {
+            self.running = false;
+        }
+    }
+
+    fn on_initialized(&mut self) {
+        self.initialized = true;
+        self.write(&json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }));
+        for message in std::mem::take(&mut self.queued) {
+            self.write(&message);
+        }
+    }
+}
+
+// Asks the server to shut down and exit, and kills it if it is still running after the grace period
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        let handshake = self.running && self.initialized;
+        if handshake {
+            let id = self.next_id;
+            self.write(&json!({ "jsonrpc": "2.0", "id": id, "method": "shutdown" }));
+            let deadline = Instant::now() + SHUTDOWN_GRACE;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                match self.receiver.recv_timeout(left) {
+                    Ok(Some(message)) if message.get("method").is_none() && message.get("id") == Some(&json!(id)) => break,
+                    Ok(Some(_)) => {}
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            self.write(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+        }
+        let Some(mut child) = self.child.take() else { return };
+        let deadline = Instant::now() + SHUTDOWN_GRACE;
+        while handshake && Instant::now() < deadline && matches!(child.try_wait(), Ok(None)) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+// ---------------------------------------------------------------------------
+// Positions and URIs
+
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let raw = rest.as_bytes();
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut i = 0;
+    while i < raw.len() {
+        // A '%' that does not start a valid escape is kept as it is
+        if raw[i] == b'%' && i + 2 < raw.len() {
+            if let (Some(high), Some(low)) = (hex(raw[i + 1]), hex(raw[i + 2])) {
+                bytes.push(high << 4 | low);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // "/C:/dir" on Windows
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+
+// LSP columns count UTF-16 code units
+pub fn to_position(text: &str, index: usize) -> Position {
+    let mut line = 0;
+    let mut character = 0;
+    for c in text.chars().take(index) {
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16();
+        }
+    }
+    Position { line, character }
+}
+
+pub fn to_index(text: &str, position: Position) -> usize {
+    let line_start = editor::line_start_index(text, position.line);
+    let mut units = 0;
+    let mut index = line_start;
+    for c in text.chars().skip(line_start) {
+        if c == '\n' || units >= position.character {
+            break;
+        }
+        units += c.len_utf16();
+        index += 1;
+    }
+    index
+}
+
+fn position_json(position: Position) -> Value {
+    json!({ "line": position.line, "character": position.character })
+}
+
+fn parse_position(value: &Value) -> Option<Position> {
+    Some(Position {
+        line: value.get("line")?.as_u64()? as usize,
+        character: value.get("character")?.as_u64()? as usize,
+    })
+}
+
+fn parse_range(value: &Value) -> Option<Range> {
+    Some(Range { start: parse_position(value.get("start")?)?, end: parse_position(value.get("end")?)? })
+}
+
+fn parse_location(value: &Value) -> Option<Location> {
+    // Location or LocationLink
+    let uri = value.get("uri").or_else(|| value.get("targetUri"))?.as_str()?;
+    let range = value.get("range").or_else(|| value.get("targetSelectionRange"))?;
+    Some(Location { path: uri_to_path(uri)?, range: parse_range(range)? })
+}
+
+fn parse_locations(value: &Value) -> Vec<Location> {
+    match value {
+        Value::Array(items) => items.iter().filter_map(parse_location).collect(),
+        Value::Null => Vec::new(),
+        single => parse_location(single).into_iter().collect(),
+    }
+}
+
+fn text_document(path: &Path) -> Value {
+    json!({ "uri": path_to_uri(path) })
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Hover contents can be a string, MarkupContent, MarkedString or a list of those
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(hover_text).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n\n"),
+        Value::Object(map) => map.get("value").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+        _ => String::new(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Editor-facing state
+
+struct SyncedDocument {
+    server: String,
+    version: i64,
+    hash: u64,
+}
+
+#[derive(Default)]
+pub struct LspState {
+    clients: HashMap<String, LspClient>,
+    failed: HashSet<String>,
+    documents: HashMap<PathBuf, SyncedDocument>,
+    pub diagnostics: BTreeMap<PathBuf, Vec<Diagnostic>>,
+    pub hover: Option<(usize, String)>,
+    hover_requested: Option<usize>,
+    pub signature: Option<SignatureHelp>,
+    pub references: Vec<Location>,
+    pub code_actions: Vec<CodeAction>,
+    pub code_actions_open: bool,
+    pub rename: Option<RenameState>,
+}
+
+fn server_key(app: &CodeEditorApp, path: &Path) -> Option<String> {
+    if !app.config.lsp.enabled {
+        return None;
+    }
+    let ext = path.extension()?.to_str()?;
+    app.config.lsp.servers.get(ext).map(|s| s.command.clone())
+}
+
+fn client_for<'a>(app: &'a mut CodeEditorApp, path: &Path) -> Option<&'a mut LspClient> {
+    let key = server_key(app, path)?;
+    app.lsp.clients.get_mut(&key).filter(|c| c.running)
+}
+
+pub fn status(app: &CodeEditorApp) -> Option<String> {
+    let path = app.file_path.as_ref()?;
+    let key = server_key(app, path)?;
+    if app.lsp.failed.contains(&key) {
+        return Some(format!("{} ✕", key));
+    }
+    let client = app.lsp.clients.get(&key)?;
+    Some(if !client.running {
+        format!("{} ✕", key)
+    } else if client.initialized {
+        format!("{} ✓", key)
+    } else {
+        format!("{} …", key)
+    })
+}
+
+// Starts servers for the active file and keeps every server's view of open files current
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if let Some(path) = app.file_path.clone() {
+        if let Some(key) = server_key(app, &path) {
+            if !app.lsp.clients.contains_key(&key) && !app.lsp.failed.contains(&key) {
+                start_server(app, ctx, &path, &key);
+            }
+            sync_document(app, &path, &key);
+        }
+    }
+
+    let open = app.panes.paths();
+    let closed: Vec<PathBuf> = app.lsp.documents.keys()
+        .filter(|p| !open.contains(p) && app.file_path.as_ref() != Some(*p))
+        .cloned()
+        .collect();
+    for path in closed {
+        if let Some(doc) = app.lsp.documents.remove(&path) {
+            if let Some(client) = app.lsp.clients.get_mut(&doc.server) {
+                client.notify("textDocument/didClose", json!({ "textDocument": text_document(&path) }));
+            }
+        }
+        app.lsp.diagnostics.remove(&path);
+    }
+
+    poll(app, ctx);
+}
+
+fn start_server(app: &mut CodeEditorApp, ctx: &egui::Context, path: &Path, key: &str) {
+    let Some(config) = path.extension()
+        .and_then(|e| e.to_str())
+        .and_then(|ext| app.config.lsp.servers.get(ext))
+        .cloned()
+    else {
+        return;
+    };
+    match LspClient::spawn(&config, app.root_folder.as_deref(), ctx) {
+        Ok(client) => {
+            app.lsp.clients.insert(key.to_string(), client);
+        }
+        Err(e) => {
+            app.lsp.failed.insert(key.to_string());
+            let message = format!("Could not start language server '{}': {}", config.command, e);
+            notifications::notify(app, Level::Error, message, Vec::new());
+        }
+    }
+}
+
+fn sync_document(app: &mut CodeEditorApp, path: &Path, key: &str) {
+    let hash = match app.panes.active_doc {
+        Some(doc) => panes::hash_contents(app, doc),
+        None => hash_text(&app.text),
+    };
+    let Some(client) = app.lsp.clients.get_mut(key) else { return };
+
+    match app.lsp.documents.get_mut(path) {
+        Some(doc) if doc.hash == hash => {}
+        Some(doc) => {
+            let text = app.text.clone();
+            doc.version += 1;
+            doc.hash = hash;
+            client.notify("textDocument/didChange", json!({
+                "textDocument": { "uri": path_to_uri(path), "version": doc.version },
+                "contentChanges": [{ "text": text }],
+            }));
+        }
+        None => {
+            let text = app.text.clone();
+            let language_id = crate::language::Language::from_path(path).id();
+            client.notify("textDocument/didOpen", json!({
+                "textDocument": { "uri": path_to_uri(path), "languageId": language_id, "version": 1, "text": text },
+            }));
+            app.lsp.documents.insert(path.to_path_buf(), SyncedDocument { server: key.to_string(), version: 1, hash });
+        }
+    }
+}
+
+// Stops every server; called when the window closes
+pub fn shutdown(app: &mut CodeEditorApp) {
+    app.lsp.clients.clear();
+    app.lsp.documents.clear();
+}
+
+pub fn did_save(app: &mut CodeEditorApp, path: &Path) {
+    if let Some(client) = client_for(app, path) {
+        client.notify("textDocument/didSave", json!({ "textDocument": text_document(path) }));
+    }
+}
+
+fn poll(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let keys: Vec<String> = app.lsp.clients.keys().cloned().collect();
+    for key in keys {
+        while let Some(client) = app.lsp.clients.get_mut(&key) {
+            let message = match client.receiver.try_recv() {
+                Ok(Some(message)) => message,
+                Ok(None) | Err(mpsc::TryRecvError::Disconnected) => {
+                    if client.running {
+                        client.running = false;
+                        notifications::notify(app, Level::Error, format!("Language server '{}' exited", key), Vec::new());
+                    }
+                    break;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+            };
+            handle_message(app, ctx, &key, message);
+        }
+    }
+}
+
+fn handle_message(app: &mut CodeEditorApp, ctx: &egui::Context, key: &str, message: Value) {
+    let method = message.get("method").and_then(|m| m.as_str()).map(str::to_string);
+    let id = message.get("id").cloned();
+
+    match (method, id) {
+        // Server -> client request
+        (Some(method), Some(id)) => {
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+            let result = match method.as_str() {
+                "workspace/configuration" => {
+                    let count = params.get("items").and_then(|i| i.as_array()).map_or(0, |i| i.len());
+                    Value::Array(vec![Value::Null; count])
+                }
+                "workspace/applyEdit" => {
+                    if let Some(edit) = params.get("edit") {
+                        apply_workspace_edit(app, ctx, edit);
+                    }
+                    json!({ "applied": true })
+                }
+                _ => Value::Null,
+            };
+            if let Some(client) = app.lsp.clients.get_mut(key) {
+                client.respond(id, result);
+            }
+        }
+        // Notification
+        (Some(method), None) => {
+            if method == "textDocument/publishDiagnostics" {
+                if let Some(params) = message.get("params") {
+                    publish_diagnostics(app, params);
+                }
+            } else if method == "window/showMessage" {
+                if let Some(text) = message.pointer("/params/message").and_then(|m| m.as_str()) {
+                    // LSP message types: 1 error, 2 warning, 3 info, 4 log
+                    let level = match message.pointer("/params/type").and_then(|t| t.as_u64()) {
+                        Some(1) => Level::Error,
+                        Some(2) => Level::Warning,
+                        _ => Level::Info,
+                    };
+                    notifications::notify(app, level, format!("{}: {}", key, text), Vec::new());
+                }
+            }
+        }
+        // Response
+        (None, Some(id)) => {
+            let Some(pending) = id.as_i64().and_then(|id| app.lsp.clients.get_mut(key)?.pending.remove(&id)) else { return };
+            let result = message.get("result").cloned().unwrap_or(Value::Null);
+            handle_response(app, ctx, key, pending, result);
+        }
+        (None, None) => {}
+    }
+}
+
+fn publish_diagnostics(app: &mut CodeEditorApp, params: &Value) {
+    let Some(path) = params.get("uri").and_then(|u| u.as_str()).and_then(uri_to_path) else { return };
+    let diagnostics: Vec<Diagnostic> = params.get("diagnostics")
+        .and_then(|d| d.as_array())
+        .map(|items| items.iter().filter_map(|d| {
+            Some(Diagnostic {
+                range: parse_range(d.get("range")?)?,
+                severity: match d.get("severity").and_then(|s| s.as_u64()) {
+                    Some(2) => Severity::Warning,
+                    Some(3) => Severity::Information,
+                    Some(4) => Severity::Hint,
+                    _ => Severity::Error,
+                },
+                message: d.get("message")?.as_str()?.to_string(),
+                source: d.get("source").and_then(|s| s.as_str()).map(str::to_string),
+                raw: d.clone(),
+            })
+        }).collect())
+        .unwrap_or_default();
+
+    if diagnostics.is_empty() {
+        app.lsp.diagnostics.remove(&path);
+    } else {
+        app.lsp.diagnostics.insert(path, diagnostics);
+    }
+}
+
+fn handle_response(app: &mut CodeEditorApp, ctx: &egui::Context, key: &str, pending: Pending, result: Value) {
+    match pending {
+        Pending::Initialize => {
+            if let Some(client) = app.lsp.clients.get_mut(key) {
+                client.on_initialized();
+            }
+        }
+        Pending::Hover { path, index } => {
+            if app.file_path.as_ref() == Some(&path) && app.lsp.hover_requested == Some(index) {
+                let text = result.get("contents").map(hover_text).unwrap_or_default();
+                app.lsp.hover = (!text.trim().is_empty()).then(|| (index, text.trim().to_string()));
+            }
+        }
+        Pending::Completion { path } => {
+            if app.file_path.as_ref() != Some(&path) {
+                return;
+            }
+            let items = match &result {
+                Value::Array(items) => items.clone(),
+                Value::Object(list) => list.get("items").and_then(|i| i.as_array()).cloned().unwrap_or_default(),
+                _ => Vec::new(),
+            };
+            let candidates = items.iter().filter_map(|item| {
+                let label = item.get("label")?.as_str()?.to_string();
+                let insert = item.pointer("/textEdit/newText")
+                    .or_else(|| item.get("insertText"))
+                    .and_then(|t| t.as_str())
+                    .map(str::to_string);
+                let filter = item.get("filterText").and_then(|t| t.as_str()).map(str::to_string);
+                Some(Candidate {
+                    word: filter.unwrap_or_else(|| label.clone()),
+                    source: Source::Language,
+                    count: 0,
+                    distance: None,
+                    label: Some(label),
+                    insert_text: insert,
+                    snippet: item.get("insertTextFormat").and_then(|f| f.as_u64()) == Some(2),
+                    detail: item.get("detail").and_then(|d| d.as_str()).map(str::to_string),
+                })
+            }).collect();
+            completion::extend(app, candidates);
+        }
+        Pending::SignatureHelp => {
+            app.lsp.signature = parse_signature(&result);
+        }
+        Pending::Definition => {
+            if let Some(location) = parse_locations(&result).into_iter().next() {
+                open_location(app, ctx, &location);
+            }
+        }
+        Pending::References => {
+            app.lsp.references = parse_locations(&result);
+            app.bottom_panel = crate::app::BottomPanel::References;
+            app.show_terminal = true;
+        }
+        Pending::Rename => {
+            if !result.is_null() {
+                apply_workspace_edit(app, ctx, &result);
+            }
+        }
+        Pending::CodeActions => {
+            app.lsp.code_actions = result.as_array()
+                .map(|actions| actions.iter().filter_map(|a| {
+                    Some(CodeAction { title: a.get("title")?.as_str()?.to_string(), raw: a.clone() })
+                }).collect())
+                .unwrap_or_default();
+            app.lsp.code_actions_open = !app.lsp.code_actions.is_empty();
+        }
+        Pending::Other => {}
+    }
+}
+
+fn parse_signature(result: &Value) -> Option<SignatureHelp> {
+    let signatures = result.get("signatures")?.as_array()?;
+    let active = result.get("activeSignature").and_then(|a| a.as_u64()).unwrap_or(0) as usize;
+    let signature = signatures.get(active).or_else(|| signatures.first())?;
+    let label = signature.get("label")?.as_str()?.to_string();
+    let active_parameter = signature.get("activeParameter")
+        .or_else(|| result.get("activeParameter"))
+        .and_then(|a| a.as_u64())
+        .unwrap_or(0) as usize;
+    let parameter = signature.get("parameters")
+        .and_then(|p| p.as_array())
+        .and_then(|p| p.get(active_parameter))
+        .and_then(|p| p.get("label"));
+    // Parameter labels are either a substring of the label or [start, end] UTF-16 offsets
+    let range = match parameter {
+        Some(Value::String(s)) => label.find(s.as_str()).map(|start| (start, start + s.len())),
+        Some(Value::Array(offsets)) if offsets.len() == 2 => {
+            let utf16_to_byte = |offset: u64| {
+                let mut units = 0;
+                label.char_indices()
+                    .find(|(_, c)| { let found = units >= offset as usize; units += c.len_utf16(); found })
+                    .map_or(label.len(), |(i, _)| i)
+            };
+            Some((utf16_to_byte(offsets[0].as_u64()?), utf16_to_byte(offsets[1].as_u64()?)))
+        }
+        _ => None,
+    };
+    Some(SignatureHelp { label, active_parameter: range })
+}
+
+// ---------------------------------------------------------------------------
+// Requests
+
+fn cursor_params(app: &CodeEditorApp) -> Option<(PathBuf, Value)> {
+    let path = app.file_path.clone()?;
+    let (primary, _) = app.cursor?;
+    let params = json!({
+        "textDocument": text_document(&path),
+        "position": position_json(to_position(&app.text, primary)),
+    });
+    Some((path, params))
+}
+
+pub fn request_hover(app: &mut CodeEditorApp, index: usize) {
+    if app.lsp.hover_requested == Some(index) {
+        return;
+    }
+    let Some(path) = app.file_path.clone() else { return };
+    let position = to_position(&app.text, index);
+    app.lsp.hover_requested = Some(index);
+    app.lsp.hover = None;
+    if let Some(client) = client_for(app, &path) {
+        let params = json!({ "textDocument": text_document(&path), "position": position_json(position) });
+        client.request("textDocument/hover", params, Pending::Hover { path, index });
+    }
+}
+
+pub fn clear_hover(app: &mut CodeEditorApp) {
+    app.lsp.hover = None;
+    app.lsp.hover_requested = None;
+}
+
+pub fn request_completion(app: &mut CodeEditorApp) {
+    let Some((path, params)) = cursor_params(app) else { return };
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/completion", params, Pending::Completion { path });
+    }
+}
+
+pub fn request_signature_help(app: &mut CodeEditorApp) {
+    let Some((path, params)) = cursor_params(app) else { return };
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/signatureHelp", params, Pending::SignatureHelp);
+    }
+}
+
+pub fn goto_definition(app: &mut CodeEditorApp) {
+    let Some((path, params)) = cursor_params(app) else { return };
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/definition", params, Pending::Definition);
+    }
+}
+
+pub fn find_references(app: &mut CodeEditorApp) {
+    let Some((path, mut params)) = cursor_params(app) else { return };
+    params["context"] = json!({ "includeDeclaration": true });
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/references", params, Pending::References);
+    }
+}
+
+pub fn start_rename(app: &mut CodeEditorApp) {
+    let Some((primary, _)) = app.cursor else { return };
+    let chars: Vec<char> = app.text.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let start = chars[..primary.min(chars.len())].iter().rposition(|c| !is_word(c)).map_or(0, |i| i + 1);
+    let end = primary + chars[primary.min(chars.len())..].iter().take_while(|c| is_word(c)).count();
+    let word: String = chars[start..end].iter().collect();
+    if !word.is_empty() {
+        app.lsp.rename = Some(RenameState { new_name: word, index: primary });
+    }
+}
+
+pub fn commit_rename(app: &mut CodeEditorApp) {
+    let Some(rename) = app.lsp.rename.take() else { return };
+    let Some(path) = app.file_path.clone() else { return };
+    let params = json!({
+        "textDocument": text_document(&path),
+        "position": position_json(to_position(&app.text, rename.index)),
+        "newName": rename.new_name,
+    });
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/rename", params, Pending::Rename);
+    }
+}
+
+pub fn request_code_actions(app: &mut CodeEditorApp) {
+    let Some(path) = app.file_path.clone() else { return };
+    let Some((a, b)) = app.cursor else { return };
+    let range = Range { start: to_position(&app.text, a.min(b)), end: to_position(&app.text, a.max(b)) };
+    let line = range.start.line;
+    let diagnostics: Vec<Value> = app.lsp.diagnostics.get(&path)
+        .map(|d| d.iter()
+            .filter(|d| d.range.start.line <= line && line <= d.range.end.line)
+            .map(|d| d.raw.clone())
+            .collect())
+        .unwrap_or_default();
+    let params = json!({
+        "textDocument": text_document(&path),
+        "range": { "start": position_json(range.start), "end": position_json(range.end) },
+        "context": { "diagnostics": diagnostics },
+    });
+    if let Some(client) = client_for(app, &path) {
+        client.request("textDocument/codeAction", params, Pending::CodeActions);
+    }
+}
+
+pub fn apply_code_action(app: &mut CodeEditorApp, ctx: &egui::Context, index: usize) {
+    app.lsp.code_actions_open = false;
+    let Some(action) = app.lsp.code_actions.get(index).cloned() else { return };
+    if let Some(edit) = action.raw.get("edit") {
+        apply_workspace_edit(app, ctx, edit);
+    }
+    // A bare Command, or a CodeAction carrying one
+    let command = action.raw.get("command").filter(|c| c.is_object()).unwrap_or(&action.raw);
+    if let (Some(name), Some(path)) = (command.get("command").and_then(|c| c.as_str()), app.file_path.clone()) {
+        let arguments = command.get("arguments").cloned().unwrap_or(json!([]));
+        if let Some(client) = client_for(app, &path) {
+            client.request("workspace/executeCommand", json!({ "command": name, "arguments": arguments }), Pending::Other);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Applying results
+
+pub fn open_location(app: &mut CodeEditorApp, ctx: &egui::Context, location: &Location) {
+    if app.file_path.as_ref() != Some(&location.path) {
+        file_ops::open_path(app, location.path.clone());
+    }
+    if app.file_path.as_ref() == Some(&location.path) {
+        let index = to_index(&app.text, location.range.start);
+        editor::jump_to(app, ctx, index);
+    }
+}
+
+fn apply_text_edits(text: &mut String, edits: &[Value]) {
+    let mut resolved: Vec<(usize, usize, String)> = edits.iter().filter_map(|edit| {
+        let range = parse_range(edit.get("range")?)?;
+        let new_text = edit.get("newText")?.as_str()?.to_string();
+        Some((to_index(text, range.start), to_index(text, range.end), new_text))
+    }).collect();
+    // Back to front so earlier offsets stay valid
+    resolved.sort_by_key(|edit| std::cmp::Reverse(edit.0));
+    for (start, end, new_text) in resolved {
+        editor::replace_chars(text, start, end, &new_text);
+    }
+}
+
+pub fn apply_workspace_edit(app: &mut CodeEditorApp, ctx: &egui::Context, edit: &Value) {
+    let mut per_file: Vec<(PathBuf, Vec<Value>)> = Vec::new();
+    // A server may send both forms; `documentChanges` then replaces `changes`
+    if let Some(document_changes) = edit.get("documentChanges").and_then(|c| c.as_array()) {
+        for change in document_changes {
+            let uri = change.pointer("/textDocument/uri").and_then(|u| u.as_str());
+            if let (Some(path), Some(edits)) = (uri.and_then(uri_to_path), change.get("edits").and_then(|e| e.as_array())) {
+                per_file.push((path, edits.clone()));
+            }
+        }
+    } else if let Some(changes) = edit.get("changes").and_then(|c| c.as_object()) {
+        for (uri, edits) in changes {
+            if let (Some(path), Some(edits)) = (uri_to_path(uri), edits.as_array()) {
+                per_file.push((path, edits.clone()));
+            }
+        }
+    }
+
+    let mut changed_files = 0;
+    for (path, edits) in per_file {
+        if app.file_path.as_ref() == Some(&path) {
+            let cursor = app.cursor.map(|(p, _)| to_position(&app.text, p));
+            apply_text_edits(&mut app.text, &edits);
+            panes::mark_active_edited(app);
+            if let Some(position) = cursor {
+                let index = to_index(&app.text, position);
+                editor::select(app, ctx, index, index);
+            }
+            changed_files += 1;
+        } else if let Some(doc) = app.panes.find(&path) {
+            // Open files take the edit in their buffer; the user saves it like any other change
+            if let Some(document) = app.panes.documents.get_mut(&doc).filter(|d| d.bytes.is_none()) {
+                apply_text_edits(&mut document.text, &edits);
+                panes::mark_edited(app, doc);
+                changed_files += 1;
+            }
+        } else if let Ok(mut content) = fs::read_to_string(&path) {
+            apply_text_edits(&mut content, &edits);
+            match file_ops::write_atomic(&path, &content, app.config.save.keep_backup) {
+                Ok(()) => changed_files += 1,
+                Err(e) => notifications::notify(app, Level::Error, format!("Could not write {}: {}", path.display(), e), Vec::new()),
+            }
+        }
+    }
+    if changed_files > 0 {
+        let mut output = app.terminal_output.lock().unwrap();
+        output.push_str(&format!("✓ Applied edits to {} file(s)\n> ", changed_files));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Painting
+
+// Squiggles under diagnostics of the active file; returns the message under the pointer
+pub fn paint_diagnostics(app: &CodeEditorApp, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) -> Option<String> {
+    let path = app.file_path.as_ref()?;
+    let diagnostics = app.lsp.diagnostics.get(path)?;
+    let painter = ui.painter_at(output.text_clip_rect);
+    let origin = output.text_draw_pos.to_vec2();
+    let pointer = ui.input(|i| i.pointer.hover_pos());
+    let mut hovered = None;
+
+    for diagnostic in diagnostics {
+        let start = to_index(&app.text, diagnostic.range.start);
+        let mut end = to_index(&app.text, diagnostic.range.end);
+        if end <= start {
+            end = start + 1;
+        }
+        let mut segment_start = start;
+        let line_ends = app.text.chars().enumerate()
+            .skip(start)
+            .take(end - start)
+            .filter(|&(_, c)| c == '\n')
+            .map(|(i, _)| i);
+        for segment_end in line_ends.chain(std::iter::once(end)) {
+            let from = output.galley.pos_from_cursor(&output.galley.from_ccursor(CCursor::new(segment_start))).translate(origin);
+            let to = output.galley.pos_from_cursor(&output.galley.from_ccursor(CCursor::new(segment_end))).translate(origin);
+            let rect = egui::Rect::from_min_max(from.left_top(), egui::pos2(to.right_bottom().x.max(from.right() + 4.0), from.bottom()));
+            paint_squiggle(&painter, rect, diagnostic.severity.color());
+            if pointer.is_some_and(|p| rect.contains(p)) {
+                hovered = Some(diagnostic.message.clone());
+            }
+            segment_start = segment_end + 1;
+        }
+    }
+    hovered
+}
+
+fn paint_squiggle(painter: &egui::Painter, rect: egui::Rect, color: egui::Color32) {
+    let y = rect.bottom() - 1.0;
+    let mut points = Vec::new();
+    let mut x = rect.left();
+    let mut up = true;
+    while x <= rect.right() {
+        points.push(egui::pos2(x, if up { y - 1.5 } else { y + 1.0 }));
+        x += 3.0;
+        up = !up;
+    }
+    if points.len() > 1 {
+        painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    // Servers are keyed by command; "rs" maps to this in the default settings
+    const SERVER: &str = "rust-analyzer";
+
+    // In-memory stand-in for a child's stdin/stdout
+    #[derive(Clone)]
+    struct PipeWriter(mpsc::Sender<Vec<u8>>);
+
+    struct PipeReader {
+        receiver: Receiver<Vec<u8>>,
+        buffer: VecDeque<u8>,
+    }
+
+    impl Write for PipeWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.send(buf.to_vec()).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for PipeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buffer.is_empty() {
+                match self.receiver.recv() {
+                    Ok(bytes) => self.buffer.extend(bytes),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let count = buf.len().min(self.buffer.len());
+            for (slot, byte) in buf.iter_mut().zip(self.buffer.drain(..count)) {
+                *slot = byte;
+            }
+            Ok(count)
+        }
+    }
+
+    fn pipe() -> (PipeWriter, PipeReader) {
+        let (sender, receiver) = mpsc::channel();
+        (PipeWriter(sender), PipeReader { receiver, buffer: VecDeque::new() })
+    }
+
+    // Answers requests through `answer` and reports every message the client sends
+    struct MockServer {
+        received: Receiver<Value>,
+        writer: PipeWriter,
+    }
+
+    impl MockServer {
+        fn start(answer: impl Fn(&str, &Value) -> Value + Send + 'static) -> (Self, PipeReader, PipeWriter) {
+            let (client_writer, server_reader) = pipe();
+            let (server_writer, client_reader) = pipe();
+            let (sender, received) = mpsc::channel();
+            let mut writer = server_writer.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(server_reader);
+                while let Some(message) = read_message(&mut reader) {
+                    if let (Some(method), Some(id)) = (message.get("method").and_then(|m| m.as_str()), message.get("id")) {
+                        let result = answer(method, message.get("params").unwrap_or(&Value::Null));
+                        send(&mut writer, &json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+                    }
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            });
+            (Self { received, writer: server_writer }, client_reader, client_writer)
+        }
+
+        fn notify(&mut self, method: &str, params: Value) {
+            send(&mut self.writer, &json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+        }
+
+        fn request(&mut self, id: i64, method: &str, params: Value) {
+            send(&mut self.writer, &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        }
+
+        // Next message matching `method`, skipping others; None matches responses
+        fn expect(&self, method: Option<&str>) -> Value {
+            let deadline = Instant::now() + TIMEOUT;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let message = self.received.recv_timeout(left).unwrap_or_else(|_| panic!("no {:?} from the client", method));
+                if message.get("method").and_then(|m| m.as_str()) == method {
+                    return message;
+                }
+            }
+        }
+    }
+
+    fn send(writer: &mut PipeWriter, message: &Value) {
+        let body = message.to_string();
+        let _ = writer.write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes());
+    }
+
+    struct Fixture {
+        app: CodeEditorApp,
+        ctx: egui::Context,
+        server: MockServer,
+        dir: tempfile::TempDir,
+    }
+
+    impl Fixture {
+        // Opens `files` (the first one active) with a mock server attached
+        fn new(files: &[(&str, &str)], answer: impl Fn(&Path, &str, &Value) -> Value + Send + 'static) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let root = dir.path().to_path_buf();
+            let mut app = CodeEditorApp { root_folder: Some(root.clone()), ..Default::default() };
+            let mut docs = Vec::new();
+            for (name, text) in files {
+                let path = root.join(name);
+                fs::write(&path, text).unwrap();
+                docs.push(panes::add_document(&mut app, Some(path), text.to_string()));
+            }
+            for &doc in docs.iter().rev() {
+                panes::show(&mut app, doc);
+            }
+
+            let server_root = root.clone();
+            let (server, reader, writer) = MockServer::start(move |method, params| answer(&server_root, method, params));
+            let ctx = egui::Context::default();
+            let mut client = LspClient::from_streams(reader, writer, &ctx);
+            client.initialize(Some(&root));
+            app.lsp.clients.insert(SERVER.to_string(), client);
+            Self { app, ctx, server, dir }
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        fn uri(&self, name: &str) -> String {
+            path_to_uri(&self.path(name))
+        }
+
+        // Runs the per-frame sync until `done` holds
+        fn pump(&mut self, done: impl Fn(&CodeEditorApp) -> bool) {
+            let deadline = Instant::now() + TIMEOUT;
+            loop {
+                sync(&mut self.app, &self.ctx);
+                if done(&self.app) {
+                    return;
+                }
+                assert!(Instant::now() < deadline, "timed out waiting for the client");
+                thread::sleep(Duration::from_millis(5));
+            }
+        }
+
+        fn initialized(&mut self) {
+            self.pump(|app| app.lsp.clients[SERVER].initialized);
+            self.server.expect(Some("textDocument/didOpen"));
+        }
+
+        fn place_cursor(&mut self, index: usize) {
+            self.app.cursor = Some((index, index));
+        }
+    }
+
+    fn edit(line: usize, start: usize, end: usize, new_text: &str) -> Value {
+        json!({
+            "range": { "start": { "line": line, "character": start }, "end": { "line": line, "character": end } },
+            "newText": new_text,
+        })
+    }
+
+    fn no_answers(_: &Path, _: &str, _: &Value) -> Value {
+        Value::Null
+    }
+
+    #[test]
+    fn initialize_queues_messages_until_answered() {
+        let mut fixture = Fixture::new(&[("main.rs", "fn main() {}\n")], no_answers);
+        let initialize = fixture.server.expect(Some("initialize"));
+        assert_eq!(initialize.pointer("/params/rootUri").and_then(|u| u.as_str()), Some(fixture.uri("").trim_end_matches('/')));
+        assert_eq!(initialize.pointer("/params/capabilities/workspace/applyEdit"), Some(&json!(true)));
+
+        fixture.pump(|app| app.lsp.clients[SERVER].initialized);
+        assert_eq!(status(&fixture.app).as_deref(), Some("rust-analyzer ✓"));
+        fixture.server.expect(Some("initialized"));
+        let open = fixture.server.expect(Some("textDocument/didOpen"));
+        assert_eq!(open.pointer("/params/textDocument/uri").and_then(|u| u.as_str()), Some(fixture.uri("main.rs").as_str()));
+        assert_eq!(open.pointer("/params/textDocument/languageId").and_then(|u| u.as_str()), Some("rust"));
+        assert_eq!(open.pointer("/params/textDocument/text").and_then(|u| u.as_str()), Some("fn main() {}\n"));
+    }
+
+    #[test]
+    fn edits_are_sent_as_full_text_changes() {
+        let mut fixture = Fixture::new(&[("main.rs", "fn main() {}\n")], no_answers);
+        fixture.initialized();
+
+        fixture.app.text.insert_str(11, " run(); ");
+        sync(&mut fixture.app, &fixture.ctx);
+        let change = fixture.server.expect(Some("textDocument/didChange"));
+        assert_eq!(change.pointer("/params/textDocument/version"), Some(&json!(2)));
+        assert_eq!(change.pointer("/params/contentChanges/0/text").and_then(|t| t.as_str()), Some("fn main() { run(); }\n"));
+
+        // Unchanged text is not resent
+        sync(&mut fixture.app, &fixture.ctx);
+        fixture.app.text.push('x');
+        sync(&mut fixture.app, &fixture.ctx);
+        let change = fixture.server.expect(Some("textDocument/didChange"));
+        assert_eq!(change.pointer("/params/textDocument/version"), Some(&json!(3)));
+    }
+
+    #[test]
+    fn published_diagnostics_replace_previous_ones() {
+        let mut fixture = Fixture::new(&[("main.rs", "fn main() {\n    let x = 1\n}\n")], no_answers);
+        fixture.initialized();
+        let path = fixture.path("main.rs");
+        let uri = fixture.uri("main.rs");
+
+        fixture.server.notify("textDocument/publishDiagnostics", json!({
+            "uri": uri,
+            "diagnostics": [
+                { "range": { "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 9 } }, "severity": 2, "message": "unused variable", "source": "rustc" },
+                { "range": { "start": { "line": 1, "character": 13 }, "end": { "line": 1, "character": 13 } }, "message": "expected `;`" },
+            ],
+        }));
+        fixture.pump(|app| app.lsp.diagnostics.contains_key(&path));
+        let diagnostics = &fixture.app.lsp.diagnostics[&path];
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].source.as_deref(), Some("rustc"));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].range.start, Position { line: 1, character: 13 });
+
+        fixture.server.notify("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }));
+        fixture.pump(|app| !app.lsp.diagnostics.contains_key(&path));
+    }
+
+    #[test]
+    fn show_message_becomes_a_notification() {
+        let mut fixture = Fixture::new(&[("main.rs", "")], no_answers);
+        fixture.initialized();
+        fixture.server.notify("window/showMessage", json!({ "type": 2, "message": "indexing failed" }));
+        fixture.pump(|app| !app.notifications.history.is_empty());
+        let notification = &fixture.app.notifications.history[0];
+        assert_eq!(notification.level, Level::Warning);
+        assert_eq!(notification.message, "rust-analyzer: indexing failed");
+    }
+
+    #[test]
+    fn hover_shows_markup_contents() {
+        let mut fixture = Fixture::new(&[("main.rs", "fn main() {\n    helper();\n}\n")], |_, method, _| match method {
+            "textDocument/hover" => json!({ "contents": { "kind": "markdown", "value": "fn helper()\n" } }),
+            _ => Value::Null,
+        });
+        fixture.initialized();
+
+        request_hover(&mut fixture.app, 18);
+        let request = fixture.server.expect(Some("textDocument/hover"));
+        assert_eq!(request.pointer("/params/position"), Some(&json!({ "line": 1, "character": 6 })));
+        fixture.pump(|app| app.lsp.hover.is_some());
+        assert_eq!(fixture.app.lsp.hover, Some((18, "fn helper()".to_string())));
+    }
+
+    #[test]
+    fn completion_items_join_the_popup() {
+        let text = "fn main() {\n    pri\n}\n";
+        let mut fixture = Fixture::new(&[("main.rs", text)], |_, method, _| match method {
+            "textDocument/completion" => json!({
+                "isIncomplete": false,
+                "items": [
+                    { "label": "println!", "insertText": "println!(\"$1\")", "insertTextFormat": 2, "detail": "macro" },
+                    { "label": "print!", "filterText": "print", "textEdit": { "newText": "print!($0)" } },
+                ],
+            }),
+            _ => Value::Null,
+        });
+        fixture.initialized();
+        fixture.place_cursor(19);
+
+        request_completion(&mut fixture.app);
+        let request = fixture.server.expect(Some("textDocument/completion"));
+        assert_eq!(request.pointer("/params/position"), Some(&json!({ "line": 1, "character": 7 })));
+        fixture.pump(|app| app.completion.active);
+        let language: Vec<_> = fixture.app.completion.items.iter().filter(|i| i.source == Source::Language).collect();
+        let labels: Vec<&str> = language.iter().map(|i| i.label.as_str()).collect();
+        assert_eq!(labels, vec!["print!", "println!"]);
+        assert_eq!(language[1].detail.as_deref(), Some("macro"));
+
+        let ctx = fixture.ctx.clone();
+        let index = fixture.app.completion.items.iter().position(|i| i.label == "println!").unwrap();
+        completion::accept(&mut fixture.app, &ctx, index);
+        assert_eq!(fixture.app.text, "fn main() {\n    println!(\"\")\n}\n");
+    }
+
+    #[test]
+    fn definition_moves_the_caret() {
+        let text = "fn helper() {}\nfn main() {\n    helper();\n}\n";
+        let mut fixture = Fixture::new(&[("main.rs", text)], |root, method, _| match method {
+            "textDocument/definition" => json!([{
+                "targetUri": path_to_uri(&root.join("main.rs")),
+                "targetRange": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 14 } },
+                "targetSelectionRange": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 9 } },
+            }]),
+            _ => Value::Null,
+        });
+        fixture.initialized();
+        fixture.place_cursor(30);
+
+        goto_definition(&mut fixture.app);
+        fixture.server.expect(Some("textDocument/definition"));
+        fixture.pump(|app| app.cursor == Some((3, 3)));
+        assert!(fixture.app.scroll_to_cursor);
+    }
+
+    #[test]
+    fn rename_edits_open_buffers_and_writes_closed_files() {
+        let files = [
+            ("main.rs", "mod lib;\nfn main() { lib::old(); }\n"),
+            ("lib.rs", "pub fn old() {}\n"),
+        ];
+        let mut fixture = Fixture::new(&files, |root, method, _| {
+            match method {
+                // `changes` duplicates the main.rs edit and must be ignored next to `documentChanges`
+                "textDocument/rename" => json!({
+                    "changes": {
+                        path_to_uri(&root.join("main.rs")): [edit(1, 17, 20, "new")],
+                        path_to_uri(&root.join("closed.rs")): [edit(0, 0, 0, "// ignored\n")],
+                    },
+                    "documentChanges": [
+                        {
+                            "textDocument": { "uri": path_to_uri(&root.join("main.rs")), "version": 1 },
+                            "edits": [edit(1, 17, 20, "new")],
+                        },
+                        {
+                            "textDocument": { "uri": path_to_uri(&root.join("lib.rs")), "version": null },
+                            "edits": [edit(0, 7, 10, "new")],
+                        },
+                        {
+                            "textDocument": { "uri": path_to_uri(&root.join("closed.rs")), "version": null },
+                            "edits": [edit(0, 9, 12, "new"), edit(1, 0, 0, "// renamed\n")],
+                        },
+                    ],
+                }),
+                _ => Value::Null,
+            }
+        });
+        fs::write(fixture.path("closed.rs"), "use lib::old;\n").unwrap();
+        fixture.initialized();
+        // Unsaved edits in the open buffer must survive the rename
+        let lib = fixture.app.panes.find(&fixture.path("lib.rs")).unwrap();
+        fixture.app.panes.documents.get_mut(&lib).unwrap().text.push_str("// draft\n");
+        fixture.place_cursor(27);
+
+        start_rename(&mut fixture.app);
+        fixture.app.lsp.rename.as_mut().unwrap().new_name = "new".to_string();
+        commit_rename(&mut fixture.app);
+        let request = fixture.server.expect(Some("textDocument/rename"));
+        assert_eq!(request.pointer("/params/position"), Some(&json!({ "line": 1, "character": 18 })));
+        assert_eq!(request.pointer("/params/newName"), Some(&json!("new")));
+        fixture.pump(|app| app.text.contains("new"));
+
+        assert_eq!(fixture.app.text, "mod lib;\nfn main() { lib::new(); }\n");
+        assert_eq!(fixture.app.panes.documents[&lib].text, "pub fn new() {}\n// draft\n");
+        assert!(panes::is_dirty(&fixture.app, lib));
+        assert_eq!(fs::read_to_string(fixture.path("lib.rs")).unwrap(), "pub fn old() {}\n");
+        assert_eq!(fs::read_to_string(fixture.path("closed.rs")).unwrap(), "use lib::new;\n// renamed\n");
+    }
+
+    #[test]
+    fn code_actions_apply_edits_and_run_commands() {
+        let text = "fn main() {\n    let x = 1\n}\n";
+        let mut fixture = Fixture::new(&[("main.rs", text)], |root, method, _| match method {
+            "textDocument/codeAction" => json!([{
+                "title": "Insert `;`",
+                "kind": "quickfix",
+                "edit": { "changes": { path_to_uri(&root.join("main.rs")): [edit(1, 13, 13, ";")] } },
+                "command": { "title": "Check", "command": "rust-analyzer.runFlycheck", "arguments": [1] },
+            }]),
+            _ => Value::Null,
+        });
+        fixture.initialized();
+        let uri = fixture.uri("main.rs");
+        fixture.server.notify("textDocument/publishDiagnostics", json!({
+            "uri": uri,
+            "diagnostics": [{ "range": { "start": { "line": 1, "character": 13 }, "end": { "line": 1, "character": 13 } }, "message": "expected `;`" }],
+        }));
+        let path = fixture.path("main.rs");
+        fixture.pump(|app| app.lsp.diagnostics.contains_key(&path));
+        fixture.place_cursor(25);
+
+        request_code_actions(&mut fixture.app);
+        let request = fixture.server.expect(Some("textDocument/codeAction"));
+        assert_eq!(request.pointer("/params/context/diagnostics/0/message"), Some(&json!("expected `;`")));
+        fixture.pump(|app| app.lsp.code_actions_open);
+        assert_eq!(fixture.app.lsp.code_actions[0].title, "Insert `;`");
+
+        let ctx = fixture.ctx.clone();
+        apply_code_action(&mut fixture.app, &ctx, 0);
+        assert_eq!(fixture.app.text, "fn main() {\n    let x = 1;\n}\n");
+        assert!(!fixture.app.lsp.code_actions_open);
+        let command = fixture.server.expect(Some("workspace/executeCommand"));
+        assert_eq!(command.pointer("/params/command"), Some(&json!("rust-analyzer.runFlycheck")));
+        assert_eq!(command.pointer("/params/arguments"), Some(&json!([1])));
+    }
+
+    #[test]
+    fn server_apply_edit_requests_are_answered() {
+        let mut fixture = Fixture::new(&[("main.rs", "fn main() {}\n")], no_answers);
+        fixture.initialized();
+        let uri = fixture.uri("main.rs");
+        fixture.server.request(7, "workspace/applyEdit", json!({ "edit": { "changes": { uri: [edit(0, 3, 7, "start")] } } }));
+        fixture.pump(|app| app.text.starts_with("fn start"));
+        let response = fixture.server.expect(None);
+        assert_eq!(response.get("id"), Some(&json!(7)));
+        assert_eq!(response.pointer("/result/applied"), Some(&json!(true)));
+    }
+
+    #[test]
+    fn shutdown_sends_shutdown_then_exit() {
+        let mut fixture = Fixture::new(&[("main.rs", "")], no_answers);
+        fixture.initialized();
+        shutdown(&mut fixture.app);
+        let request = fixture.server.expect(Some("shutdown"));
+        assert!(request.get("id").is_some());
+        fixture.server.expect(Some("exit"));
+        assert!(fixture.app.lsp.clients.is_empty());
+    }
+
+    #[test]
+    fn uris_round_trip() {
+        let path = Path::new("/tmp/a dir/ü#1.rs");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///tmp/a%20dir/%C3%BC%231.rs");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(uri_to_path("file:///C:/src/main.rs"), Some(PathBuf::from("C:/src/main.rs")));
+    }
+
+    #[test]
+    fn malformed_escapes_are_kept_as_text() {
+        assert_eq!(uri_to_path("file:///tmp/%ü1.rs"), Some(PathBuf::from("/tmp/%ü1.rs")));
+        assert_eq!(uri_to_path("file:///tmp/%+1%4"), Some(PathBuf::from("/tmp/%+1%4")));
+        assert_eq!(uri_to_path("file:///tmp/100%"), Some(PathBuf::from("/tmp/100%")));
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let text = "a😀b\nc";
+        assert_eq!(to_position(text, 2), Position { line: 0, character: 3 });
+        assert_eq!(to_index(text, Position { line: 0, character: 3 }), 2);
+        assert_eq!(to_index(text, Position { line: 1, character: 9 }), 5);
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::VecDeque;
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::editor::{self, EditorCommand};
+use crate::notifications::{self, Level};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MacroStep {
+    Text(String),
+    Key { key: egui::Key, modifiers: egui::Modifiers },
+    Paste(String),
+    Copy,
+    Cut,
+    Command(EditorCommand),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub key: egui::Key,
+    pub modifiers: egui::Modifiers,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Macro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+    pub binding: Option<KeyBinding>,
+}
+
+pub enum PlaybackStep {
+    GotoLine(usize),
+    Step(MacroStep),
+}
+
+// What a single frame of playback does before the text edit handles its events
+#[derive(Default)]
+pub struct PlaybackFrame {
+    pub goto_line: Option<usize>,
+    pub command: Option<EditorCommand>,
+}
+
+pub struct MacroState {
+    pub recording: Option<Vec<MacroStep>>,
+    pub last: Vec<MacroStep>,
+    pub playback: VecDeque<PlaybackStep>,
+    pub repeat_count: u32,
+    pub save_name: String,
+    pub binding_capture: Option<usize>,
+}
+
+impl Default for MacroState {
+    fn default() -> Self {
+        Self {
+            recording: None,
+            last: Vec::new(),
+            playback: VecDeque::new(),
+            repeat_count: 1,
+            save_name: String::new(),
+            binding_capture: None,
+        }
+    }
+}
+
+impl MacroStep {
+    fn from_event(event: &egui::Event) -> Option<Self> {
+        match event {
+            egui::Event::Text(text) => Some(MacroStep::Text(text.clone())),
+            egui::Event::Key { key, pressed: true, modifiers, .. } => Some(MacroStep::Key {
+                key: *key,
+                modifiers: *modifiers,
+            }),
+            egui::Event::Paste(text) => Some(MacroStep::Paste(text.clone())),
+            egui::Event::Copy => Some(MacroStep::Copy),
+            egui::Event::Cut => Some(MacroStep::Cut),
+            _ => None,
+        }
+    }
+
+    fn to_events(&self) -> Vec<egui::Event> {
+        match self {
+            MacroStep::Text(text) => vec![egui::Event::Text(text.clone())],
+            MacroStep::Key { key, modifiers } => vec![
+                egui::Event::Key { key: *key, pressed: true, repeat: false, modifiers: *modifiers },
+                egui::Event::Key { key: *key, pressed: false, repeat: false, modifiers: *modifiers },
+            ],
+            MacroStep::Paste(text) => vec![egui::Event::Paste(text.clone())],
+            MacroStep::Copy => vec![egui::Event::Copy],
+            MacroStep::Cut => vec![egui::Event::Cut],
+            MacroStep::Command(_) => Vec::new(),
+        }
+    }
+
+    // Keys that the editor handles as shortcuts before the text edit sees the frame's events
+    fn is_command(&self) -> bool {
+        match self {
+            MacroStep::Command(_) => true,
+            MacroStep::Key { key, modifiers } => {
+                modifiers.command || modifiers.ctrl || modifiers.alt || !matches!(
+                    key,
+                    egui::Key::ArrowDown | egui::Key::ArrowLeft | egui::Key::ArrowRight | egui::Key::ArrowUp
+                        | egui::Key::Home | egui::Key::End | egui::Key::PageUp | egui::Key::PageDown
+                        | egui::Key::Backspace | egui::Key::Delete | egui::Key::Enter
+                )
+            }
+            _ => false,
+        }
+    }
+
+    // Bracket auto-closing runs once per frame, so the frame has to end after an opening char
+    fn ends_frame(&self) -> bool {
+        match self {
+            MacroStep::Text(text) => text.ends_with(['(', '[', '{', '"', '\'']),
+            _ => false,
+        }
+    }
+}
+
+impl KeyBinding {
+    pub fn label(&self, ctx: &egui::Context) -> String {
+        ctx.format_shortcut(&egui::KeyboardShortcut::new(self.modifiers, self.key))
+    }
+}
+
+pub fn toggle_recording(app: &mut CodeEditorApp) {
+    match app.macros.recording.take() {
+        Some(steps) => {
+            let mut output = app.terminal_output.lock().unwrap();
+            output.push_str(&format!("⏺ Macro recorded ({} steps)\n> ", steps.len()));
+            app.macros.last = steps;
+        }
+        None => {
+            app.macros.recording = Some(Vec::new());
+        }
+    }
+}
+
+pub fn record_command(app: &mut CodeEditorApp, command: EditorCommand) {
+    if is_playing(app) {
+        return;
+    }
+    if let Some(steps) = app.macros.recording.as_mut() {
+        steps.push(MacroStep::Command(command));
+    }
+}
+
+pub fn is_playing(app: &CodeEditorApp) -> bool {
+    !app.macros.playback.is_empty()
+}
+
+pub fn play(app: &mut CodeEditorApp, steps: &[MacroStep], times: u32) {
+    for _ in 0..times {
+        app.macros.playback.extend(steps.iter().cloned().map(PlaybackStep::Step));
+    }
+}
+
+pub fn play_on_lines(app: &mut CodeEditorApp, steps: &[MacroStep]) {
+    let (a, b) = app.cursor.unwrap_or((0, 0));
+    let (start, end) = editor::selected_lines(&app.text, a, b);
+    for line in start..=end {
+        app.macros.playback.push_back(PlaybackStep::GotoLine(line));
+        app.macros.playback.extend(steps.iter().cloned().map(PlaybackStep::Step));
+    }
+}
+
+pub fn save_last(app: &mut CodeEditorApp) {
+    let name = app.macros.save_name.trim().to_string();
+    if name.is_empty() || app.macros.last.is_empty() {
+        return;
+    }
+    let steps = app.macros.last.clone();
+    match app.config.macros.iter_mut().find(|m| m.name == name) {
+        Some(existing) => existing.steps = steps,
+        None => app.config.macros.push(Macro { name, steps, binding: None }),
+    }
+    app.macros.save_name.clear();
+    save_config(app);
+}
+
+pub fn delete(app: &mut CodeEditorApp, index: usize) {
+    if index < app.config.macros.len() {
+        app.config.macros.remove(index);
+        app.macros.binding_capture = None;
+        save_config(app);
+    }
+}
+
+fn save_config(app: &mut CodeEditorApp) {
+    if let Err(e) = app.config.save() {
+        notifications::notify(app, Level::Error, format!("Could not save settings: {}", e), Vec::new());
+    }
+}
+
+// Runs before the editor widget: binds captured keys and triggers bound macros
+pub fn handle_shortcuts(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if let Some(index) = app.macros.binding_capture {
+        let captured = ctx.input_mut(|i| {
+            let position = i.events.iter().position(|e| matches!(
+                e,
+                egui::Event::Key { pressed: true, key, .. } if *key != egui::Key::Escape
+            ))?;
+            match i.events.remove(position) {
+                egui::Event::Key { key, modifiers, .. } => Some(KeyBinding { key, modifiers }),
+                _ => None,
+            }
+        });
+        if let Some(binding) = captured {
+            if let Some(m) = app.config.macros.get_mut(index) {
+                m.binding = Some(binding);
+            }
+            app.macros.binding_capture = None;
+            save_config(app);
+        } else if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            app.macros.binding_capture = None;
+        }
+        return;
+    }
+
+    let triggered: Vec<Vec<MacroStep>> = app.config.macros.iter()
+        .filter_map(|m| {
+            let binding = m.binding?;
+            ctx.input_mut(|i| i.consume_key(binding.modifiers, binding.key))
+                .then(|| m.steps.clone())
+        })
+        .collect();
+    for steps in triggered {
+        play(app, &steps, 1);
+    }
+}
+
+// Feeds the next chunk of a running playback into this frame's input and
+// records the frame's keystrokes while recording.
+pub fn process_input(app: &mut CodeEditorApp, ctx: &egui::Context, has_focus: bool) -> PlaybackFrame {
+    if is_playing(app) {
+        let (frame, events) = next_chunk(&mut app.macros.playback);
+        ctx.input_mut(|i| i.events.extend(events));
+        return frame;
+    }
+
+    if has_focus {
+        if let Some(steps) = app.macros.recording.as_mut() {
+            ctx.input(|i| steps.extend(i.events.iter().filter_map(MacroStep::from_event)));
+        }
+    }
+    PlaybackFrame::default()
+}
+
+fn next_chunk(queue: &mut VecDeque<PlaybackStep>) -> (PlaybackFrame, Vec<egui::Event>) {
+    let mut frame = PlaybackFrame::default();
+    let mut events = Vec::new();
+
+    while let Some(front) = queue.front() {
+        match front {
+            PlaybackStep::GotoLine(line) => {
+                if frame.goto_line.is_some() || frame.command.is_some() || !events.is_empty() {
+                    break;
+                }
+                frame.goto_line = Some(*line);
+                queue.pop_front();
+            }
+            PlaybackStep::Step(step) => {
+                if step.is_command() && (frame.command.is_some() || !events.is_empty()) {
+                    break;
+                }
+                if let MacroStep::Command(command) = step {
+                    frame.command = Some(*command);
+                }
+                let ends_frame = step.ends_frame();
+                events.extend(step.to_events());
+                queue.pop_front();
+                if ends_frame {
+                    break;
+                }
+            }
+        }
+    }
+    (frame, events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::UserConfig;
+
+    fn key(key: egui::Key, modifiers: egui::Modifiers) -> MacroStep {
+        MacroStep::Key { key, modifiers }
+    }
+
+    // The pressed events and command of each playback frame, until the queue is empty
+    fn frames(queue: &mut VecDeque<PlaybackStep>) -> Vec<(Option<usize>, Vec<MacroStep>, Option<EditorCommand>)> {
+        let mut frames = Vec::new();
+        while !queue.is_empty() {
+            let (frame, events) = next_chunk(queue);
+            let steps = events.iter().filter_map(MacroStep::from_event).collect();
+            frames.push((frame.goto_line, steps, frame.command));
+        }
+        frames
+    }
+
+    #[test]
+    fn only_shortcut_keys_are_commands() {
+        assert!(!key(egui::Key::ArrowLeft, egui::Modifiers::NONE).is_command());
+        assert!(!key(egui::Key::ArrowLeft, egui::Modifiers::SHIFT).is_command());
+        assert!(!key(egui::Key::Enter, egui::Modifiers::NONE).is_command());
+        assert!(key(egui::Key::ArrowLeft, egui::Modifiers::CTRL).is_command());
+        assert!(key(egui::Key::Tab, egui::Modifiers::NONE).is_command());
+        assert!(MacroStep::Command(EditorCommand::ToggleLineComment).is_command());
+        assert!(!MacroStep::Text("a".to_string()).is_command());
+    }
+
+    #[test]
+    fn recorded_steps_replay_in_order_split_across_frames() {
+        let ctx = egui::Context::default();
+        let mut app = CodeEditorApp::default();
+        toggle_recording(&mut app);
+        let events = vec![
+            egui::Event::Text("ab".to_string()),
+            egui::Event::Key { key: egui::Key::ArrowLeft, pressed: true, repeat: false, modifiers: egui::Modifiers::NONE },
+            egui::Event::Key { key: egui::Key::ArrowLeft, pressed: false, repeat: false, modifiers: egui::Modifiers::NONE },
+            egui::Event::Key { key: egui::Key::D, pressed: true, repeat: false, modifiers: egui::Modifiers::COMMAND },
+            egui::Event::Text("f(".to_string()),
+            egui::Event::Text("x".to_string()),
+        ];
+        ctx.input_mut(|i| i.events = events);
+        process_input(&mut app, &ctx, true);
+        record_command(&mut app, EditorCommand::ToggleLineComment);
+        toggle_recording(&mut app);
+
+        let text = |t: &str| MacroStep::Text(t.to_string());
+        let recorded = vec![
+            text("ab"),
+            key(egui::Key::ArrowLeft, egui::Modifiers::NONE),
+            key(egui::Key::D, egui::Modifiers::COMMAND),
+            text("f("),
+            text("x"),
+            MacroStep::Command(EditorCommand::ToggleLineComment),
+        ];
+        assert_eq!(app.macros.last, recorded);
+
+        play(&mut app, &recorded, 1);
+        assert_eq!(frames(&mut app.macros.playback), vec![
+            // A shortcut starts a frame of its own
+            (None, vec![text("ab"), key(egui::Key::ArrowLeft, egui::Modifiers::NONE)], None),
+            // An opening bracket ends the frame so it is auto-closed before the next text
+            (None, vec![key(egui::Key::D, egui::Modifiers::COMMAND), text("f(")], None),
+            (None, vec![text("x")], None),
+            (None, Vec::new(), Some(EditorCommand::ToggleLineComment)),
+        ]);
+    }
+
+    #[test]
+    fn playback_on_lines_moves_to_each_line_first() {
+        let mut app = CodeEditorApp { text: "one\ntwo\n".to_string(), cursor: Some((0, 5)), ..Default::default() };
+        let steps = vec![MacroStep::Text("// ".to_string())];
+        play_on_lines(&mut app, &steps);
+        assert_eq!(frames(&mut app.macros.playback), vec![
+            (Some(0), steps.clone(), None),
+            (Some(1), steps.clone(), None),
+        ]);
+    }
+
+    #[test]
+    fn saved_macros_are_written_to_the_settings() {
+        let mut app = CodeEditorApp::default();
+        app.macros.last = vec![MacroStep::Text("hi".to_string())];
+        app.macros.save_name = " greet ".to_string();
+        save_last(&mut app);
+        let saved = UserConfig::load().macros;
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].name, "greet");
+        assert_eq!(saved[0].steps, app.macros.last);
+        assert!(app.macros.save_name.is_empty());
+
+        delete(&mut app, 0);
+        assert!(UserConfig::load().macros.is_empty());
+    }
+}
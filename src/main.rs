@@ -4,11 +4,53 @@ mod editor;
 mod terminal;
 mod file_tree;
 mod file_ops;
+mod config;
+mod macros;
+mod language;
+mod snippets;
+mod comments;
+mod completion;
+mod lsp;
+mod outline;
+mod panes;
+mod diff;
+mod watcher;
+mod excludes;
+mod recovery;
+mod session;
+mod recent;
+mod cli;
+mod instance;
+mod autosave;
+mod notifications;
+mod hex;
+mod search;
 
 use eframe::egui;
 use app::CodeEditorApp;
 
 fn main() -> Result<(), eframe::Error> {
+    let mut args = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli::Parsed::Run(args)) => args,
+        Ok(cli::Parsed::Exit(text)) => {
+            println!("{}", text);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("codonn: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = cli::read_stdin(&mut args) {
+        eprintln!("codonn: {}", e);
+        std::process::exit(1);
+    }
+
+    let listener = match instance::start(&args) {
+        instance::Startup::Forwarded => return Ok(()),
+        instance::Startup::Primary(listener) => listener,
+    };
+
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(1600.0, 1000.0)),
         ..Default::default()
@@ -17,7 +59,24 @@ fn main() -> Result<(), eframe::Error> {
     match eframe::run_native(
         "🧬Codonn",
         options,
-        Box::new(|_cc| Box::new(CodeEditorApp::default())),
+        Box::new(move |cc| {
+            let mut app = CodeEditorApp::default();
+            if args.restores_session() {
+                session::restore(&mut app, &cc.egui_ctx);
+            } else {
+                session::start_detached(&mut app, &cc.egui_ctx);
+            }
+            if args.restores_buffers() {
+                recovery::restore(&mut app);
+            }
+            let wait = args.wait;
+            let opened = cli::apply(&mut app, &cc.egui_ctx, args);
+            if wait {
+                app.waiting = opened;
+            }
+            instance::serve(&mut app, &cc.egui_ctx, listener);
+            Box::new(app)
+        }),
     ) {
         Ok(_) => Ok(()),
         Err(e) => {
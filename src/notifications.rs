@@ -0,0 +1,324 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use eframe::egui;
+use crate::app::{BottomPanel, CodeEditorApp};
+use crate::file_ops;
+use crate::terminal;
+
+const HISTORY_LIMIT: usize = 200;
+const TOAST_LIMIT: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Level::Info => "ℹ",
+            Level::Warning => "⚠",
+            Level::Error => "❌",
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            Level::Info => egui::Color32::from_rgb(139, 233, 253),
+            Level::Warning => egui::Color32::from_rgb(241, 250, 140),
+            Level::Error => egui::Color32::from_rgb(255, 85, 85),
+        }
+    }
+
+    // How long a toast stays up while the pointer is not on it
+    fn lifetime(&self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(4),
+            Level::Warning => Duration::from_secs(8),
+            Level::Error => Duration::from_secs(15),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    ShowOutput,
+    RetrySave(usize, PathBuf),
+    RetryOpen(PathBuf),
+    RetryRun(PathBuf),
+    RetryBuild(PathBuf),
+    RetryCommand(String),
+    UndoDelete,
+}
+
+impl Action {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::ShowOutput => "Show Output",
+            Action::UndoDelete => "Undo",
+            _ => "Retry",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub id: u64,
+    pub level: Level,
+    pub message: String,
+    pub actions: Vec<Action>,
+    pub time: DateTime<Local>,
+}
+
+// Lets background threads post notifications; they show up on the next frame
+#[derive(Clone, Default)]
+pub struct Notifier {
+    inbox: Arc<Mutex<Vec<Notification>>>,
+    ctx: Option<egui::Context>,
+}
+
+impl Notifier {
+    pub fn send(&self, level: Level, message: String, actions: Vec<Action>) {
+        let notification = Notification { id: 0, level, message, actions, time: Local::now() };
+        self.inbox.lock().unwrap().push(notification);
+        if let Some(ctx) = &self.ctx {
+            ctx.request_repaint();
+        }
+    }
+}
+
+pub struct Toast {
+    pub id: u64,
+    pub shown: Instant,
+}
+
+#[derive(Default)]
+pub struct NotificationState {
+    notifier: Notifier,
+    // Oldest first
+    pub history: Vec<Notification>,
+    pub toasts: Vec<Toast>,
+    // Notifications posted since the history panel was last looked at
+    pub unread: usize,
+    next_id: u64,
+}
+
+pub fn notifier(app: &CodeEditorApp) -> Notifier {
+    app.notifications.notifier.clone()
+}
+
+pub fn notify(app: &mut CodeEditorApp, level: Level, message: String, actions: Vec<Action>) {
+    push(app, Notification { id: 0, level, message, actions, time: Local::now() });
+}
+
+fn push(app: &mut CodeEditorApp, mut notification: Notification) {
+    let state = &mut app.notifications;
+    state.next_id += 1;
+    notification.id = state.next_id;
+    state.toasts.push(Toast { id: notification.id, shown: Instant::now() });
+    if state.toasts.len() > TOAST_LIMIT {
+        state.toasts.remove(0);
+    }
+    state.history.push(notification);
+    if state.history.len() > HISTORY_LIMIT {
+        state.history.remove(0);
+    }
+    if !history_visible(app) {
+        app.notifications.unread += 1;
+    }
+}
+
+pub fn history_visible(app: &CodeEditorApp) -> bool {
+    app.show_terminal && app.bottom_panel == BottomPanel::Notifications
+}
+
+pub fn get(app: &CodeEditorApp, id: u64) -> Option<&Notification> {
+    app.notifications.history.iter().rev().find(|n| n.id == id)
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if app.notifications.notifier.ctx.is_none() {
+        app.notifications.notifier.ctx = Some(ctx.clone());
+    }
+    let received = std::mem::take(&mut *app.notifications.notifier.inbox.lock().unwrap());
+    for notification in received {
+        push(app, notification);
+    }
+    if history_visible(app) {
+        app.notifications.unread = 0;
+    }
+
+    let mut next_expiry: Option<Duration> = None;
+    let history = &app.notifications.history;
+    app.notifications.toasts.retain(|toast| {
+        let Some(notification) = history.iter().rev().find(|n| n.id == toast.id) else { return false };
+        let lifetime = notification.level.lifetime();
+        let elapsed = toast.shown.elapsed();
+        if elapsed >= lifetime {
+            return false;
+        }
+        let remaining = lifetime - elapsed;
+        next_expiry = Some(next_expiry.map_or(remaining, |next| next.min(remaining)));
+        true
+    });
+    if let Some(remaining) = next_expiry {
+        ctx.request_repaint_after(remaining);
+    }
+}
+
+// Keeps a toast up while it is being read
+pub fn hold(app: &mut CodeEditorApp, id: u64) {
+    for toast in app.notifications.toasts.iter_mut().filter(|t| t.id == id) {
+        toast.shown = Instant::now();
+    }
+}
+
+pub fn dismiss(app: &mut CodeEditorApp, id: u64) {
+    app.notifications.toasts.retain(|t| t.id != id);
+}
+
+pub fn clear(app: &mut CodeEditorApp) {
+    app.notifications.history.clear();
+    app.notifications.toasts.clear();
+    app.notifications.unread = 0;
+}
+
+pub fn show_history(app: &mut CodeEditorApp) {
+    app.show_terminal = true;
+    app.bottom_panel = BottomPanel::Notifications;
+    app.notifications.unread = 0;
+}
+
+pub fn perform(app: &mut CodeEditorApp, id: u64, action: Action) {
+    dismiss(app, id);
+    match action {
+        Action::ShowOutput => {
+            app.show_terminal = true;
+            app.bottom_panel = BottomPanel::Terminal;
+        }
+        Action::RetrySave(doc, path) => file_ops::save_to(app, doc, path),
+        Action::RetryOpen(path) => file_ops::open_path(app, path),
+        Action::RetryRun(path) => terminal::run_file(app, &path),
+        Action::RetryBuild(path) => terminal::build_file(app, &path),
+        Action::RetryCommand(command) => terminal::execute_command(app, &command),
+        Action::UndoDelete => file_ops::undo_delete(app),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::panes;
+
+    fn info(app: &mut CodeEditorApp, message: &str) {
+        notify(app, Level::Info, message.to_string(), Vec::new());
+    }
+
+    fn last_message(app: &CodeEditorApp) -> (Level, String) {
+        let last = app.notifications.history.last().unwrap();
+        (last.level, last.message.clone())
+    }
+
+    #[test]
+    fn history_and_toasts_keep_the_newest() {
+        let mut app = CodeEditorApp::default();
+        for i in 0..HISTORY_LIMIT + 5 {
+            info(&mut app, &i.to_string());
+        }
+        let history = &app.notifications.history;
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history[0].message, "5");
+        assert_eq!(app.notifications.toasts.len(), TOAST_LIMIT);
+        assert_eq!(app.notifications.toasts[0].id, history[HISTORY_LIMIT - TOAST_LIMIT].id);
+    }
+
+    #[test]
+    fn unread_counts_until_the_history_is_shown() {
+        let mut app = CodeEditorApp::default();
+        info(&mut app, "one");
+        info(&mut app, "two");
+        assert_eq!(app.notifications.unread, 2);
+        show_history(&mut app);
+        assert_eq!(app.notifications.unread, 0);
+        info(&mut app, "seen");
+        assert_eq!(app.notifications.unread, 0);
+
+        app.bottom_panel = BottomPanel::Terminal;
+        info(&mut app, "three");
+        assert_eq!(app.notifications.unread, 1);
+        clear(&mut app);
+        assert_eq!(app.notifications.unread, 0);
+        assert!(app.notifications.history.is_empty() && app.notifications.toasts.is_empty());
+    }
+
+    #[test]
+    fn toasts_expire_after_their_lifetime() {
+        let ctx = egui::Context::default();
+        let mut app = CodeEditorApp::default();
+        info(&mut app, "short");
+        notify(&mut app, Level::Error, "long".to_string(), Vec::new());
+        let five_seconds_ago = Instant::now().checked_sub(Duration::from_secs(5)).unwrap();
+        for toast in &mut app.notifications.toasts {
+            toast.shown = five_seconds_ago;
+        }
+        sync(&mut app, &ctx);
+        let error = app.notifications.history[1].id;
+        assert_eq!(app.notifications.toasts.iter().map(|t| t.id).collect::<Vec<_>>(), vec![error]);
+
+        // Holding restarts the lifetime; the history keeps expired toasts
+        hold(&mut app, error);
+        assert!(app.notifications.toasts[0].shown > five_seconds_ago);
+        assert_eq!(app.notifications.history.len(), 2);
+    }
+
+    #[test]
+    fn background_notifications_arrive_on_the_next_sync() {
+        let mut app = CodeEditorApp::default();
+        let sender = notifier(&app);
+        std::thread::spawn(move || sender.send(Level::Warning, "from a thread".to_string(), Vec::new())).join().unwrap();
+        assert!(app.notifications.history.is_empty());
+        sync(&mut app, &egui::Context::default());
+        assert_eq!(last_message(&app), (Level::Warning, "from a thread".to_string()));
+    }
+
+    #[test]
+    fn actions_retry_what_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = CodeEditorApp::default();
+        let path = dir.path().join("a.txt");
+        notify(&mut app, Level::Error, "open failed".to_string(), vec![Action::RetryOpen(path.clone())]);
+        let id = app.notifications.history[0].id;
+        fs::write(&path, "contents").unwrap();
+        perform(&mut app, id, Action::RetryOpen(path.clone()));
+        assert!(app.notifications.toasts.is_empty());
+        let doc = app.panes.find(&path).unwrap();
+
+        app.text = "edited".to_string();
+        panes::mark_edited(&mut app, doc);
+        let copy = dir.path().join("copy.txt");
+        perform(&mut app, id, Action::RetrySave(doc, copy.clone()));
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "edited");
+
+        perform(&mut app, id, Action::ShowOutput);
+        assert_eq!(app.bottom_panel, BottomPanel::Terminal);
+        assert!(app.show_terminal);
+    }
+
+    #[test]
+    fn undo_delete_asks_the_trash_for_the_last_deleted_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = CodeEditorApp::default();
+        // Never moved to the trash, so restoring it fails and says so
+        app.file_tree.trashed = Some(dir.path().join("gone.txt"));
+        perform(&mut app, 0, Action::UndoDelete);
+        assert!(app.file_tree.trashed.is_none());
+        let (level, message) = last_message(&app);
+        assert_eq!(level, Level::Error);
+        assert!(message.starts_with("Could not restore"), "{}", message);
+    }
+}
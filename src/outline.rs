@@ -0,0 +1,580 @@
+use crate::app::CodeEditorApp;
+use crate::language::Language;
+use crate::panes;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    Impl,
+    Module,
+    Class,
+    Heading,
+}
+
+impl SymbolKind {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            SymbolKind::Function | SymbolKind::Method => "ƒ",
+            SymbolKind::Struct => "🧱",
+            SymbolKind::Enum => "🔢",
+            SymbolKind::Trait => "🔌",
+            SymbolKind::Impl => "⚙",
+            SymbolKind::Module => "📦",
+            SymbolKind::Class => "🏛",
+            SymbolKind::Heading => "#",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    // Char index of the name, used when jumping
+    pub index: usize,
+    // Char range of the whole declaration, used for nesting and cursor tracking
+    pub start: usize,
+    pub end: usize,
+    pub children: Vec<Symbol>,
+}
+
+#[derive(Default)]
+pub struct OutlineState {
+    pub filter: String,
+    cache: Option<(u64, Language, Vec<Symbol>)>,
+}
+
+// Symbols of the active file, reparsed only when the text changes
+pub fn symbols(app: &mut CodeEditorApp) -> Vec<Symbol> {
+    let language = app.file_path.as_deref().map(Language::from_path).unwrap_or(Language::Unknown);
+    let hash = match app.panes.active_doc {
+        Some(doc) => panes::hash_contents(app, doc),
+        None => panes::hash_text(&app.text),
+    };
+
+    match &app.outline.cache {
+        Some((h, l, symbols)) if *h == hash && *l == language => symbols.clone(),
+        _ => {
+            let symbols = parse(language, &app.text);
+            app.outline.cache = Some((hash, language, symbols.clone()));
+            symbols
+        }
+    }
+}
+
+pub fn parse(language: Language, text: &str) -> Vec<Symbol> {
+    let mut flat = match language {
+        Language::Rust => parse_rust(text),
+        Language::Python => parse_python(text),
+        Language::JavaScript | Language::TypeScript => parse_js(text),
+        Language::Markdown => parse_markdown(text),
+        _ => Vec::new(),
+    };
+    flat.sort_by_key(|s| (s.start, std::cmp::Reverse(s.end)));
+    nest(flat)
+}
+
+// Deepest symbol whose range contains the cursor
+pub fn symbol_at(symbols: &[Symbol], index: usize) -> Option<&Symbol> {
+    let symbol = symbols.iter().find(|s| s.start <= index && index <= s.end)?;
+    symbol_at(&symbol.children, index).or(Some(symbol))
+}
+
+// Builds the tree from symbols sorted by start; a symbol is a child of the last one enclosing it
+fn nest(flat: Vec<Symbol>) -> Vec<Symbol> {
+    let mut roots: Vec<Symbol> = Vec::new();
+    let mut stack: Vec<Symbol> = Vec::new();
+    for symbol in flat {
+        while stack.last().is_some_and(|top| symbol.start >= top.end) {
+            pop_into(&mut stack, &mut roots);
+        }
+        stack.push(symbol);
+    }
+    while !stack.is_empty() {
+        pop_into(&mut stack, &mut roots);
+    }
+    roots
+}
+
+fn pop_into(stack: &mut Vec<Symbol>, roots: &mut Vec<Symbol>) {
+    let Some(symbol) = stack.pop() else { return };
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(symbol),
+        None => roots.push(symbol),
+    }
+}
+
+struct Line<'a> {
+    start: usize,
+    text: &'a str,
+}
+
+fn lines(text: &str) -> Vec<Line<'_>> {
+    let mut start = 0;
+    text.split('\n')
+        .map(|line| {
+            let entry = Line { start, text: line };
+            start += line.chars().count() + 1;
+            entry
+        })
+        .collect()
+}
+
+fn identifier(text: &str) -> Option<&str> {
+    let end = text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(text.len());
+    (end > 0).then(|| &text[..end])
+}
+
+fn char_offset(line: &str, byte: usize) -> usize {
+    line[..byte].chars().count()
+}
+
+// Braces, brackets and semicolons outside of strings and comments, with their char index
+fn structure_tokens(text: &str, language: Language) -> Vec<(usize, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match c {
+            '/' if next == Some('/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if next == Some('*') => {
+                i += 2;
+                while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '"' | '`' => {
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            '\'' => {
+                // In Rust a quote is either a char literal or a lifetime
+                let is_char = language != Language::Rust
+                    || chars.get(i + 2) == Some(&'\'')
+                    || (next == Some('\\'));
+                if is_char {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '\'' && chars[i] != '\n' {
+                        if chars[i] == '\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                }
+            }
+            '{' | '}' | '(' | ')' | '[' | ']' | ';' => tokens.push((i, c)),
+            _ => {}
+        }
+        i += 1;
+    }
+    tokens
+}
+
+// End of a declaration starting at `start`: the matching '}' of its body, or the ';' ending it
+fn declaration_end(tokens: &[(usize, char)], start: usize, fallback: usize) -> usize {
+    let mut depth = 0;
+    let mut parens = 0;
+    for &(index, c) in tokens.iter().skip_while(|(i, _)| *i < start) {
+        match c {
+            '(' | '[' => parens += 1,
+            ')' | ']' => parens -= 1,
+            ';' if depth == 0 && parens == 0 => return index + 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index + 1;
+                }
+                if depth < 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    fallback
+}
+
+fn parse_rust(text: &str) -> Vec<Symbol> {
+    let tokens = structure_tokens(text, Language::Rust);
+    let total = text.chars().count();
+    let mut symbols = Vec::new();
+
+    for line in lines(text) {
+        let trimmed = line.text.trim_start();
+        let indent = line.text.len() - trimmed.len();
+        let mut rest = trimmed;
+        let mut offset = indent;
+        // Strip visibility and qualifiers in front of the keyword
+        loop {
+            let before = rest.len();
+            for prefix in ["pub(crate) ", "pub(super) ", "pub ", "async ", "unsafe ", "const ", "default ", "extern \"C\" "] {
+                if let Some(stripped) = rest.strip_prefix(prefix) {
+                    offset += prefix.len();
+                    rest = stripped;
+                }
+            }
+            if rest.len() == before {
+                break;
+            }
+        }
+
+        let keywords = [
+            ("fn ", SymbolKind::Function),
+            ("struct ", SymbolKind::Struct),
+            ("enum ", SymbolKind::Enum),
+            ("trait ", SymbolKind::Trait),
+            ("mod ", SymbolKind::Module),
+            ("impl", SymbolKind::Impl),
+            ("macro_rules! ", SymbolKind::Function),
+        ];
+        let Some((keyword, kind)) = keywords.iter().find(|(k, _)| rest.starts_with(k)) else { continue };
+        let after = &rest[keyword.len()..];
+        let start = line.start + char_offset(line.text, indent);
+
+        let (name, name_offset) = if *kind == SymbolKind::Impl {
+            // `impl<T> Trait for Type` is shown as written, up to the body
+            if !(after.starts_with(' ') || after.starts_with('<')) {
+                continue;
+            }
+            let header = skip_generics(after).split('{').next().unwrap_or_default();
+            let header = header.split(" where").next().unwrap_or_default().trim();
+            (format!("impl {}", header), offset)
+        } else {
+            let Some(name) = identifier(after.trim_start()) else { continue };
+            (name.to_string(), offset + keyword.len() + (after.len() - after.trim_start().len()))
+        };
+
+        symbols.push(Symbol {
+            name,
+            kind: *kind,
+            index: line.start + char_offset(line.text, name_offset),
+            start,
+            end: declaration_end(&tokens, start, total),
+            children: Vec::new(),
+        });
+    }
+
+    // Functions inside impls and traits are methods
+    let containers: Vec<(usize, usize)> = symbols.iter()
+        .filter(|s| matches!(s.kind, SymbolKind::Impl | SymbolKind::Trait))
+        .map(|s| (s.start, s.end))
+        .collect();
+    for symbol in symbols.iter_mut().filter(|s| s.kind == SymbolKind::Function) {
+        if containers.iter().any(|&(start, end)| start < symbol.start && symbol.start < end) {
+            symbol.kind = SymbolKind::Method;
+        }
+    }
+    symbols
+}
+
+// Drops a leading `<...>` parameter list
+fn skip_generics(text: &str) -> &str {
+    if !text.starts_with('<') {
+        return text;
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &text[i + 1..];
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+fn parse_python(text: &str) -> Vec<Symbol> {
+    let lines = lines(text);
+    let total = text.chars().count();
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+    let mut symbols = Vec::new();
+
+    for (n, line) in lines.iter().enumerate() {
+        let trimmed = line.text.trim_start();
+        let indent = indent_of(line.text);
+        let (keyword, kind) = if trimmed.starts_with("class ") {
+            ("class ", SymbolKind::Class)
+        } else if trimmed.starts_with("def ") {
+            ("def ", SymbolKind::Function)
+        } else if trimmed.starts_with("async def ") {
+            ("async def ", SymbolKind::Function)
+        } else {
+            continue;
+        };
+        let Some(name) = identifier(&trimmed[keyword.len()..]) else { continue };
+
+        // The body runs until the next non-blank line at the same or lower indentation
+        let end = lines[n + 1..].iter()
+            .find(|l| !l.text.trim().is_empty() && !l.text.trim_start().starts_with('#') && indent_of(l.text) <= indent)
+            .map_or(total, |l| l.start - 1);
+        symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            index: line.start + char_offset(line.text, indent + keyword.len()),
+            start: line.start + char_offset(line.text, indent),
+            end,
+            children: Vec::new(),
+        });
+    }
+
+    let classes: Vec<(usize, usize)> = symbols.iter()
+        .filter(|s| s.kind == SymbolKind::Class)
+        .map(|s| (s.start, s.end))
+        .collect();
+    for symbol in symbols.iter_mut().filter(|s| s.kind == SymbolKind::Function) {
+        if classes.iter().any(|&(start, end)| start < symbol.start && symbol.start < end) {
+            symbol.kind = SymbolKind::Method;
+        }
+    }
+    symbols
+}
+
+fn parse_js(text: &str) -> Vec<Symbol> {
+    let tokens = structure_tokens(text, Language::JavaScript);
+    let total = text.chars().count();
+    let mut symbols = Vec::new();
+    let mut classes: Vec<(usize, usize)> = Vec::new();
+
+    for line in lines(text) {
+        let trimmed = line.text.trim_start();
+        let indent = line.text.len() - trimmed.len();
+        let mut rest = trimmed;
+        let mut offset = indent;
+        for prefix in ["export ", "default ", "async ", "static ", "public ", "private ", "protected "] {
+            if let Some(stripped) = rest.strip_prefix(prefix) {
+                offset += prefix.len();
+                rest = stripped;
+            }
+        }
+        let start = line.start + char_offset(line.text, indent);
+        let in_class = classes.iter().any(|&(s, e)| s < start && start < e);
+
+        let found = if let Some(after) = rest.strip_prefix("function") {
+            let after = after.trim_start_matches('*');
+            identifier(after.trim_start()).map(|name| (name, SymbolKind::Function, offset + rest.len() - after.trim_start().len()))
+        } else if let Some(after) = rest.strip_prefix("class ") {
+            identifier(after).map(|name| (name, SymbolKind::Class, offset + "class ".len()))
+        } else if let Some(after) = ["const ", "let ", "var "].iter().find_map(|k| rest.strip_prefix(k)) {
+            // Arrow functions and function expressions bound to a name
+            let name = identifier(after);
+            let value = after.split_once('=').map(|(_, v)| v.trim_start()).unwrap_or_default();
+            let is_function = value.starts_with("function")
+                || value.starts_with("async")
+                || (value.contains("=>") && (value.starts_with('(') || identifier(value).is_some()));
+            name.filter(|_| is_function).map(|name| (name, SymbolKind::Function, offset + rest.len() - after.len()))
+        } else if in_class {
+            // Method definitions: `name(args) {`
+            identifier(rest)
+                .filter(|name| !matches!(*name, "if" | "for" | "while" | "switch" | "catch" | "return" | "constructor" | "super"))
+                .filter(|name| rest[name.len()..].trim_start().starts_with('(') && rest.trim_end().ends_with('{'))
+                .map(|name| (name, SymbolKind::Method, offset))
+                .or_else(|| rest.starts_with("constructor(").then_some(("constructor", SymbolKind::Method, offset)))
+        } else {
+            None
+        };
+        let Some((name, kind, name_offset)) = found else { continue };
+
+        let end = declaration_end(&tokens, start, total);
+        if kind == SymbolKind::Class {
+            classes.push((start, end));
+        }
+        symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            index: line.start + char_offset(line.text, name_offset),
+            start,
+            end,
+            children: Vec::new(),
+        });
+    }
+    symbols
+}
+
+fn parse_markdown(text: &str) -> Vec<Symbol> {
+    let lines = lines(text);
+    let total = text.chars().count();
+    let mut headings: Vec<(usize, usize, usize, String)> = Vec::new();
+    let mut in_fence = false;
+
+    for line in &lines {
+        let trimmed = line.text.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let title = trimmed[level..].trim();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') && !title.is_empty() {
+            let indent = line.text.len() - trimmed.len();
+            headings.push((level, line.start + char_offset(line.text, indent), line.start + char_offset(line.text, indent + level + 1), title.trim_end_matches('#').trim().to_string()));
+        }
+    }
+
+    // A section runs until the next heading of the same or a higher level
+    headings.iter().enumerate().map(|(n, (level, start, index, title))| {
+        let end = headings[n + 1..].iter()
+            .find(|(l, ..)| l <= level)
+            .map_or(total, |(_, s, ..)| s - 1);
+        Symbol {
+            name: title.clone(),
+            kind: SymbolKind::Heading,
+            index: *index,
+            start: *start,
+            end,
+            children: Vec::new(),
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (depth, name, kind) in tree order
+    fn tree(language: Language, text: &str) -> Vec<(usize, String, SymbolKind)> {
+        fn walk(symbols: &[Symbol], depth: usize, out: &mut Vec<(usize, String, SymbolKind)>) {
+            for symbol in symbols {
+                out.push((depth, symbol.name.clone(), symbol.kind));
+                walk(&symbol.children, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        walk(&parse(language, text), 0, &mut out);
+        out
+    }
+
+    fn entry(depth: usize, name: &str, kind: SymbolKind) -> (usize, String, SymbolKind) {
+        (depth, name.to_string(), kind)
+    }
+
+    #[test]
+    fn rust_items_and_methods() {
+        let text = "\
+pub struct Point { x: i32 }
+
+impl<T: Clone> Display for Wrapper<T> where T: Debug {
+    pub fn fmt(&self) {
+        let s = \"fn fake() {\";
+    }
+}
+
+mod inner {
+    pub(crate) async fn helper() {}
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+}
+";
+        assert_eq!(tree(Language::Rust, text), vec![
+            entry(0, "Point", SymbolKind::Struct),
+            entry(0, "impl Display for Wrapper<T>", SymbolKind::Impl),
+            entry(1, "fmt", SymbolKind::Method),
+            entry(0, "inner", SymbolKind::Module),
+            entry(1, "helper", SymbolKind::Function),
+            entry(0, "Shape", SymbolKind::Trait),
+            entry(1, "area", SymbolKind::Method),
+        ]);
+    }
+
+    #[test]
+    fn rust_symbol_positions() {
+        let text = "fn a() {}\n  pub fn b() {\n  }\n";
+        let symbols = parse(Language::Rust, text);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!((symbols[0].index, symbols[0].start, symbols[0].end), (3, 0, 9));
+        assert_eq!(symbols[1].index, 19);
+        assert_eq!(symbols[1].start, 12);
+        assert!(symbol_at(&symbols, 25).is_some_and(|s| s.name == "b"));
+    }
+
+    #[test]
+    fn python_classes_by_indentation() {
+        let text = "\
+class Foo:
+    def bar(self):
+        pass
+
+    # comment
+    async def baz(self):
+        pass
+
+def top():
+    pass
+";
+        assert_eq!(tree(Language::Python, text), vec![
+            entry(0, "Foo", SymbolKind::Class),
+            entry(1, "bar", SymbolKind::Method),
+            entry(1, "baz", SymbolKind::Method),
+            entry(0, "top", SymbolKind::Function),
+        ]);
+    }
+
+    #[test]
+    fn javascript_functions_and_classes() {
+        let text = "\
+export default class Widget {
+    constructor(props) {
+        super(props);
+    }
+    render() {
+        if (x) {
+        }
+    }
+}
+const handler = (event) => {
+};
+let value = 42;
+async function* load() {}
+";
+        assert_eq!(tree(Language::JavaScript, text), vec![
+            entry(0, "Widget", SymbolKind::Class),
+            entry(1, "constructor", SymbolKind::Method),
+            entry(1, "render", SymbolKind::Method),
+            entry(0, "handler", SymbolKind::Function),
+            entry(0, "load", SymbolKind::Function),
+        ]);
+    }
+
+    #[test]
+    fn markdown_sections_nest_by_level() {
+        let text = "# Title\n## Install ##\n```\n# not a heading\n```\n### Linux\n## Usage\n#nospace\n# Other\n";
+        assert_eq!(tree(Language::Markdown, text), vec![
+            entry(0, "Title", SymbolKind::Heading),
+            entry(1, "Install", SymbolKind::Heading),
+            entry(2, "Linux", SymbolKind::Heading),
+            entry(1, "Usage", SymbolKind::Heading),
+            entry(0, "Other", SymbolKind::Heading),
+        ]);
+    }
+
+    #[test]
+    fn unknown_languages_have_no_symbols() {
+        assert!(parse(Language::Unknown, "fn main() {}").is_empty());
+    }
+}
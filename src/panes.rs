@@ -0,0 +1,672 @@
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+use eframe::egui;
+use crate::app::CodeEditorApp;
+
+// An open file; while it is the active document its text lives in `app.text`
+pub struct Document {
+    pub path: Option<PathBuf>,
+    pub text: String,
+    // Contents of a binary file, edited in the hex view; `text` stays empty
+    pub bytes: Option<Vec<u8>>,
+    // Hash of the text as last loaded or saved; None for a buffer never written to disk
+    pub saved_hash: Option<u64>,
+    // Modification time of the file when it was last loaded or saved
+    pub disk_modified: Option<SystemTime>,
+    // The file was deleted or renamed away while open
+    pub missing: bool,
+    // Decoded from bytes that were not valid UTF-8, so saving over the file would corrupt it
+    pub lossy: bool,
+    // Number shown as "Untitled-N" until the buffer gets a path
+    untitled: usize,
+    // Bumped by `mark_edited`; the hash of the contents is cached per revision and length
+    revision: u64,
+    hashed: Cell<Option<(u64, usize, u64)>>,
+}
+
+pub struct EditorGroup {
+    pub id: usize,
+    pub tabs: Vec<usize>,
+    pub active: usize,
+}
+
+impl Document {
+    pub fn name(&self) -> String {
+        self.path.as_ref()
+            .and_then(|p| p.file_name())
+            .map_or_else(|| format!("Untitled-{}", self.untitled), |n| n.to_string_lossy().to_string())
+    }
+}
+
+impl EditorGroup {
+    pub fn current(&self) -> Option<usize> {
+        self.tabs.get(self.active).copied()
+    }
+}
+
+// What a close waiting on the user refers to, by group id and document so it survives tab moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseTarget {
+    Tab(usize, usize),
+    Group(usize),
+    Document(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitDirection {
+    // Groups side by side
+    Horizontal,
+    // Groups stacked on top of each other
+    Vertical,
+}
+
+pub struct PaneState {
+    pub documents: BTreeMap<usize, Document>,
+    pub groups: Vec<EditorGroup>,
+    pub active_group: usize,
+    pub direction: SplitDirection,
+    pub active_doc: Option<usize>,
+    next_untitled: usize,
+    // (group, tab) being dragged in a tab strip
+    pub dragged_tab: Option<(usize, usize)>,
+    // A close asking what to do with these unsaved documents, the first one being asked about
+    pub closing: Option<(CloseTarget, Vec<usize>)>,
+    next_id: usize,
+}
+
+// Starts with one empty group; the welcome page shows until a document is opened
+impl Default for PaneState {
+    fn default() -> Self {
+        Self {
+            documents: BTreeMap::new(),
+            groups: vec![EditorGroup { id: 0, tabs: Vec::new(), active: 0 }],
+            active_group: 0,
+            direction: SplitDirection::Horizontal,
+            active_doc: None,
+            next_untitled: 0,
+            dragged_tab: None,
+            closing: None,
+            next_id: 0,
+        }
+    }
+}
+
+impl PaneState {
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.documents.iter().find(|(_, d)| d.path.as_deref() == Some(path)).map(|(id, _)| *id)
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.documents.values().filter_map(|d| d.path.clone()).collect()
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+// Text of a document, reading through to `app.text` for the active one
+pub fn text_of(app: &CodeEditorApp, doc: usize) -> &str {
+    if app.panes.active_doc == Some(doc) {
+        &app.text
+    } else {
+        app.panes.documents.get(&doc).map_or("", |d| d.text.as_str())
+    }
+}
+
+pub fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+pub fn is_binary(app: &CodeEditorApp, doc: usize) -> bool {
+    app.panes.documents.get(&doc).is_some_and(|d| d.bytes.is_some())
+}
+
+// What a save writes: the bytes of a binary document, the text of any other
+pub fn contents_of(app: &CodeEditorApp, doc: usize) -> &[u8] {
+    match app.panes.documents.get(&doc).and_then(|d| d.bytes.as_ref()) {
+        Some(bytes) => bytes,
+        None => text_of(app, doc).as_bytes(),
+    }
+}
+
+// Cached, so checking every tab for unsaved changes each frame stays cheap; the length
+// check catches an edit that did not go through `mark_edited`
+pub fn hash_contents(app: &CodeEditorApp, doc: usize) -> u64 {
+    let Some(document) = app.panes.documents.get(&doc) else { return hash_text("") };
+    let len = contents_of(app, doc).len();
+    if let Some((revision, cached_len, hash)) = document.hashed.get() {
+        if revision == document.revision && cached_len == len {
+            return hash;
+        }
+    }
+    let hash = match &document.bytes {
+        Some(bytes) => hash_bytes(bytes),
+        None => hash_text(text_of(app, doc)),
+    };
+    document.hashed.set(Some((document.revision, len, hash)));
+    hash
+}
+
+// Every change to a document's contents goes through here; it also starts the auto-save delay
+pub fn mark_edited(app: &mut CodeEditorApp, doc: usize) {
+    if let Some(document) = app.panes.documents.get_mut(&doc) {
+        document.revision += 1;
+        app.autosave.edited = Some(Instant::now());
+    }
+}
+
+pub fn mark_active_edited(app: &mut CodeEditorApp) {
+    if let Some(doc) = app.panes.active_doc {
+        mark_edited(app, doc);
+    }
+}
+
+// Hash of file contents read from disk, comparable with the document's `saved_hash`;
+// None when a text document's file is no longer valid UTF-8
+pub fn hash_file_contents(document: &Document, contents: &[u8]) -> Option<u64> {
+    match document.bytes {
+        Some(_) => Some(hash_bytes(contents)),
+        None => std::str::from_utf8(contents).ok().map(hash_text),
+    }
+}
+
+pub fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub fn is_dirty(app: &CodeEditorApp, doc: usize) -> bool {
+    let Some(document) = app.panes.documents.get(&doc) else { return false };
+    match document.saved_hash {
+        Some(hash) => hash_contents(app, doc) != hash,
+        None => !contents_of(app, doc).is_empty(),
+    }
+}
+
+// A document read from `path`, or a new untitled buffer when there is none
+pub fn add_document(app: &mut CodeEditorApp, path: Option<PathBuf>, text: String) -> usize {
+    let id = app.panes.next_id();
+    let disk_modified = path.as_deref().and_then(modified_time);
+    let (saved_hash, untitled) = match path {
+        Some(_) => (Some(hash_text(&text)), 0),
+        None => {
+            app.panes.next_untitled += 1;
+            (None, app.panes.next_untitled)
+        }
+    };
+    app.panes.documents.insert(id, Document {
+        path,
+        text,
+        bytes: None,
+        saved_hash,
+        disk_modified,
+        missing: false,
+        lossy: false,
+        untitled,
+        revision: 0,
+        hashed: Cell::new(None),
+    });
+    id
+}
+
+pub fn add_binary_document(app: &mut CodeEditorApp, path: PathBuf, bytes: Vec<u8>) -> usize {
+    let id = add_document(app, Some(path), String::new());
+    if let Some(document) = app.panes.documents.get_mut(&id) {
+        document.saved_hash = Some(hash_bytes(&bytes));
+        document.bytes = Some(bytes);
+    }
+    id
+}
+
+// Records that the document's current text is what is on disk at `path`
+pub fn mark_saved(app: &mut CodeEditorApp, doc: usize, path: PathBuf) {
+    let hash = hash_contents(app, doc);
+    if app.panes.active_doc == Some(doc) {
+        app.file_path = Some(path.clone());
+    }
+    if let Some(document) = app.panes.documents.get_mut(&doc) {
+        document.disk_modified = modified_time(&path);
+        document.missing = false;
+        document.lossy = false;
+        document.path = Some(path);
+        document.saved_hash = Some(hash);
+    }
+}
+
+// Points documents at their new location after a file or folder was renamed or moved
+pub fn rename_path(app: &mut CodeEditorApp, from: &Path, to: &Path) {
+    for document in app.panes.documents.values_mut() {
+        let Some(rest) = document.path.as_ref().and_then(|p| p.strip_prefix(from).ok()) else { continue };
+        let moved = if rest.as_os_str().is_empty() { to.to_path_buf() } else { to.join(rest) };
+        document.path = Some(moved);
+        document.missing = false;
+    }
+    if let Some(doc) = app.panes.active_doc {
+        app.file_path = app.panes.documents.get(&doc).and_then(|d| d.path.clone());
+    }
+}
+
+// Shows a document in the active group, adding a tab for it if needed
+pub fn show(app: &mut CodeEditorApp, doc: usize) {
+    let group = &mut app.panes.groups[app.panes.active_group];
+    match group.tabs.iter().position(|&t| t == doc) {
+        Some(i) => group.active = i,
+        None => {
+            group.tabs.push(doc);
+            group.active = group.tabs.len() - 1;
+        }
+    }
+    activate(app);
+}
+
+// Makes the active group's current document the one held in `app.text`
+pub fn activate(app: &mut CodeEditorApp) {
+    let doc = app.panes.groups[app.panes.active_group].current();
+    if doc == app.panes.active_doc {
+        return;
+    }
+    if let Some(old) = app.panes.active_doc {
+        if let Some(document) = app.panes.documents.get_mut(&old) {
+            document.text = std::mem::take(&mut app.text);
+        }
+    }
+    app.panes.active_doc = doc;
+    match doc.and_then(|d| app.panes.documents.get_mut(&d)) {
+        Some(document) => {
+            app.text = std::mem::take(&mut document.text);
+            app.file_path = document.path.clone();
+        }
+        None => {
+            app.text.clear();
+            app.file_path = None;
+        }
+    }
+    app.cursor = None;
+    app.completion.active = false;
+    app.snippets.session = None;
+}
+
+pub fn focus_group(app: &mut CodeEditorApp, ctx: &egui::Context, group: usize) {
+    if group >= app.panes.groups.len() {
+        return;
+    }
+    app.panes.active_group = group;
+    activate(app);
+    ctx.memory_mut(|m| m.request_focus(crate::editor::editor_id(app)));
+}
+
+pub fn select_tab(app: &mut CodeEditorApp, group: usize, tab: usize) {
+    app.panes.active_group = group;
+    app.panes.groups[group].active = tab;
+    activate(app);
+}
+
+pub fn close_tab(app: &mut CodeEditorApp, group: usize, tab: usize) {
+    let Some(g) = app.panes.groups.get_mut(group) else { return };
+    if tab >= g.tabs.len() {
+        return;
+    }
+    let doc = g.tabs.remove(tab);
+    if g.active > tab || g.active >= g.tabs.len() {
+        g.active = g.active.saturating_sub(1);
+    }
+    remove_empty_group(app, group);
+    release(app, doc);
+    activate(app);
+}
+
+// Closes every tab showing the document
+pub fn close_document(app: &mut CodeEditorApp, doc: usize) {
+    for group in (0..app.panes.groups.len()).rev() {
+        if let Some(tab) = app.panes.groups[group].tabs.iter().position(|&t| t == doc) {
+            close_tab(app, group, tab);
+        }
+    }
+}
+
+// Closes like `close_tab`, `close_group` and `close_document`, but asks first about each
+// unsaved document that no other tab shows
+pub fn request_close(app: &mut CodeEditorApp, target: CloseTarget) {
+    let shown_elsewhere = |doc: usize, group_id: usize| app.panes.groups.iter().any(|g| g.id != group_id && g.tabs.contains(&doc));
+    let closed: Vec<usize> = match target {
+        CloseTarget::Tab(group_id, doc) => vec![doc].into_iter().filter(|&doc| !shown_elsewhere(doc, group_id)).collect(),
+        CloseTarget::Group(group_id) => app.panes.groups.iter()
+            .filter(|g| g.id == group_id)
+            .flat_map(|g| g.tabs.iter().copied())
+            .filter(|&doc| !shown_elsewhere(doc, group_id))
+            .collect(),
+        CloseTarget::Document(doc) => vec![doc],
+    };
+    let unsaved: Vec<usize> = closed.into_iter().filter(|&doc| is_dirty(app, doc)).collect();
+    if unsaved.is_empty() {
+        close_target(app, target);
+    } else {
+        app.panes.closing = Some((target, unsaved));
+    }
+}
+
+pub fn request_close_tab(app: &mut CodeEditorApp, group: usize, tab: usize) {
+    let Some(g) = app.panes.groups.get(group) else { return };
+    if let Some(&doc) = g.tabs.get(tab) {
+        request_close(app, CloseTarget::Tab(g.id, doc));
+    }
+}
+
+pub fn request_close_group(app: &mut CodeEditorApp, group: usize) {
+    if let Some(g) = app.panes.groups.get(group) {
+        request_close(app, CloseTarget::Group(g.id));
+    }
+}
+
+// The user answered for the first unsaved document, by saving it or letting it go; the close
+// happens once every one is answered
+pub fn close_answered(app: &mut CodeEditorApp) {
+    let Some((target, mut unsaved)) = app.panes.closing.take() else { return };
+    if !unsaved.is_empty() {
+        unsaved.remove(0);
+    }
+    unsaved.retain(|doc| app.panes.documents.contains_key(doc));
+    if unsaved.is_empty() {
+        close_target(app, target);
+    } else {
+        app.panes.closing = Some((target, unsaved));
+    }
+}
+
+fn close_target(app: &mut CodeEditorApp, target: CloseTarget) {
+    let group_index = |group_id: usize| app.panes.groups.iter().position(|g| g.id == group_id);
+    match target {
+        CloseTarget::Tab(group_id, doc) => {
+            let Some(group) = group_index(group_id) else { return };
+            if let Some(tab) = app.panes.groups[group].tabs.iter().position(|&t| t == doc) {
+                close_tab(app, group, tab);
+            }
+        }
+        CloseTarget::Group(group_id) => {
+            if let Some(group) = group_index(group_id) {
+                close_group(app, group);
+            }
+        }
+        CloseTarget::Document(doc) => close_document(app, doc),
+    }
+}
+
+// Drops the document once no tab shows it
+fn release(app: &mut CodeEditorApp, doc: usize) {
+    if app.panes.groups.iter().any(|g| g.tabs.contains(&doc)) {
+        return;
+    }
+    if app.panes.active_doc == Some(doc) {
+        app.panes.active_doc = None;
+        app.text.clear();
+        app.file_path = None;
+    }
+    app.panes.documents.remove(&doc);
+}
+
+fn remove_empty_group(app: &mut CodeEditorApp, group: usize) {
+    if app.panes.groups.len() > 1 && app.panes.groups[group].tabs.is_empty() {
+        app.panes.groups.remove(group);
+        if app.panes.active_group >= group && app.panes.active_group > 0 {
+            app.panes.active_group -= 1;
+        }
+    }
+}
+
+// Opens the current document in a new group next to the active one
+pub fn split(app: &mut CodeEditorApp, direction: SplitDirection) {
+    let id = app.panes.next_id();
+    let tabs = app.panes.active_doc.into_iter().collect();
+    let index = app.panes.active_group + 1;
+    app.panes.groups.insert(index, EditorGroup { id, tabs, active: 0 });
+    app.panes.direction = direction;
+    app.panes.active_group = index;
+    activate(app);
+}
+
+// Adds an empty group after the others and makes it active
+pub fn push_group(app: &mut CodeEditorApp) {
+    let id = app.panes.next_id();
+    app.panes.groups.push(EditorGroup { id, tabs: Vec::new(), active: 0 });
+    app.panes.active_group = app.panes.groups.len() - 1;
+    activate(app);
+}
+
+pub fn close_group(app: &mut CodeEditorApp, group: usize) {
+    if app.panes.groups.len() < 2 {
+        return;
+    }
+    let removed = app.panes.groups.remove(group);
+    if app.panes.active_group >= app.panes.groups.len() || app.panes.active_group > group {
+        app.panes.active_group = app.panes.active_group.saturating_sub(1);
+    }
+    for doc in removed.tabs {
+        release(app, doc);
+    }
+    activate(app);
+}
+
+pub fn move_tab(app: &mut CodeEditorApp, from: usize, tab: usize, to: usize) {
+    if from == to || to >= app.panes.groups.len() {
+        return;
+    }
+    let Some(&doc) = app.panes.groups[from].tabs.get(tab) else { return };
+    let source = &mut app.panes.groups[from];
+    source.tabs.remove(tab);
+    if source.active > tab || source.active >= source.tabs.len() {
+        source.active = source.active.saturating_sub(1);
+    }
+
+    let target = &mut app.panes.groups[to];
+    let position = match target.tabs.iter().position(|&t| t == doc) {
+        Some(i) => i,
+        None => {
+            target.tabs.push(doc);
+            target.tabs.len() - 1
+        }
+    };
+    target.active = position;
+    app.panes.active_group = to;
+
+    let emptied = app.panes.groups.len() > 1 && app.panes.groups[from].tabs.is_empty();
+    if emptied {
+        app.panes.groups.remove(from);
+        if app.panes.active_group > from {
+            app.panes.active_group -= 1;
+        }
+    }
+    activate(app);
+}
+
+// Moves the active tab to the neighbouring group, splitting if there is none
+pub fn move_active_tab(app: &mut CodeEditorApp, forward: bool) {
+    let from = app.panes.active_group;
+    let tab = app.panes.groups[from].active;
+    if app.panes.groups[from].current().is_none() {
+        return;
+    }
+    if forward && from + 1 == app.panes.groups.len() {
+        if app.panes.groups[from].tabs.len() < 2 {
+            return;
+        }
+        let direction = app.panes.direction;
+        split(app, direction);
+        let group = &mut app.panes.groups[from];
+        group.tabs.remove(tab);
+        group.active = group.active.min(group.tabs.len().saturating_sub(1));
+        return;
+    }
+    if !forward && from == 0 {
+        return;
+    }
+    let to = if forward { from + 1 } else { from - 1 };
+    move_tab(app, from, tab, to);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(app: &mut CodeEditorApp, text: &str) -> usize {
+        let doc = add_document(app, None, text.to_string());
+        show(app, doc);
+        doc
+    }
+
+    fn tabs(app: &CodeEditorApp) -> Vec<Vec<usize>> {
+        app.panes.groups.iter().map(|g| g.tabs.clone()).collect()
+    }
+
+    #[test]
+    fn moving_a_groups_last_tab_removes_the_group() {
+        let mut app = CodeEditorApp::default();
+        let a = open(&mut app, "a");
+        push_group(&mut app);
+        let b = open(&mut app, "b");
+        push_group(&mut app);
+        let c = open(&mut app, "c");
+
+        move_tab(&mut app, 0, 0, 2);
+        assert_eq!(tabs(&app), vec![vec![b], vec![c, a]]);
+        // The target group moved down one place when the emptied group before it went
+        assert_eq!(app.panes.active_group, 1);
+        assert_eq!(app.panes.groups[1].active, 1);
+        assert_eq!(app.panes.active_doc, Some(a));
+        assert_eq!(app.text, "a");
+        assert_eq!(app.panes.documents[&c].text, "c");
+    }
+
+    #[test]
+    fn moving_a_tab_within_its_group_or_onto_itself_changes_nothing() {
+        let mut app = CodeEditorApp::default();
+        let a = open(&mut app, "a");
+        let b = open(&mut app, "b");
+        move_tab(&mut app, 0, 0, 0);
+        assert_eq!(tabs(&app), vec![vec![a, b]]);
+        assert_eq!(app.panes.groups[0].active, 1);
+
+        // A group already showing the document just switches to it
+        split(&mut app, SplitDirection::Horizontal);
+        select_tab(&mut app, 0, 1);
+        move_tab(&mut app, 0, 1, 1);
+        assert_eq!(tabs(&app), vec![vec![a], vec![b]]);
+        assert_eq!(app.panes.active_group, 1);
+        assert_eq!(app.panes.active_doc, Some(b));
+    }
+
+    #[test]
+    fn splitting_the_rightmost_group_appends_one() {
+        let mut app = CodeEditorApp::default();
+        let a = open(&mut app, "a");
+        split(&mut app, SplitDirection::Horizontal);
+        let b = open(&mut app, "b");
+        split(&mut app, SplitDirection::Vertical);
+        assert_eq!(tabs(&app), vec![vec![a], vec![a, b], vec![b]]);
+        assert_eq!(app.panes.active_group, 2);
+        assert_eq!(app.panes.direction, SplitDirection::Vertical);
+
+        // Moving on from the rightmost group splits it, taking the tab along
+        let c = open(&mut app, "c");
+        move_active_tab(&mut app, true);
+        assert_eq!(tabs(&app), vec![vec![a], vec![a, b], vec![b], vec![c]]);
+        assert_eq!(app.panes.active_group, 3);
+        assert_eq!(app.text, "c");
+
+        // A lone tab has nowhere to go, and the first group has no previous one
+        move_active_tab(&mut app, true);
+        assert_eq!(app.panes.groups.len(), 4);
+        select_tab(&mut app, 0, 0);
+        move_active_tab(&mut app, false);
+        assert_eq!(tabs(&app)[0], vec![a]);
+    }
+
+    #[test]
+    fn closing_an_unsaved_tab_asks_first() {
+        let mut app = CodeEditorApp::default();
+        let clean = open(&mut app, "");
+        let draft = open(&mut app, "draft");
+        request_close_tab(&mut app, 0, 0);
+        assert!(!app.panes.documents.contains_key(&clean));
+
+        request_close_tab(&mut app, 0, 0);
+        assert_eq!(app.panes.closing, Some((CloseTarget::Tab(0, draft), vec![draft])));
+        assert_eq!(app.panes.groups[0].tabs, vec![draft]);
+        close_answered(&mut app);
+        assert!(app.panes.closing.is_none());
+        assert!(app.panes.documents.is_empty());
+    }
+
+    #[test]
+    fn closing_a_group_asks_only_about_documents_it_alone_shows() {
+        let mut app = CodeEditorApp::default();
+        let shared = open(&mut app, "shared");
+        split(&mut app, SplitDirection::Horizontal);
+        let second = app.panes.groups[1].id;
+        let own = open(&mut app, "own");
+        let other = open(&mut app, "other");
+        assert_eq!(app.panes.groups[1].tabs, vec![shared, own, other]);
+
+        request_close_group(&mut app, 1);
+        assert_eq!(app.panes.closing, Some((CloseTarget::Group(second), vec![own, other])));
+        close_answered(&mut app);
+        assert_eq!(app.panes.groups.len(), 2);
+        close_answered(&mut app);
+        assert_eq!(app.panes.groups.len(), 1);
+        assert!(app.panes.documents.contains_key(&shared));
+        assert!(!app.panes.documents.contains_key(&own));
+    }
+
+    #[test]
+    fn dirty_state_follows_edits() {
+        let mut app = CodeEditorApp::default();
+        let doc = add_document(&mut app, Some(PathBuf::from("/nonexistent/a.rs")), "fn a() {}".to_string());
+        assert!(!is_dirty(&app, doc));
+
+        app.panes.documents.get_mut(&doc).unwrap().text = "fn b() {}".to_string();
+        mark_edited(&mut app, doc);
+        assert!(is_dirty(&app, doc));
+
+        app.panes.documents.get_mut(&doc).unwrap().text = "fn a() {}".to_string();
+        mark_edited(&mut app, doc);
+        assert!(!is_dirty(&app, doc));
+    }
+
+    #[test]
+    fn edits_start_the_auto_save_delay() {
+        let mut app = CodeEditorApp::default();
+        let doc = add_document(&mut app, None, String::new());
+        assert!(app.autosave.edited.is_none());
+        mark_edited(&mut app, doc);
+        assert!(app.autosave.edited.is_some());
+    }
+
+    #[test]
+    fn hash_is_cached_until_the_next_edit() {
+        let mut app = CodeEditorApp::default();
+        let doc = add_document(&mut app, None, "one".to_string());
+        let first = hash_contents(&app, doc);
+        assert_eq!(app.panes.documents[&doc].hashed.get().map(|(_, _, hash)| hash), Some(first));
+
+        app.panes.documents.get_mut(&doc).unwrap().text = "two".to_string();
+        assert_eq!(hash_contents(&app, doc), first);
+        mark_edited(&mut app, doc);
+        assert_eq!(hash_contents(&app, doc), hash_text("two"));
+
+        // A length change is picked up even without the hook
+        app.panes.documents.get_mut(&doc).unwrap().text = "three".to_string();
+        assert_eq!(hash_contents(&app, doc), hash_text("three"));
+    }
+}
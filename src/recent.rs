@@ -0,0 +1,162 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::config;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+
+// Unpinned entries kept per list; pinned ones are never dropped
+const LIMIT: usize = 15;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecentEntry {
+    pub path: PathBuf,
+    pub pinned: bool,
+    // Checked on load and when the file system reports a change, not on every frame
+    #[serde(skip)]
+    pub exists: bool,
+}
+
+// Recently opened files and folders, pinned entries first and then newest first
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentList {
+    pub folders: Vec<RecentEntry>,
+    pub files: Vec<RecentEntry>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecentKind {
+    Folder,
+    File,
+}
+
+impl RecentList {
+    pub fn load() -> Self {
+        let mut list: Self = recent_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        for entry in list.folders.iter_mut().chain(list.files.iter_mut()) {
+            entry.exists = entry.path.exists();
+        }
+        list
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = recent_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        file_ops::write_atomic(&path, content, false)
+    }
+
+    fn list_mut(&mut self, kind: RecentKind) -> &mut Vec<RecentEntry> {
+        match kind {
+            RecentKind::Folder => &mut self.folders,
+            RecentKind::File => &mut self.files,
+        }
+    }
+}
+
+fn recent_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("recent.json"))
+}
+
+fn save(app: &mut CodeEditorApp) {
+    if let Err(e) = app.recent.save() {
+        notifications::notify(app, Level::Error, format!("Could not save the recent files list: {}", e), Vec::new());
+    }
+}
+
+// Moves the path to the top of its list, keeping its pin
+pub fn add(app: &mut CodeEditorApp, kind: RecentKind, path: &Path) {
+    let list = app.recent.list_mut(kind);
+    let pinned = list.iter().any(|e| e.path == path && e.pinned);
+    list.retain(|e| e.path != path);
+    list.insert(0, RecentEntry { path: path.to_path_buf(), pinned, exists: true });
+    sort_and_trim(list);
+    save(app);
+}
+
+pub fn toggle_pin(app: &mut CodeEditorApp, kind: RecentKind, path: &Path) {
+    let list = app.recent.list_mut(kind);
+    for entry in list.iter_mut().filter(|e| e.path == path) {
+        entry.pinned = !entry.pinned;
+    }
+    sort_and_trim(list);
+    save(app);
+}
+
+pub fn remove(app: &mut CodeEditorApp, kind: RecentKind, path: &Path) {
+    app.recent.list_mut(kind).retain(|e| e.path != path);
+    save(app);
+}
+
+// Rechecks entries at or below paths the file system reported as changed
+pub fn paths_changed(app: &mut CodeEditorApp, paths: &BTreeSet<PathBuf>) {
+    let recent = &mut app.recent;
+    for entry in recent.folders.iter_mut().chain(recent.files.iter_mut()) {
+        if paths.iter().any(|p| entry.path.starts_with(p)) {
+            entry.exists = entry.path.exists();
+        }
+    }
+}
+
+// Forgets everything that is not pinned
+pub fn clear(app: &mut CodeEditorApp) {
+    app.recent.folders.retain(|e| e.pinned);
+    app.recent.files.retain(|e| e.pinned);
+    save(app);
+}
+
+fn sort_and_trim(list: &mut Vec<RecentEntry>) {
+    list.sort_by_key(|e| !e.pinned);
+    let mut unpinned = 0;
+    list.retain(|e| {
+        if !e.pinned {
+            unpinned += 1;
+        }
+        e.pinned || unpinned <= LIMIT
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn existence_is_rechecked_for_changed_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "").unwrap();
+        let recent = RecentList {
+            folders: vec![RecentEntry { path: dir.path().to_path_buf(), pinned: false, exists: true }],
+            files: vec![RecentEntry { path: file.clone(), pinned: false, exists: true }],
+        };
+        let mut app = CodeEditorApp { recent, ..Default::default() };
+
+        fs::remove_file(&file).unwrap();
+        paths_changed(&mut app, &BTreeSet::from([PathBuf::from("/elsewhere")]));
+        assert!(app.recent.files[0].exists);
+        // A removed folder reports only its own path
+        paths_changed(&mut app, &BTreeSet::from([dir.path().to_path_buf()]));
+        assert!(!app.recent.files[0].exists);
+        assert!(app.recent.folders[0].exists);
+    }
+
+    #[test]
+    fn unpinned_entries_are_trimmed_after_pinned_ones() {
+        let entry = |i: usize, pinned| RecentEntry { path: PathBuf::from(format!("/f{}", i)), pinned, exists: true };
+        let mut list: Vec<RecentEntry> = (0..LIMIT + 3).map(|i| entry(i, i == LIMIT + 2)).collect();
+        sort_and_trim(&mut list);
+        assert_eq!(list.len(), LIMIT + 1);
+        assert_eq!(list[0].path, PathBuf::from(format!("/f{}", LIMIT + 2)));
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::config;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+use crate::panes;
+
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(2);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+// A session whose heartbeat is older than this belongs to an instance that is no longer running
+const STALE_AFTER: Duration = Duration::from_secs(15);
+const HEARTBEAT: &str = "alive";
+
+// The unsaved contents of one buffer
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    path: Option<PathBuf>,
+    text: String,
+    // A buffer edited in the hex view keeps its bytes in a raw file next to the snapshot instead of `text`
+    #[serde(default)]
+    binary: bool,
+}
+
+// One snapshot's worth of file work, done in order on the writer thread so typing never waits on a disk
+struct Batch {
+    dir: PathBuf,
+    writes: Vec<(usize, Snapshot, Option<Vec<u8>>)>,
+    removed: Vec<usize>,
+    heartbeat: bool,
+    // Nothing is left to keep, so the session folder goes
+    remove_dir: bool,
+}
+
+// Documents whose snapshot could not be written, and why
+struct Written {
+    failed: Vec<usize>,
+    error: Option<io::Error>,
+}
+
+pub struct RecoveryState {
+    // This instance's folder in the recovery directory, created once there is something to keep
+    dir: Option<PathBuf>,
+    last_snapshot: Option<Instant>,
+    last_heartbeat: Option<Instant>,
+    // Path and contents hash last written for each document
+    written: BTreeMap<usize, (Option<PathBuf>, u64)>,
+    failed: bool,
+    writer: Option<(Sender<Batch>, Receiver<Written>)>,
+    // Batches sent to the writer that have not been answered yet
+    pending: usize,
+    // Documents brought back from a previous session that have not been saved since
+    pub recovered: BTreeSet<usize>,
+}
+
+impl Default for RecoveryState {
+    fn default() -> Self {
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let session = format!("{}-{}", std::process::id(), started);
+        Self {
+            dir: config::config_dir().map(|dir| dir.join("recovery").join(session)),
+            last_snapshot: None,
+            last_heartbeat: None,
+            written: BTreeMap::new(),
+            failed: false,
+            writer: None,
+            pending: 0,
+            recovered: BTreeSet::new(),
+        }
+    }
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    ctx.request_repaint_after(SNAPSHOT_INTERVAL);
+    if app.recovery.last_snapshot.is_some_and(|t| t.elapsed() < SNAPSHOT_INTERVAL) {
+        return;
+    }
+    snapshot(app);
+}
+
+// Sends the modified buffers that changed since the last snapshot to the writer thread and drops
+// the snapshots of buffers that were saved, reverted or closed
+pub fn snapshot(app: &mut CodeEditorApp) {
+    receive(app, false);
+    app.recovery.last_snapshot = Some(Instant::now());
+    let Some(dir) = app.recovery.dir.clone() else { return };
+    let dirty: BTreeMap<usize, (Option<PathBuf>, u64)> = app.panes.documents.iter()
+        .filter(|(&doc, _)| panes::is_dirty(app, doc))
+        .map(|(&doc, d)| (doc, (d.path.clone(), panes::hash_contents(app, doc))))
+        .collect();
+
+    let removed: Vec<usize> = app.recovery.written.keys().filter(|doc| !dirty.contains_key(doc)).copied().collect();
+    for doc in &removed {
+        app.recovery.written.remove(doc);
+    }
+    app.recovery.recovered.retain(|doc| dirty.contains_key(doc));
+    if app.recovery.written.is_empty() && dirty.is_empty() {
+        if app.recovery.last_heartbeat.take().is_some() {
+            send(app, Batch { dir, writes: Vec::new(), removed, heartbeat: false, remove_dir: true });
+        }
+        return;
+    }
+
+    // Only copying happens here; encoding and writing are left to the writer thread
+    let mut writes = Vec::new();
+    for (doc, state) in dirty {
+        if app.recovery.written.get(&doc) == Some(&state) {
+            continue;
+        }
+        let binary = app.panes.documents.get(&doc).is_some_and(|d| d.bytes.is_some());
+        let text = if binary { String::new() } else { panes::text_of(app, doc).to_string() };
+        let bytes = binary.then(|| panes::contents_of(app, doc).to_vec());
+        writes.push((doc, Snapshot { path: state.0.clone(), text, binary }, bytes));
+        app.recovery.written.insert(doc, state);
+    }
+    let heartbeat = match app.recovery.last_heartbeat {
+        Some(beat) => beat.elapsed() >= HEARTBEAT_INTERVAL,
+        None => true,
+    };
+    if heartbeat {
+        app.recovery.last_heartbeat = Some(Instant::now());
+    }
+    if !writes.is_empty() || !removed.is_empty() || heartbeat {
+        send(app, Batch { dir, writes, removed, heartbeat, remove_dir: false });
+    }
+}
+
+fn send(app: &mut CodeEditorApp, batch: Batch) {
+    let (sender, _) = app.recovery.writer.get_or_insert_with(|| {
+        let (sender, batches) = channel::<Batch>();
+        let (results, receiver) = channel();
+        thread::spawn(move || {
+            for batch in batches {
+                if results.send(write_batch(batch)).is_err() {
+                    break;
+                }
+            }
+        });
+        (sender, receiver)
+    });
+    if sender.send(batch).is_ok() {
+        app.recovery.pending += 1;
+    }
+}
+
+// Handles the batches the writer finished, waiting for all of them when `wait` is set
+fn receive(app: &mut CodeEditorApp, wait: bool) {
+    let mut error = None;
+    let mut answered = false;
+    while app.recovery.pending > 0 {
+        let Some((_, results)) = &app.recovery.writer else { break };
+        let result = if wait { results.recv().ok() } else { results.try_recv().ok() };
+        let Some(written) = result else { break };
+        app.recovery.pending -= 1;
+        answered = true;
+        // Written again with the next snapshot
+        for doc in written.failed {
+            app.recovery.written.remove(&doc);
+        }
+        error = error.or(written.error);
+    }
+
+    // Reported once rather than on every snapshot
+    match error {
+        Some(e) if !app.recovery.failed => {
+            app.recovery.failed = true;
+            let dir = app.recovery.dir.as_deref().unwrap_or(Path::new("")).display();
+            let message = format!("Could not write recovery copies to {}: {}", dir, e);
+            notifications::notify(app, Level::Error, message, Vec::new());
+        }
+        Some(_) => {}
+        None if answered => app.recovery.failed = false,
+        None => {}
+    }
+}
+
+fn write_batch(batch: Batch) -> Written {
+    let mut written = Written { failed: Vec::new(), error: None };
+    for doc in batch.removed {
+        let _ = fs::remove_file(snapshot_path(&batch.dir, doc));
+        let _ = fs::remove_file(bytes_path(&batch.dir, doc));
+    }
+    for (doc, snapshot, bytes) in batch.writes {
+        if let Err(e) = write_snapshot(&batch.dir, doc, &snapshot, bytes.as_deref()) {
+            written.failed.push(doc);
+            written.error = Some(e);
+        }
+    }
+    if batch.heartbeat {
+        let _ = fs::write(batch.dir.join(HEARTBEAT), "");
+    }
+    if batch.remove_dir {
+        let _ = fs::remove_dir_all(&batch.dir);
+    }
+    written
+}
+
+fn snapshot_path(dir: &Path, doc: usize) -> PathBuf {
+    dir.join(format!("{}.json", doc))
+}
+
+fn bytes_path(dir: &Path, doc: usize) -> PathBuf {
+    dir.join(format!("{}.bin", doc))
+}
+
+// The raw bytes go first, so a snapshot that says it is binary always has them
+fn write_snapshot(dir: &Path, doc: usize, snapshot: &Snapshot, bytes: Option<&[u8]>) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    match bytes {
+        Some(bytes) => file_ops::write_atomic(&bytes_path(dir, doc), bytes, false)?,
+        None => {
+            let _ = fs::remove_file(bytes_path(dir, doc));
+        }
+    }
+    let content = serde_json::to_string(snapshot).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    file_ops::write_atomic(&snapshot_path(dir, doc), &content, false)
+}
+
+fn read_snapshot(file: &Path) -> Option<(Snapshot, Option<Vec<u8>>)> {
+    let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(file).ok()?).ok()?;
+    let bytes = match snapshot.binary {
+        true => Some(fs::read(file.with_extension("bin")).ok()?),
+        false => None,
+    };
+    Some((snapshot, bytes))
+}
+
+// Keeps the unsaved buffers for the next launch instead of asking about them
+pub fn exit(app: &mut CodeEditorApp) {
+    snapshot(app);
+    receive(app, true);
+    if let Some(dir) = &app.recovery.dir {
+        let _ = fs::remove_file(dir.join(HEARTBEAT));
+    }
+}
+
+// Reopens the unsaved buffers of sessions that crashed or were closed with changes
+pub fn restore(app: &mut CodeEditorApp) {
+    let Some(root) = app.recovery.dir.as_deref().and_then(Path::parent).map(Path::to_path_buf) else { return };
+    let Ok(entries) = fs::read_dir(&root) else { return };
+    let sessions: Vec<PathBuf> = entries.filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() && !is_running(p))
+        .collect();
+
+    let previous = app.panes.active_doc;
+    let mut count = 0;
+    for session in &sessions {
+        let mut files: Vec<(usize, PathBuf)> = fs::read_dir(session).into_iter().flatten()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let path = e.path();
+                if path.extension()? != "json" {
+                    return None;
+                }
+                let doc = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((doc, path))
+            })
+            .collect();
+        files.sort();
+        for (_, file) in files {
+            if let Some((snapshot, bytes)) = read_snapshot(&file) {
+                let doc = reopen(app, snapshot, bytes);
+                app.recovery.recovered.insert(doc);
+                count += 1;
+            }
+        }
+    }
+
+    if count > 0 {
+        if let Some(previous) = previous {
+            let unused = app.panes.documents.get(&previous).is_some_and(|d| d.path.is_none()) && !panes::is_dirty(app, previous);
+            if unused {
+                panes::close_document(app, previous);
+            }
+        }
+        let message = format!("Recovered {} unsaved file(s) from the last session", count);
+        notifications::notify(app, Level::Info, message, Vec::new());
+    }
+    // The old sessions are only dropped once this one holds their buffers
+    snapshot(app);
+    receive(app, true);
+    if !app.recovery.failed {
+        for session in sessions {
+            let _ = fs::remove_dir_all(session);
+        }
+    }
+}
+
+fn is_running(session: &Path) -> bool {
+    fs::metadata(session.join(HEARTBEAT))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age < STALE_AFTER)
+}
+
+// A file that is still open or on disk keeps that as its baseline, so the recovered text shows as modified
+fn reopen(app: &mut CodeEditorApp, snapshot: Snapshot, bytes: Option<Vec<u8>>) -> usize {
+    if let (Some(path), Some(bytes)) = (snapshot.path.clone(), bytes) {
+        return reopen_binary(app, path, bytes);
+    }
+    if let Some(doc) = snapshot.path.as_deref().and_then(|p| app.panes.find(p)) {
+        panes::show(app, doc);
+        app.text = snapshot.text;
+        panes::mark_edited(app, doc);
+        return doc;
+    }
+    let on_disk = snapshot.path.as_ref().and_then(|p| fs::read_to_string(p).ok());
+    let doc = match on_disk {
+        Some(content) => panes::add_document(app, snapshot.path.clone(), content),
+        None => {
+            let doc = panes::add_document(app, snapshot.path.clone(), String::new());
+            if let Some(document) = app.panes.documents.get_mut(&doc) {
+                document.saved_hash = None;
+                document.missing = document.path.as_ref().is_some_and(|p| !p.exists());
+            }
+            doc
+        }
+    };
+    panes::show(app, doc);
+    app.text = snapshot.text;
+    panes::mark_edited(app, doc);
+    doc
+}
+
+fn reopen_binary(app: &mut CodeEditorApp, path: PathBuf, bytes: Vec<u8>) -> usize {
+    let doc = match app.panes.find(&path) {
+        Some(doc) => doc,
+        None => {
+            let on_disk = fs::read(&path).ok();
+            let missing = on_disk.is_none();
+            let doc = panes::add_binary_document(app, path, on_disk.unwrap_or_default());
+            if let Some(document) = app.panes.documents.get_mut(&doc).filter(|_| missing) {
+                document.saved_hash = None;
+                document.missing = true;
+            }
+            doc
+        }
+    };
+    if let Some(document) = app.panes.documents.get_mut(&doc) {
+        document.bytes = Some(bytes);
+    }
+    panes::mark_edited(app, doc);
+    panes::show(app, doc);
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_snapshot(path: &Path, text: &str) -> Snapshot {
+        Snapshot { path: Some(path.to_path_buf()), text: text.to_string(), binary: false }
+    }
+
+    #[test]
+    fn snapshots_follow_unsaved_changes() {
+        let files = tempfile::tempdir().unwrap();
+        let path = files.path().join("a.rs");
+        fs::write(&path, "saved\n").unwrap();
+        let mut app = CodeEditorApp::default();
+        let dir = app.recovery.dir.clone().unwrap();
+        let doc = panes::add_document(&mut app, Some(path.clone()), "saved\n".to_string());
+        panes::show(&mut app, doc);
+
+        app.text.push_str("draft\n");
+        panes::mark_edited(&mut app, doc);
+        snapshot(&mut app);
+        receive(&mut app, true);
+        let (written, _) = read_snapshot(&snapshot_path(&dir, doc)).unwrap();
+        assert_eq!(written.path, Some(path));
+        assert_eq!(written.text, "saved\ndraft\n");
+        assert!(dir.join(HEARTBEAT).exists());
+
+        // Back to the saved text, so there is nothing left to keep
+        app.text.truncate("saved\n".len());
+        panes::mark_edited(&mut app, doc);
+        snapshot(&mut app);
+        receive(&mut app, true);
+        assert!(!snapshot_path(&dir, doc).exists());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn stale_sessions_are_restored_over_the_file_on_disk() {
+        let files = tempfile::tempdir().unwrap();
+        let path = files.path().join("b.rs");
+        fs::write(&path, "on disk\n").unwrap();
+        let mut app = CodeEditorApp::default();
+        let dir = app.recovery.dir.clone().unwrap();
+        let old = dir.parent().unwrap().join("1-1");
+        write_snapshot(&old, 4, &text_snapshot(&path, "recovered\n"), None).unwrap();
+
+        restore(&mut app);
+        let doc = app.panes.find(&path).unwrap();
+        assert_eq!(panes::text_of(&app, doc), "recovered\n");
+        assert!(panes::is_dirty(&app, doc));
+        assert!(app.recovery.recovered.contains(&doc));
+        // This session holds the buffer now, so the old one is gone
+        assert!(!old.exists());
+        assert_eq!(read_snapshot(&snapshot_path(&dir, doc)).unwrap().0.text, "recovered\n");
+    }
+
+    #[test]
+    fn sessions_with_a_recent_heartbeat_are_left_alone() {
+        let files = tempfile::tempdir().unwrap();
+        let path = files.path().join("c.rs");
+        fs::write(&path, "on disk\n").unwrap();
+        let mut app = CodeEditorApp::default();
+        let old = app.recovery.dir.as_deref().and_then(Path::parent).unwrap().join("1-1");
+        write_snapshot(&old, 4, &text_snapshot(&path, "still open elsewhere\n"), None).unwrap();
+        fs::write(old.join(HEARTBEAT), "").unwrap();
+
+        restore(&mut app);
+        assert!(app.panes.find(&path).is_none());
+        assert!(snapshot_path(&old, 4).exists());
+    }
+
+    #[test]
+    fn binary_snapshots_round_trip_through_a_raw_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = vec![0x00, 0xFF, 0x7F, b'\n'];
+        let snapshot = Snapshot { path: Some(PathBuf::from("/a.bin")), text: String::new(), binary: true };
+        write_snapshot(dir.path(), 3, &snapshot, Some(&bytes)).unwrap();
+        let (read, read_bytes) = read_snapshot(&snapshot_path(dir.path(), 3)).unwrap();
+        assert!(read.binary);
+        assert_eq!(read.path, snapshot.path);
+        assert_eq!(read_bytes, Some(bytes));
+
+        // Once the buffer is text again the raw file is dropped
+        let snapshot = Snapshot { path: Some(PathBuf::from("/a.bin")), text: "hi".to_string(), binary: false };
+        write_snapshot(dir.path(), 3, &snapshot, None).unwrap();
+        assert!(!bytes_path(dir.path(), 3).exists());
+        let (read, read_bytes) = read_snapshot(&snapshot_path(dir.path(), 3)).unwrap();
+        assert_eq!(read.text, "hi");
+        assert_eq!(read_bytes, None);
+    }
+}
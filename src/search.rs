@@ -0,0 +1,372 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use eframe::egui;
+use crate::app::CodeEditorApp;
+use crate::completion;
+use crate::editor;
+use crate::excludes;
+use crate::file_ops;
+use crate::file_tree;
+use crate::hex;
+use crate::panes;
+
+const MAX_QUICK_OPEN_ITEMS: usize = 50;
+const MAX_MATCHES: usize = 5000;
+const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+// Files under a workspace root, sorted
+type FileIndex = (PathBuf, Arc<Vec<PathBuf>>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub path: PathBuf,
+    pub line: usize,
+    // Character columns of the match within the line
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct QuickOpen {
+    pub query: String,
+    pub matches: Vec<PathBuf>,
+    pub selected: usize,
+}
+
+#[derive(Default)]
+pub struct SearchState {
+    index: Option<FileIndex>,
+    listing: Option<Receiver<FileIndex>>,
+    // Files came or went since the index was listed
+    stale: bool,
+    pub quick_open: Option<QuickOpen>,
+    // Find in files
+    pub query: String,
+    pub case_sensitive: bool,
+    pub matches: Vec<Match>,
+    // Stopped at MAX_MATCHES
+    pub truncated: bool,
+    pub focus_query: bool,
+    results: Option<Receiver<Vec<Match>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl SearchState {
+    pub fn searching(&self) -> bool {
+        self.results.is_some()
+    }
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if let Some(listing) = &app.search.listing {
+        match listing.try_recv() {
+            Ok(index) => {
+                app.search.index = Some(index);
+                app.search.listing = None;
+                refresh_quick_open(app);
+                file_tree::index_changed(app);
+            }
+            Err(TryRecvError::Disconnected) => app.search.listing = None,
+            Err(TryRecvError::Empty) => {}
+        }
+    }
+    let indexed_root = app.search.index.as_ref().map(|(root, _)| root);
+    let outdated = indexed_root != app.root_folder.as_ref() || app.search.stale;
+    if outdated && app.search.listing.is_none() {
+        match app.root_folder.clone() {
+            Some(root) => start_listing(app, ctx, root),
+            None => app.search.index = None,
+        }
+    }
+
+    let search = &mut app.search;
+    let Some(results) = &search.results else { return };
+    let mut finished = false;
+    loop {
+        match results.try_recv() {
+            Ok(found) => search.matches.extend(found),
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                finished = true;
+                break;
+            }
+        }
+    }
+    if search.matches.len() >= MAX_MATCHES {
+        search.matches.truncate(MAX_MATCHES);
+        search.truncated = true;
+        search.cancel.store(true, Ordering::Relaxed);
+        finished = true;
+    }
+    if finished {
+        search.results = None;
+    }
+}
+
+fn start_listing(app: &mut CodeEditorApp, ctx: &egui::Context, root: PathBuf) {
+    let settings = app.config.excludes.clone();
+    let (sender, receiver) = mpsc::channel();
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        let mut files: Vec<PathBuf> = excludes::workspace_files(&root, &settings).collect();
+        files.sort();
+        let _ = sender.send((root, Arc::new(files)));
+        ctx.request_repaint();
+    });
+    app.search.listing = Some(receiver);
+    app.search.stale = false;
+}
+
+// The workspace's files, or None until they have been listed once
+pub fn workspace_index(app: &CodeEditorApp) -> Option<Arc<Vec<PathBuf>>> {
+    let (root, files) = app.search.index.as_ref()?;
+    (app.root_folder.as_ref() == Some(root)).then(|| Arc::clone(files))
+}
+
+// Lists the workspace again when files were created, removed or renamed under it;
+// edits to files already in the index leave it alone
+pub fn files_changed(app: &mut CodeEditorApp, paths: &BTreeSet<PathBuf>) {
+    if app.search.listing.is_some() {
+        app.search.stale = true;
+        return;
+    }
+    let Some((root, files)) = &app.search.index else { return };
+    let changed = paths.iter().filter(|path| path.starts_with(root)).any(|path| {
+        if excludes::is_ignore_file(path) {
+            return true;
+        }
+        let indexed = files.binary_search(path).is_ok();
+        match fs::metadata(path) {
+            Ok(meta) if meta.is_dir() => true,
+            Ok(_) => !indexed,
+            Err(_) => indexed || files.iter().any(|f| f.starts_with(path)),
+        }
+    });
+    if changed {
+        app.search.stale = true;
+    }
+}
+
+// Called after the exclude settings were edited
+pub fn settings_changed(app: &mut CodeEditorApp) {
+    app.search.stale = true;
+}
+
+pub fn open_quick_open(app: &mut CodeEditorApp) {
+    if app.root_folder.is_none() {
+        return;
+    }
+    app.search.quick_open = Some(QuickOpen::default());
+    refresh_quick_open(app);
+}
+
+// Ranks the workspace files against the typed query again
+pub fn refresh_quick_open(app: &mut CodeEditorApp) {
+    let (Some(root), Some(files)) = (app.root_folder.clone(), workspace_index(app)) else { return };
+    let Some(quick_open) = app.search.quick_open.as_mut() else { return };
+    quick_open.matches = quick_open_matches(&root, &files, &quick_open.query);
+    quick_open.selected = 0;
+}
+
+// Workspace files whose name, or failing that whose relative path, fuzzy-matches the query
+pub fn quick_open_matches(root: &Path, files: &[PathBuf], query: &str) -> Vec<PathBuf> {
+    let query: String = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return files.iter().take(MAX_QUICK_OPEN_ITEMS).cloned().collect();
+    }
+    let mut scored: Vec<(f32, &PathBuf)> = files.iter()
+        .filter_map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+            // A hit on the file name beats one spread across folders
+            let score = completion::fuzzy_score(&query, &name).map(|s| s + 20.0)
+                .or_else(|| completion::fuzzy_score(&query, &relative))?;
+            Some((score, path))
+        })
+        .collect();
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1.components().count().cmp(&b.1.components().count()))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    scored.into_iter().take(MAX_QUICK_OPEN_ITEMS).map(|(_, path)| path.clone()).collect()
+}
+
+pub fn open_file(app: &mut CodeEditorApp, ctx: &egui::Context, path: PathBuf) {
+    app.search.quick_open = None;
+    file_ops::open_path(app, path);
+    ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+}
+
+pub fn open_find_in_files(app: &mut CodeEditorApp) {
+    app.show_terminal = true;
+    app.bottom_panel = crate::app::BottomPanel::Search;
+    app.search.focus_query = true;
+    // Seed the query with a one-line selection
+    if let Some((a, b)) = app.cursor.filter(|(a, b)| a != b) {
+        let selected: String = app.text.chars().skip(a.min(b)).take(a.max(b) - a.min(b)).collect();
+        if !selected.contains('\n') {
+            app.search.query = selected;
+        }
+    }
+}
+
+// Searches every workspace file on a background thread; open documents are searched as edited
+pub fn start_search(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    app.search.cancel.store(true, Ordering::Relaxed);
+    app.search.results = None;
+    app.search.matches.clear();
+    app.search.truncated = false;
+    let Some(root) = app.root_folder.clone() else { return };
+    if app.search.query.is_empty() {
+        return;
+    }
+
+    let open: HashMap<PathBuf, String> = app.panes.documents.iter()
+        .filter(|(_, d)| d.bytes.is_none())
+        .filter_map(|(&doc, d)| Some((d.path.clone()?, panes::text_of(app, doc).to_string())))
+        .collect();
+    let settings = app.config.excludes.clone();
+    let query = app.search.query.clone();
+    let case_sensitive = app.search.case_sensitive;
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    app.search.cancel = Arc::clone(&cancel);
+    app.search.results = Some(receiver);
+
+    let ctx = ctx.clone();
+    thread::spawn(move || {
+        for path in excludes::workspace_files(&root, &settings) {
+            if cancel.load(Ordering::Relaxed) {
+                return;
+            }
+            let text = match open.get(&path) {
+                Some(text) => text.clone(),
+                None => match read_text(&path) {
+                    Some(text) => text,
+                    None => continue,
+                },
+            };
+            let found = find_in_text(&path, &text, &query, case_sensitive);
+            if found.is_empty() {
+                continue;
+            }
+            if sender.send(found).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+        }
+        drop(sender);
+        ctx.request_repaint();
+    });
+}
+
+pub fn cancel_search(app: &mut CodeEditorApp) {
+    app.search.cancel.store(true, Ordering::Relaxed);
+    app.search.results = None;
+}
+
+// Contents of a text file small enough to search; None for binaries
+fn read_text(path: &Path) -> Option<String> {
+    if fs::metadata(path).ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let text = String::from_utf8(fs::read(path).ok()?).ok()?;
+    (!hex::looks_binary(text.as_bytes())).then_some(text)
+}
+
+fn find_in_text(path: &Path, text: &str, query: &str, case_sensitive: bool) -> Vec<Match> {
+    let fold = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+    let needle: Vec<char> = query.chars().map(fold).collect();
+    let mut matches = Vec::new();
+    if needle.is_empty() {
+        return matches;
+    }
+    for (line, content) in text.lines().enumerate() {
+        let chars: Vec<char> = content.chars().map(fold).collect();
+        let mut column = 0;
+        while column + needle.len() <= chars.len() {
+            if chars[column..column + needle.len()] == needle[..] {
+                matches.push(Match {
+                    path: path.to_path_buf(),
+                    line,
+                    start: column,
+                    end: column + needle.len(),
+                    text: content.to_string(),
+                });
+                column += needle.len();
+            } else {
+                column += 1;
+            }
+        }
+    }
+    matches
+}
+
+// Opens the file and selects the match
+pub fn open_match(app: &mut CodeEditorApp, ctx: &egui::Context, found: &Match) {
+    if app.file_path.as_ref() != Some(&found.path) {
+        file_ops::open_path(app, found.path.clone());
+    }
+    if app.file_path.as_ref() == Some(&found.path) {
+        let line_start = editor::line_start_index(&app.text, found.line);
+        editor::select(app, ctx, line_start + found.end, line_start + found.start);
+        app.scroll_to_cursor = true;
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(list: &[&str]) -> Vec<PathBuf> {
+        list.iter().map(|p| Path::new("/ws").join(p)).collect()
+    }
+
+    #[test]
+    fn quick_open_prefers_file_name_matches() {
+        let files = paths(&["docs/main_notes.md", "src/domain/app.rs", "src/main.rs"]);
+        let found = quick_open_matches(Path::new("/ws"), &files, "main");
+        assert_eq!(found, paths(&["src/main.rs", "docs/main_notes.md", "src/domain/app.rs"]));
+    }
+
+    #[test]
+    fn quick_open_matches_across_folders() {
+        let files = paths(&["src/lsp.rs", "tests/ui/render.rs"]);
+        let found = quick_open_matches(Path::new("/ws"), &files, "tui");
+        assert_eq!(found, paths(&["tests/ui/render.rs"]));
+    }
+
+    #[test]
+    fn quick_open_with_empty_query_lists_files() {
+        let files = paths(&["a.rs", "b.rs"]);
+        assert_eq!(quick_open_matches(Path::new("/ws"), &files, " "), files);
+    }
+
+    #[test]
+    fn finds_every_match_with_char_columns() {
+        let found = find_in_text(Path::new("a.rs"), "é foo foo\r\nbar\nFOO", "foo", true);
+        let spans: Vec<(usize, usize, usize)> = found.iter().map(|m| (m.line, m.start, m.end)).collect();
+        assert_eq!(spans, vec![(0, 2, 5), (0, 6, 9)]);
+        assert_eq!(found[0].text, "é foo foo");
+    }
+
+    #[test]
+    fn case_insensitive_search() {
+        let found = find_in_text(Path::new("a.rs"), "Foo\nfOO\nbar", "foo", false);
+        assert_eq!(found.iter().map(|m| m.line).collect::<Vec<_>>(), vec![0, 1]);
+    }
+
+    #[test]
+    fn matches_do_not_overlap() {
+        let found = find_in_text(Path::new("a.rs"), "aaaa", "aa", true);
+        assert_eq!(found.iter().map(|m| m.start).collect::<Vec<_>>(), vec![0, 2]);
+    }
+}
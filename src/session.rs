@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use eframe::egui;
+use egui::text_edit::TextEditState;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::app::CodeEditorApp;
+use crate::config;
+use crate::editor;
+use crate::file_ops;
+use crate::notifications::{self, Level};
+use crate::panes::{self, SplitDirection};
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Which workspace to reopen on the next launch
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct LastSession {
+    workspace: Option<PathBuf>,
+}
+
+// Layout and open files of one workspace, kept per root folder
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct WorkspaceState {
+    groups: Vec<GroupState>,
+    active_group: usize,
+    split_vertical: bool,
+    expanded: Vec<PathBuf>,
+    show_sidebar: bool,
+    show_terminal: bool,
+    sidebar_width: Option<f32>,
+    terminal_height: Option<f32>,
+    terminal_dir: Option<PathBuf>,
+    terminal_history: Vec<String>,
+}
+
+impl Default for WorkspaceState {
+    fn default() -> Self {
+        Self {
+            groups: Vec::new(),
+            active_group: 0,
+            split_vertical: false,
+            expanded: Vec::new(),
+            show_sidebar: true,
+            show_terminal: true,
+            sidebar_width: None,
+            terminal_height: None,
+            terminal_dir: None,
+            terminal_history: Vec::new(),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GroupState {
+    tabs: Vec<TabState>,
+    active: Option<PathBuf>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TabState {
+    path: PathBuf,
+    cursor: (usize, usize),
+    scroll: [f32; 2],
+}
+
+#[derive(Default)]
+pub struct SessionState {
+    // Cursors live in egui's memory, so saving needs the context
+    ctx: Option<egui::Context>,
+    last_save: Option<Instant>,
+    // The state last written, so an unchanged session is not written again
+    written: String,
+    failed: bool,
+    // Started for specific files, so the last session is left as it was until a folder is opened
+    detached: bool,
+    // Scroll offset of each editor pane, keyed like its cursor by `editor::pane_id`
+    pub scroll: HashMap<egui::Id, egui::Vec2>,
+    // Offsets to apply when a restored pane is first shown
+    pub restore_scroll: HashMap<egui::Id, egui::Vec2>,
+    pub sidebar_width: Option<f32>,
+    pub terminal_height: Option<f32>,
+}
+
+fn last_session_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("session.json"))
+}
+
+// FNV-1a; unlike DefaultHasher its output is fixed, so file names survive toolchain updates
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+// One file per root folder, plus one for files opened without a folder
+fn workspace_path(root: Option<&Path>) -> Option<PathBuf> {
+    let name = match root {
+        Some(root) => format!("{:016x}.json", stable_hash(root.to_string_lossy().as_bytes())),
+        None => "no-folder.json".to_string(),
+    };
+    config::config_dir().map(|dir| dir.join("workspaces").join(name))
+}
+
+// Where earlier versions kept a folder's state, read when there is none under the new name
+fn legacy_workspace_path(root: Option<&Path>) -> Option<PathBuf> {
+    let name = format!("{:016x}.json", panes::hash_text(&root?.to_string_lossy()));
+    config::config_dir().map(|dir| dir.join("workspaces").join(name))
+}
+
+fn read<T: DeserializeOwned>(path: Option<PathBuf>) -> Option<T> {
+    let content = fs::read_to_string(path?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// Reopens the workspace that was open when the app was last closed
+pub fn restore(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    app.session.ctx = Some(ctx.clone());
+    let last: LastSession = read(last_session_path()).unwrap_or_default();
+    if let Some(root) = last.workspace.filter(|root| root.is_dir()) {
+        app.root_folder = Some(root.clone());
+        app.breadcrumb_path = vec![root];
+    }
+    load_workspace(app);
+}
+
+pub fn start_detached(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    app.session.ctx = Some(ctx.clone());
+    app.session.detached = true;
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if app.session.ctx.is_none() {
+        app.session.ctx = Some(ctx.clone());
+    }
+    if app.session.last_save.is_some_and(|t| t.elapsed() < SAVE_INTERVAL) {
+        return;
+    }
+    save(app);
+}
+
+pub fn save(app: &mut CodeEditorApp) {
+    app.session.last_save = Some(Instant::now());
+    if app.session.detached {
+        return;
+    }
+    let state = capture(app);
+    let Ok(content) = serde_json::to_string_pretty(&state) else { return };
+    if content == app.session.written {
+        return;
+    }
+    let last = LastSession { workspace: app.root_folder.clone() };
+    let result = write(workspace_path(app.root_folder.as_deref()), &content)
+        .and_then(|_| write(last_session_path(), &serde_json::to_string_pretty(&last).unwrap_or_default()));
+    match result {
+        Ok(()) => {
+            if let Some(legacy) = legacy_workspace_path(app.root_folder.as_deref()) {
+                let _ = fs::remove_file(legacy);
+            }
+            app.session.written = content;
+            app.session.failed = false;
+        }
+        Err(e) if !app.session.failed => {
+            app.session.failed = true;
+            notifications::notify(app, Level::Error, format!("Could not save the session: {}", e), Vec::new());
+        }
+        Err(_) => {}
+    }
+}
+
+fn write(path: Option<PathBuf>, content: &str) -> std::io::Result<()> {
+    let path = path.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no config directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    file_ops::write_atomic(&path, content, false)
+}
+
+// Untitled buffers are left out; crash recovery keeps their text
+fn capture(app: &CodeEditorApp) -> WorkspaceState {
+    let ctx = app.session.ctx.as_ref();
+    let mut groups = Vec::new();
+    let mut active_group = 0;
+    for (index, group) in app.panes.groups.iter().enumerate() {
+        let mut tabs = Vec::new();
+        for &doc in &group.tabs {
+            let Some(path) = app.panes.documents.get(&doc).and_then(|d| d.path.clone()) else { continue };
+            let id = editor::pane_id(group.id, Some(doc));
+            let cursor = ctx.and_then(|ctx| TextEditState::load(ctx, id))
+                .and_then(|state| state.ccursor_range())
+                .map_or((0, 0), |range| (range.primary.index, range.secondary.index));
+            let scroll = app.session.scroll.get(&id).copied().unwrap_or_default();
+            tabs.push(TabState { path, cursor, scroll: [scroll.x, scroll.y] });
+        }
+        if tabs.is_empty() {
+            continue;
+        }
+        if index <= app.panes.active_group {
+            active_group = groups.len();
+        }
+        let active = group.current().and_then(|doc| app.panes.documents.get(&doc)).and_then(|d| d.path.clone());
+        groups.push(GroupState { tabs, active });
+    }
+
+    let mut expanded: Vec<PathBuf> = app.file_tree_expanded.iter().filter(|(_, &open)| open).map(|(p, _)| p.clone()).collect();
+    expanded.sort();
+    WorkspaceState {
+        groups,
+        active_group,
+        split_vertical: app.panes.direction == SplitDirection::Vertical,
+        expanded,
+        show_sidebar: app.show_sidebar,
+        show_terminal: app.show_terminal,
+        sidebar_width: app.session.sidebar_width,
+        terminal_height: app.session.terminal_height,
+        terminal_dir: app.terminal_dir.clone(),
+        terminal_history: app.terminal_history.clone(),
+    }
+}
+
+// Applies the saved state of the workspace at `app.root_folder`
+fn load_workspace(app: &mut CodeEditorApp) {
+    let root = app.root_folder.as_deref();
+    let state: WorkspaceState = read(workspace_path(root))
+        .or_else(|| read(legacy_workspace_path(root)))
+        .unwrap_or_default();
+    app.show_sidebar = state.show_sidebar;
+    app.show_terminal = state.show_terminal;
+    app.session.sidebar_width = state.sidebar_width;
+    app.session.terminal_height = state.terminal_height;
+    app.terminal_dir = state.terminal_dir.filter(|dir| dir.is_dir());
+    app.terminal_history = state.terminal_history;
+    app.file_tree_expanded = state.expanded.into_iter().map(|path| (path, true)).collect();
+    app.panes.direction = if state.split_vertical { SplitDirection::Vertical } else { SplitDirection::Horizontal };
+
+    for (index, group_state) in state.groups.into_iter().enumerate() {
+        if index < app.panes.groups.len() {
+            app.panes.active_group = index;
+        } else {
+            panes::push_group(app);
+        }
+        let group = app.panes.active_group;
+        for tab in group_state.tabs {
+            file_ops::open_path(app, tab.path.clone());
+            let Some(doc) = app.panes.find(&tab.path) else { continue };
+            let id = editor::pane_id(app.panes.groups[group].id, Some(doc));
+            if let Some(ctx) = &app.session.ctx {
+                editor::set_cursor(ctx, id, tab.cursor.0, tab.cursor.1);
+            }
+            app.session.restore_scroll.insert(id, egui::vec2(tab.scroll[0], tab.scroll[1]));
+        }
+        if let Some(doc) = group_state.active.and_then(|path| app.panes.find(&path)) {
+            panes::show(app, doc);
+        }
+        // Every file of the group is gone
+        if app.panes.groups[group].tabs.is_empty() && app.panes.groups.len() > 1 {
+            panes::close_group(app, group);
+        }
+    }
+    app.panes.active_group = state.active_group.min(app.panes.groups.len() - 1);
+    panes::activate(app);
+}
+
+// Saves the current workspace and restores the one at `root`; tabs with unsaved changes stay open
+pub fn switch_workspace(app: &mut CodeEditorApp, root: PathBuf) {
+    save(app);
+    let clean: Vec<usize> = app.panes.documents.keys().copied().filter(|&doc| !panes::is_dirty(app, doc)).collect();
+    for doc in clean {
+        panes::close_document(app, doc);
+    }
+    app.session.scroll.clear();
+    app.session.detached = false;
+    app.root_folder = Some(root.clone());
+    app.breadcrumb_path = vec![root];
+    load_workspace(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_hash_matches_fnv1a() {
+        assert_eq!(stable_hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(stable_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(stable_hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+}
@@ -0,0 +1,689 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use eframe::egui;
+use serde::Deserialize;
+use crate::app::CodeEditorApp;
+use crate::config;
+use crate::editor;
+use crate::language::Language;
+use crate::panes;
+
+#[derive(Clone, Debug)]
+pub struct Snippet {
+    pub name: String,
+    pub prefix: String,
+    pub body: String,
+    pub description: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TabStop {
+    pub index: u32,
+    pub ranges: Vec<(usize, usize)>,
+    pub choices: Option<Vec<String>>,
+}
+
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<TabStop>,
+}
+
+pub struct SnippetSession {
+    pub stops: Vec<TabStop>,
+    pub current: usize,
+    snapshot: String,
+}
+
+#[derive(Default)]
+pub struct SnippetState {
+    pub session: Option<SnippetSession>,
+    pub picker_open: bool,
+    pub picker_filter: String,
+    cache: Option<(Language, Option<PathBuf>, Vec<Snippet>)>,
+}
+
+// ---------------------------------------------------------------------------
+// Snippet files (VS Code format: { "Name": { "prefix": ..., "body": ..., "description": ... } })
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(s) => vec![s],
+            OneOrMany::Many(v) => v,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SnippetFileEntry {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    #[serde(default)]
+    description: String,
+}
+
+fn load_file(path: &Path) -> Vec<Snippet> {
+    let Some(entries) = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<HashMap<String, SnippetFileEntry>>(&content).ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut snippets = Vec::new();
+    for (name, entry) in entries {
+        let body = entry.body.into_vec().join("\n");
+        for prefix in entry.prefix.into_vec() {
+            snippets.push(Snippet {
+                name: name.clone(),
+                prefix,
+                body: body.clone(),
+                description: entry.description.clone(),
+            });
+        }
+    }
+    snippets.sort_by(|a, b| a.prefix.cmp(&b.prefix));
+    snippets
+}
+
+fn builtin(language: Language) -> Vec<Snippet> {
+    let table: &[(&str, &str, &str)] = match language {
+        Language::Rust => &[
+            ("fn", "Function", "fn ${1:name}(${2}) ${3:-> ${4:()} }{\n\t$0\n}"),
+            ("pfn", "Public function", "pub fn ${1:name}(${2}) ${3:-> ${4:()} }{\n\t$0\n}"),
+            ("struct", "Struct", "struct ${1:Name} {\n\t$0\n}"),
+            ("enum", "Enum", "enum ${1:Name} {\n\t$0\n}"),
+            ("impl", "Impl block", "impl ${1:Type} {\n\t$0\n}"),
+            ("implt", "Trait impl", "impl ${1:Trait} for ${2:Type} {\n\t$0\n}"),
+            ("main", "Main function", "fn main() {\n\t$0\n}"),
+            ("test", "Test function", "#[test]\nfn ${1:name}() {\n\t$0\n}"),
+            ("match", "Match", "match ${1:expr} {\n\t${2:pattern} => ${3:()},\n\t_ => $0,\n}"),
+            ("iflet", "If let", "if let ${1:Some(value)} = ${2:expr} {\n\t$0\n}"),
+            ("for", "For loop", "for ${1:item} in ${2:iter} {\n\t$0\n}"),
+            ("derive", "Derive", "#[derive(${1|Debug,Clone,Default,PartialEq|})]"),
+            ("println", "println!", "println!(\"${1}\"${2});"),
+            ("mod", "Module", "mod ${1:name} {\n\t$0\n}"),
+        ],
+        Language::Python => &[
+            ("def", "Function", "def ${1:name}(${2}):\n\t${0:pass}"),
+            ("class", "Class", "class ${1:Name}:\n\tdef __init__(self${2}):\n\t\t${0:pass}"),
+            ("ifmain", "Main guard", "if __name__ == \"__main__\":\n\t${0:main()}"),
+            ("for", "For loop", "for ${1:item} in ${2:items}:\n\t${0:pass}"),
+            ("while", "While loop", "while ${1:condition}:\n\t${0:pass}"),
+            ("try", "Try/except", "try:\n\t${1:pass}\nexcept ${2:Exception} as ${3:e}:\n\t${0:raise}"),
+            ("with", "With statement", "with ${1:open(${2:path})} as ${3:f}:\n\t${0:pass}"),
+            ("print", "Print", "print(${1})"),
+        ],
+        Language::JavaScript | Language::TypeScript => &[
+            ("function", "Function", "function ${1:name}(${2}) {\n\t$0\n}"),
+            ("af", "Arrow function", "const ${1:name} = (${2}) => {\n\t$0\n};"),
+            ("log", "console.log", "console.log(${1});"),
+            ("for", "For loop", "for (let ${1:i} = 0; $1 < ${2:array}.length; $1++) {\n\t$0\n}"),
+            ("forof", "For...of loop", "for (const ${1:item} of ${2:items}) {\n\t$0\n}"),
+            ("class", "Class", "class ${1:Name} {\n\tconstructor(${2}) {\n\t\t$0\n\t}\n}"),
+            ("import", "Import", "import { $2 } from '${1:module}';"),
+            ("try", "Try/catch", "try {\n\t$1\n} catch (${2:error}) {\n\t$0\n}"),
+            ("var", "Declaration", "${1|const,let,var|} ${2:name} = ${0:value};"),
+        ],
+        _ => &[],
+    };
+
+    table.iter()
+        .map(|(prefix, name, body)| Snippet {
+            name: name.to_string(),
+            prefix: prefix.to_string(),
+            body: body.to_string(),
+            description: String::new(),
+        })
+        .collect()
+}
+
+// Workspace snippets override user snippets, which override the built-in set
+pub fn snippets_for(app: &mut CodeEditorApp) -> Vec<Snippet> {
+    let language = app.file_path.as_deref().map(Language::from_path).unwrap_or(Language::Unknown);
+    if let Some((cached_language, cached_root, snippets)) = &app.snippets.cache {
+        if *cached_language == language && *cached_root == app.root_folder {
+            return snippets.clone();
+        }
+    }
+
+    let file_name = format!("{}.json", language.id());
+    let mut snippets = builtin(language);
+    let mut sources = Vec::new();
+    if let Some(dir) = config::config_dir() {
+        sources.push(dir.join("snippets").join(&file_name));
+    }
+    if let Some(root) = &app.root_folder {
+        sources.push(root.join(".codonn").join("snippets").join(&file_name));
+    }
+    for source in sources {
+        for snippet in load_file(&source) {
+            snippets.retain(|s| s.prefix != snippet.prefix);
+            snippets.push(snippet);
+        }
+    }
+
+    app.snippets.cache = Some((language, app.root_folder.clone(), snippets.clone()));
+    snippets
+}
+
+pub fn reload(app: &mut CodeEditorApp) {
+    app.snippets.cache = None;
+}
+
+// ---------------------------------------------------------------------------
+// Body parsing
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    variables: &'a HashMap<&'static str, String>,
+    indent: &'a str,
+    defaults: &'a HashMap<u32, String>,
+    out: String,
+    len: usize,
+    stops: Vec<TabStop>,
+}
+
+impl<'a> Parser<'a> {
+    fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.out.push('\n');
+                    self.out.push_str(self.indent);
+                    self.len += 1 + self.indent.chars().count();
+                }
+                '\t' => {
+                    self.out.push_str("    ");
+                    self.len += 4;
+                }
+                c => {
+                    self.out.push(c);
+                    self.len += 1;
+                }
+            }
+        }
+    }
+
+    fn add_stop(&mut self, index: u32, range: (usize, usize), choices: Option<Vec<String>>) {
+        match self.stops.iter_mut().find(|s| s.index == index) {
+            Some(stop) => {
+                stop.ranges.push(range);
+                if stop.choices.is_none() {
+                    stop.choices = choices;
+                }
+            }
+            None => self.stops.push(TabStop { index, ranges: vec![range], choices }),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn read_while(&mut self, f: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn parse(&mut self, until_brace: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                '}' if until_brace => return,
+                '\\' if matches!(self.chars.get(self.pos + 1), Some('$' | '}' | '\\')) => {
+                    let escaped = self.chars[self.pos + 1].to_string();
+                    self.push(&escaped);
+                    self.pos += 2;
+                }
+                '$' => {
+                    self.pos += 1;
+                    self.parse_dollar();
+                }
+                c => {
+                    self.push(&c.to_string());
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_dollar(&mut self) {
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let index: u32 = self.read_while(|c| c.is_ascii_digit()).parse().unwrap_or(0);
+                self.bare_stop(index);
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.read_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let value = self.variable(&name);
+                self.push(&value);
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.parse_braced();
+            }
+            _ => self.push("$"),
+        }
+    }
+
+    fn bare_stop(&mut self, index: u32) {
+        let start = self.len;
+        if let Some(default) = self.defaults.get(&index).cloned() {
+            self.push(&default);
+        }
+        self.add_stop(index, (start, self.len), None);
+    }
+
+    fn parse_braced(&mut self) {
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            let index: u32 = self.read_while(|c| c.is_ascii_digit()).parse().unwrap_or(0);
+            match self.peek() {
+                Some(':') => {
+                    self.pos += 1;
+                    let start = self.len;
+                    self.parse(true);
+                    self.add_stop(index, (start, self.len), None);
+                }
+                Some('|') => {
+                    self.pos += 1;
+                    let choices = self.read_choices();
+                    let start = self.len;
+                    if let Some(first) = choices.first().cloned() {
+                        self.push(&first);
+                    }
+                    self.add_stop(index, (start, self.len), Some(choices));
+                }
+                _ => self.bare_stop(index),
+            }
+        } else {
+            let name = self.read_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            let value = self.variable(&name);
+            if self.peek() == Some(':') {
+                self.pos += 1;
+                let (out_len, len, stops) = (self.out.len(), self.len, self.stops.clone());
+                self.parse(true);
+                if !value.is_empty() {
+                    self.out.truncate(out_len);
+                    self.len = len;
+                    self.stops = stops;
+                    self.push(&value);
+                }
+            } else {
+                self.push(&value);
+            }
+        }
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        }
+    }
+
+    fn read_choices(&mut self) -> Vec<String> {
+        let mut choices = vec![String::new()];
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(next) = self.peek() {
+                        choices.last_mut().unwrap().push(next);
+                        self.pos += 1;
+                    }
+                }
+                ',' => choices.push(String::new()),
+                '|' if self.peek() == Some('}') => break,
+                c => choices.last_mut().unwrap().push(c),
+            }
+        }
+        choices
+    }
+
+    fn variable(&self, name: &str) -> String {
+        self.variables.get(name).cloned().unwrap_or_default()
+    }
+}
+
+fn run_parser(
+    body: &str,
+    variables: &HashMap<&'static str, String>,
+    indent: &str,
+    defaults: &HashMap<u32, String>,
+) -> (String, Vec<TabStop>) {
+    let mut parser = Parser {
+        chars: body.chars().collect(),
+        pos: 0,
+        variables,
+        indent,
+        defaults,
+        out: String::new(),
+        len: 0,
+        stops: Vec::new(),
+    };
+    parser.parse(false);
+    (parser.out, parser.stops)
+}
+
+pub fn expand(body: &str, variables: &HashMap<&'static str, String>, indent: &str) -> Expansion {
+    // First pass finds placeholder text so bare mirrors like `$1` can repeat it
+    let (text, stops) = run_parser(body, variables, indent, &HashMap::new());
+    let defaults: HashMap<u32, String> = stops.iter()
+        .filter_map(|stop| {
+            let (start, end) = *stop.ranges.iter().find(|(s, e)| e > s)?;
+            Some((stop.index, text.chars().skip(start).take(end - start).collect()))
+        })
+        .collect();
+    let (text, mut stops) = run_parser(body, variables, indent, &defaults);
+
+    stops.sort_by_key(|stop| if stop.index == 0 { u32::MAX } else { stop.index });
+    if stops.last().map(|stop| stop.index) != Some(0) {
+        let end = text.chars().count();
+        stops.push(TabStop { index: 0, ranges: vec![(end, end)], choices: None });
+    }
+    Expansion { text, stops }
+}
+
+fn variables(app: &CodeEditorApp, selected: &str, line: &str, line_number: usize) -> HashMap<&'static str, String> {
+    let now = chrono::Local::now();
+    let mut vars = HashMap::new();
+    if let Some(path) = &app.file_path {
+        let name = |p: Option<&std::ffi::OsStr>| p.map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        vars.insert("TM_FILENAME", name(path.file_name()));
+        vars.insert("TM_FILENAME_BASE", name(path.file_stem()));
+        vars.insert("TM_DIRECTORY", path.parent().map(|p| p.display().to_string()).unwrap_or_default());
+        vars.insert("TM_FILEPATH", path.display().to_string());
+    }
+    vars.insert("TM_SELECTED_TEXT", selected.to_string());
+    vars.insert("TM_CURRENT_LINE", line.to_string());
+    vars.insert("TM_LINE_NUMBER", (line_number + 1).to_string());
+    vars.insert("CURRENT_YEAR", now.format("%Y").to_string());
+    vars.insert("CURRENT_YEAR_SHORT", now.format("%y").to_string());
+    vars.insert("CURRENT_MONTH", now.format("%m").to_string());
+    vars.insert("CURRENT_MONTH_NAME", now.format("%B").to_string());
+    vars.insert("CURRENT_DATE", now.format("%d").to_string());
+    vars.insert("CURRENT_DAY_NAME", now.format("%A").to_string());
+    vars.insert("CURRENT_HOUR", now.format("%H").to_string());
+    vars.insert("CURRENT_MINUTE", now.format("%M").to_string());
+    vars.insert("CURRENT_SECOND", now.format("%S").to_string());
+    vars
+}
+
+// ---------------------------------------------------------------------------
+// Editor integration
+
+// Replaces the chars in `start..end` with the expanded snippet and starts a tab-stop session
+pub fn insert(app: &mut CodeEditorApp, ctx: &egui::Context, snippet: &Snippet, start: usize, end: usize) {
+    let selected: String = app.text.chars().skip(start).take(end - start).collect();
+    let line_start = editor::line_start_of(&app.text, start);
+    let line: String = app.text.chars().skip(line_start).take_while(|&c| c != '\n').collect();
+    let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+    let line_number = app.text.chars().take(start).filter(|&c| c == '\n').count();
+
+    let vars = variables(app, &selected, &line, line_number);
+    let mut expansion = expand(&snippet.body, &vars, &indent);
+    editor::replace_chars(&mut app.text, start, end, &expansion.text);
+    panes::mark_active_edited(app);
+
+    for stop in &mut expansion.stops {
+        for range in &mut stop.ranges {
+            *range = (range.0 + start, range.1 + start);
+        }
+    }
+    let mut session = SnippetSession { stops: expansion.stops, current: 0, snapshot: String::new() };
+    let (a, b) = session.stops[0].ranges[0];
+    editor::set_cursor(ctx, editor::editor_id(app), b, a);
+    session.snapshot = app.text.clone();
+    app.snippets.session = if session.stops.len() > 1 { Some(session) } else { None };
+}
+
+fn word_before(text: &str, cursor: usize) -> (usize, String) {
+    let chars: Vec<char> = text.chars().take(cursor).collect();
+    let start = chars.iter()
+        .rposition(|c| !(c.is_alphanumeric() || *c == '_'))
+        .map_or(0, |i| i + 1);
+    (start, chars[start..].iter().collect())
+}
+
+// Handles Tab / Shift+Tab / Escape before the text edit sees them
+pub fn handle_keys(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let tab = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab));
+    let shift_tab = ctx.input_mut(|i| i.consume_key(egui::Modifiers::SHIFT, egui::Key::Tab));
+
+    if app.snippets.session.is_some() {
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            app.snippets.session = None;
+        } else if tab {
+            move_to_stop(app, ctx, 1);
+        } else if shift_tab {
+            move_to_stop(app, ctx, -1);
+        }
+        return;
+    }
+
+    if tab {
+        let Some((primary, secondary)) = app.cursor else { return };
+        if primary != secondary {
+            return;
+        }
+        let (start, word) = word_before(&app.text, primary);
+        if word.is_empty() {
+            return;
+        }
+        if let Some(snippet) = snippets_for(app).into_iter().find(|s| s.prefix == word) {
+            insert(app, ctx, &snippet, start, primary);
+        }
+    }
+}
+
+fn move_to_stop(app: &mut CodeEditorApp, ctx: &egui::Context, step: isize) {
+    let editor_id = editor::editor_id(app);
+    let Some(session) = app.snippets.session.as_mut() else { return };
+    let next = session.current as isize + step;
+    if next < 0 {
+        return;
+    }
+    session.current = (next as usize).min(session.stops.len() - 1);
+    let (a, b) = session.stops[session.current].ranges[0];
+    editor::set_cursor(ctx, editor_id, b, a);
+    if session.current == session.stops.len() - 1 {
+        app.snippets.session = None;
+    }
+}
+
+// Called after the text edit: keeps the stop ranges in step with typing and mirrors the current field
+pub fn after_edit(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let editor_id = editor::editor_id(app);
+    let Some(session) = app.snippets.session.as_mut() else { return };
+    if session.snapshot == app.text {
+        return;
+    }
+
+    let (start, old_end, new_end) = changed_region(&session.snapshot, &app.text);
+    if !session.apply_edit(start, old_end, new_end) {
+        app.snippets.session = None;
+        return;
+    }
+    let mirrored = session.sync_mirrors(&mut app.text);
+    if let (Some(shift), Some((primary, secondary))) = (mirrored, app.cursor) {
+        let moved = |c: usize| (c as isize + shift) as usize;
+        editor::set_cursor(ctx, editor_id, moved(primary), moved(secondary));
+    }
+    session.snapshot = app.text.clone();
+    if mirrored.is_some() {
+        panes::mark_active_edited(app);
+    }
+}
+
+pub fn choose(app: &mut CodeEditorApp, ctx: &egui::Context, choice: &str) {
+    let Some(session) = app.snippets.session.as_mut() else { return };
+    let (a, b) = session.stops[session.current].ranges[0];
+    editor::replace_chars(&mut app.text, a, b, choice);
+    session.replace_range(a, b, choice.chars().count());
+    session.sync_mirrors(&mut app.text);
+    session.snapshot = app.text.clone();
+    let (a, b) = session.stops[session.current].ranges[0];
+    editor::set_cursor(ctx, editor::editor_id(app), b, a);
+    panes::mark_active_edited(app);
+}
+
+fn changed_region(old: &str, new: &str) -> (usize, usize, usize) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let suffix = old.iter().rev().zip(new.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, old.len() - suffix, new.len() - suffix)
+}
+
+impl SnippetSession {
+    pub fn current_choices(&self) -> Option<&Vec<String>> {
+        self.stops.get(self.current)?.choices.as_ref()
+    }
+
+    fn apply_edit(&mut self, start: usize, old_end: usize, new_end: usize) -> bool {
+        let (ps, pe) = self.stops[self.current].ranges[0];
+        if start < ps || old_end > pe {
+            return false;
+        }
+        let delta = new_end as isize - old_end as isize;
+        let shift = |v: usize| (v as isize + delta) as usize;
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            for (j, range) in stop.ranges.iter_mut().enumerate() {
+                let is_primary = i == self.current && j == 0;
+                // Placeholders nested around the current field grow with it
+                let encloses = range.0 <= ps && range.1 >= pe && range.1 > ps;
+                if is_primary || encloses {
+                    range.1 = shift(range.1);
+                } else if range.0 >= old_end {
+                    *range = (shift(range.0), shift(range.1));
+                }
+            }
+        }
+        true
+    }
+
+    fn replace_range(&mut self, a: usize, b: usize, new_len: usize) {
+        let delta = new_len as isize - (b - a) as isize;
+        let shift = |v: usize| (v as isize + delta) as usize;
+        for stop in &mut self.stops {
+            for range in &mut stop.ranges {
+                if *range == (a, b) {
+                    range.1 = a + new_len;
+                } else if range.0 >= b {
+                    *range = (shift(range.0), shift(range.1));
+                } else if range.0 <= a && range.1 >= b {
+                    range.1 = shift(range.1);
+                }
+            }
+        }
+    }
+
+    // Copies the current field into its mirrors; returns how far the cursor moved
+    fn sync_mirrors(&mut self, text: &mut String) -> Option<isize> {
+        let (ps, pe) = self.stops[self.current].ranges[0];
+        let content: String = text.chars().skip(ps).take(pe - ps).collect();
+        let mut cursor_shift = 0;
+        let mut changed = false;
+
+        for j in 1..self.stops[self.current].ranges.len() {
+            let ps = self.stops[self.current].ranges[0].0;
+            let (a, b) = self.stops[self.current].ranges[j];
+            let existing: String = text.chars().skip(a).take(b - a).collect();
+            if existing == content {
+                continue;
+            }
+            editor::replace_chars(text, a, b, &content);
+            self.replace_range(a, b, content.chars().count());
+            if b <= ps {
+                cursor_shift += content.chars().count() as isize - (b - a) as isize;
+            }
+            changed = true;
+        }
+        changed.then_some(cursor_shift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_plain(body: &str) -> Expansion {
+        expand(body, &HashMap::new(), "")
+    }
+
+    fn ranges(expansion: &Expansion) -> Vec<(u32, Vec<(usize, usize)>)> {
+        expansion.stops.iter().map(|s| (s.index, s.ranges.clone())).collect()
+    }
+
+    #[test]
+    fn nested_placeholders() {
+        let expansion = expand_plain("${1:foo${2:bar}} baz");
+        assert_eq!(expansion.text, "foobar baz");
+        assert_eq!(ranges(&expansion), vec![(1, vec![(0, 6)]), (2, vec![(3, 6)]), (0, vec![(10, 10)])]);
+    }
+
+    #[test]
+    fn deeply_nested_placeholders() {
+        let expansion = expand_plain("${1:a${2:b${3:c}}}$0");
+        assert_eq!(expansion.text, "abc");
+        assert_eq!(ranges(&expansion), vec![(1, vec![(0, 3)]), (2, vec![(1, 3)]), (3, vec![(2, 3)]), (0, vec![(3, 3)])]);
+    }
+
+    #[test]
+    fn mirrors_repeat_placeholder_text() {
+        let expansion = expand_plain("let ${1:name} = $1;");
+        assert_eq!(expansion.text, "let name = name;");
+        assert_eq!(expansion.stops[0].ranges, vec![(4, 8), (11, 15)]);
+    }
+
+    #[test]
+    fn final_stop_is_last() {
+        let expansion = expand_plain("$0 ${2:b} ${1:a}");
+        let order: Vec<u32> = expansion.stops.iter().map(|s| s.index).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+        assert_eq!(expansion.stops[2].ranges, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn choices_use_the_first_option() {
+        let expansion = expand_plain("${1|one,two\\,three|}");
+        assert_eq!(expansion.text, "one");
+        assert_eq!(expansion.stops[0].choices, Some(vec!["one".to_string(), "two,three".to_string()]));
+    }
+
+    #[test]
+    fn variables_and_defaults() {
+        let mut variables = HashMap::new();
+        variables.insert("TM_FILENAME", "main.rs".to_string());
+        variables.insert("TM_SELECTED_TEXT", String::new());
+        let expansion = expand("$TM_FILENAME ${TM_SELECTED_TEXT:${1:none}}", &variables, "");
+        assert_eq!(expansion.text, "main.rs none");
+        assert_eq!(expansion.stops[0].ranges, vec![(8, 12)]);
+    }
+
+    #[test]
+    fn escapes_are_literal() {
+        let expansion = expand_plain("\\$1 \\} \\\\");
+        assert_eq!(expansion.text, "$1 } \\");
+        assert_eq!(expansion.stops.len(), 1);
+    }
+
+    #[test]
+    fn new_lines_keep_indentation() {
+        let expansion = expand("if {\n\t$0\n}", &HashMap::new(), "  ");
+        assert_eq!(expansion.text, "if {\n      \n  }");
+        assert_eq!(expansion.stops[0].ranges, vec![(11, 11)]);
+    }
+}
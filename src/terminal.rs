@@ -3,10 +3,12 @@ use std::sync::Arc;
 // use std::io::BufRead;
 use std::thread;
 use crate::app::CodeEditorApp;
-use std::path::Path;
+use crate::notifications::{self, Action, Level};
+use std::path::{Path, PathBuf};
 
 pub fn open_powershell(app: &mut CodeEditorApp) {
     let output_clone = Arc::clone(&app.terminal_output);
+    let notifier = notifications::notifier(app);
     
     thread::spawn(move || {
         match Command::new("powershell")
@@ -21,16 +23,14 @@ pub fn open_powershell(app: &mut CodeEditorApp) {
                 output.push_str("Type 'exit' to close\n");
                 output.push_str("> ");
             }
-            Err(e) => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str(&format!("Error: {}\n> ", e));
-            }
+            Err(e) => notifier.send(Level::Error, format!("Could not start PowerShell: {}", e), Vec::new()),
         }
     });
 }
 
 pub fn open_cmd(app: &mut CodeEditorApp) {
     let output_clone = Arc::clone(&app.terminal_output);
+    let notifier = notifications::notifier(app);
     
     thread::spawn(move || {
         match Command::new("cmd")
@@ -45,16 +45,14 @@ pub fn open_cmd(app: &mut CodeEditorApp) {
                 output.push_str("Type 'exit' to close\n");
                 output.push_str("> ");
             }
-            Err(e) => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str(&format!("Error: {}\n> ", e));
-            }
+            Err(e) => notifier.send(Level::Error, format!("Could not start CMD: {}", e), Vec::new()),
         }
     });
 }
 
 pub fn run_file(app: &mut CodeEditorApp, path: &Path) {
     let output_clone = Arc::clone(&app.terminal_output);
+    let notifier = notifications::notifier(app);
     let path_buf = path.to_path_buf();
     
     thread::spawn(move || {
@@ -78,10 +76,8 @@ pub fn run_file(app: &mut CodeEditorApp, path: &Path) {
                 Command::new("node").arg(&path_buf).output()
             }
             _ => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str("❌ Unsupported file type\n");
-                output.push_str("> ");
-                return;
+                let message = format!("Cannot run {}: unsupported file type", path_buf.display());
+                return notifier.send(Level::Warning, message, Vec::new());
             }
         };
         
@@ -98,10 +94,15 @@ pub fn run_file(app: &mut CodeEditorApp, path: &Path) {
                     output.push_str(&stderr);
                 }
                 output.push_str("\n> ");
+                drop(output);
+                if !output_result.status.success() {
+                    let message = format!("Running {} failed ({})", path_buf.display(), output_result.status);
+                    notifier.send(Level::Error, message, vec![Action::ShowOutput, Action::RetryRun(path_buf)]);
+                }
             }
             Err(e) => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str(&format!("❌ Error: {}\n> ", e));
+                let message = format!("Could not run {}: {}", path_buf.display(), e);
+                notifier.send(Level::Error, message, vec![Action::RetryRun(path_buf)]);
             }
         }
     });
@@ -109,6 +110,7 @@ pub fn run_file(app: &mut CodeEditorApp, path: &Path) {
 
 pub fn build_file(app: &mut CodeEditorApp, path: &Path) {
     let output_clone = Arc::clone(&app.terminal_output);
+    let notifier = notifications::notifier(app);
     let path_buf = path.to_path_buf();
     
     thread::spawn(move || {
@@ -127,33 +129,74 @@ pub fn build_file(app: &mut CodeEditorApp, path: &Path) {
                     output.push_str(&String::from_utf8_lossy(&result.stderr));
                 }
                 output.push_str("\n> ");
+                drop(output);
+                if !result.status.success() {
+                    let message = format!("Build failed ({})", result.status);
+                    notifier.send(Level::Error, message, vec![Action::ShowOutput, Action::RetryBuild(path_buf)]);
+                }
             }
             Err(e) => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str(&format!("Build Error: {}\n> ", e));
+                let message = format!("Could not start the build: {}", e);
+                notifier.send(Level::Error, message, vec![Action::RetryBuild(path_buf)]);
             }
         }
     });
 }
 
+// Runs later commands from `dir` and brings the terminal into view
+pub fn set_directory(app: &mut CodeEditorApp, dir: PathBuf) {
+    app.terminal_output.lock().unwrap().push_str(&format!("📂 {}\n> ", dir.display()));
+    app.terminal_dir = Some(dir);
+    app.show_terminal = true;
+    app.bottom_panel = crate::app::BottomPanel::Terminal;
+}
+
+const HISTORY_LIMIT: usize = 200;
+
+// Steps back (or forward) through earlier commands into the input line
+pub fn recall(app: &mut CodeEditorApp, older: bool) {
+    let count = app.terminal_history.len();
+    let index = match (app.terminal_history_index, older) {
+        (None, true) if count > 0 => Some(count - 1),
+        (Some(i), true) => Some(i.saturating_sub(1)),
+        (Some(i), false) if i + 1 < count => Some(i + 1),
+        _ => None,
+    };
+    app.terminal_history_index = index;
+    app.terminal_input = index.map(|i| app.terminal_history[i].clone()).unwrap_or_default();
+}
+
 pub fn execute_command(app: &mut CodeEditorApp, command: &str) {
+    if app.terminal_history.last().map(String::as_str) != Some(command) {
+        app.terminal_history.push(command.to_string());
+    }
+    if app.terminal_history.len() > HISTORY_LIMIT {
+        app.terminal_history.remove(0);
+    }
+    app.terminal_history_index = None;
     let output_clone = Arc::clone(&app.terminal_output);
+    let notifier = notifications::notifier(app);
     let command = command.to_string();
+    let dir = app.terminal_dir.clone();
     
     thread::spawn(move || {
         let mut output = output_clone.lock().unwrap();
         output.push_str(&format!("$ {}\n", command));
         drop(output);
         
-        let result = if cfg!(target_os = "windows") {
-            Command::new("cmd")
-                .args(&["/C", &command])
-                .output()
+        let mut shell = if cfg!(target_os = "windows") {
+            let mut shell = Command::new("cmd");
+            shell.args(&["/C", &command]);
+            shell
         } else {
-            Command::new("sh")
-                .args(&["-c", &command])
-                .output()
+            let mut shell = Command::new("sh");
+            shell.args(&["-c", &command]);
+            shell
         };
+        if let Some(dir) = &dir {
+            shell.current_dir(dir);
+        }
+        let result = shell.output();
         
         match result {
             Ok(output_result) => {
@@ -167,10 +210,15 @@ pub fn execute_command(app: &mut CodeEditorApp, command: &str) {
                     output.push_str(&stderr);
                 }
                 output.push_str("\n> ");
+                drop(output);
+                if !output_result.status.success() {
+                    let message = format!("`{}` failed ({})", command, output_result.status);
+                    notifier.send(Level::Warning, message, vec![Action::ShowOutput, Action::RetryCommand(command)]);
+                }
             }
             Err(e) => {
-                let mut output = output_clone.lock().unwrap();
-                output.push_str(&format!("Error: {}\n> ", e));
+                let message = format!("Could not run `{}`: {}", command, e);
+                notifier.send(Level::Error, message, vec![Action::RetryCommand(command)]);
             }
         }
     });
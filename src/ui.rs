@@ -1,9 +1,24 @@
 use eframe::egui;
-use crate::app::CodeEditorApp;
+use crate::app::{BottomPanel, CodeEditorApp};
+use crate::autosave::{self, AutoSave};
 use crate::file_ops;
 use crate::file_tree;
+use crate::hex;
 use crate::terminal;
 use crate::editor;
+use crate::completion;
+use crate::diff::{self, RowKind};
+use crate::lsp;
+use crate::macros;
+use crate::notifications::{self, Action, Level, Notification};
+use crate::outline::{self, Symbol};
+use crate::panes::{self, SplitDirection};
+use crate::recent::{self, RecentEntry, RecentKind};
+use crate::recovery;
+use crate::search;
+use crate::session;
+use crate::snippets;
+use crate::watcher;
 
 // Custom color scheme (Dracula-inspired)
 const COLOR_BG: egui::Color32 = egui::Color32::from_rgb(40, 42, 54);
@@ -16,12 +31,47 @@ const COLOR_MUTED: egui::Color32 = egui::Color32::from_rgb(98, 114, 164);
 
 pub fn render(app: &mut CodeEditorApp, ctx: &egui::Context) {
     set_theme(ctx);
+    handle_shortcuts(app, ctx);
+    watcher::sync(app, ctx);
+    lsp::sync(app, ctx);
+    recovery::sync(app, ctx);
+    session::sync(app, ctx);
+    autosave::sync(app, ctx);
+    notifications::sync(app, ctx);
+    search::sync(app, ctx);
     
     render_menu_bar(app, ctx);
     render_status_bar(app, ctx);
     render_sidebar(app, ctx);
     render_terminal(app, ctx);
     render_editor(app, ctx);
+    render_snippet_picker(app, ctx);
+    render_snippet_choices(app, ctx);
+    render_completion_popup(app, ctx);
+    render_signature_help(app, ctx);
+    render_code_actions(app, ctx);
+    render_rename_dialog(app, ctx);
+    render_quick_open(app, ctx);
+    render_diff_view(app, ctx);
+    render_external_changes(app, ctx);
+    render_close_confirmation(app, ctx);
+    render_toasts(app, ctx);
+    handle_dropped_files(app, ctx);
+}
+
+fn handle_dropped_files(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let dropped: Vec<std::path::PathBuf> = ctx.input(|i| i.raw.dropped_files.iter().filter_map(|f| f.path.clone()).collect());
+    if !dropped.is_empty() {
+        file_ops::open_dropped(app, dropped);
+    }
+
+    let hovering = ctx.input(|i| !i.raw.hovered_files.is_empty());
+    if hovering {
+        let screen = ctx.screen_rect();
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("file_drop")));
+        painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(160));
+        painter.text(screen.center(), egui::Align2::CENTER_CENTER, "📂 Drop files to open them, or a folder to explore it", egui::FontId::proportional(20.0), COLOR_ACCENT);
+    }
 }
 
 fn set_theme(ctx: &egui::Context) {
@@ -32,6 +82,58 @@ fn set_theme(ctx: &egui::Context) {
     ctx.set_visuals(visuals);
 }
 
+fn handle_shortcuts(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    macros::handle_shortcuts(app, ctx);
+
+    let command_shift = egui::Modifiers::COMMAND | egui::Modifiers::SHIFT;
+    if ctx.input_mut(|i| i.consume_key(command_shift, egui::Key::R)) {
+        macros::toggle_recording(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(command_shift, egui::Key::P)) && app.macros.recording.is_none() {
+        let steps = app.macros.last.clone();
+        macros::play(app, &steps, app.macros.repeat_count);
+    }
+    if ctx.input_mut(|i| i.consume_key(command_shift, egui::Key::J)) {
+        app.snippets.picker_open = true;
+    }
+    if ctx.input_mut(|i| i.consume_key(command_shift, egui::Key::F)) {
+        search::open_find_in_files(app);
+    }
+    // After Ctrl+Shift+P, which this would also match
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::P)) {
+        search::open_quick_open(app);
+    }
+
+    // Files
+    let command_alt = egui::Modifiers::COMMAND | egui::Modifiers::ALT;
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::N)) {
+        file_ops::new_file(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(command_alt, egui::Key::S)) {
+        file_ops::save_all(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(command_shift, egui::Key::S)) {
+        file_ops::save_file_as(app);
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) {
+        file_ops::save_file(app);
+    }
+
+    // Editor groups
+    if ctx.input_mut(|i| i.consume_key(command_alt, egui::Key::ArrowRight)) {
+        panes::move_active_tab(app, true);
+    }
+    if ctx.input_mut(|i| i.consume_key(command_alt, egui::Key::ArrowLeft)) {
+        panes::move_active_tab(app, false);
+    }
+    let group_keys = [egui::Key::Num1, egui::Key::Num2, egui::Key::Num3, egui::Key::Num4];
+    for (group, key) in group_keys.into_iter().enumerate() {
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, key)) {
+            panes::focus_group(app, ctx, group);
+        }
+    }
+}
+
 fn render_menu_bar(app: &mut CodeEditorApp, ctx: &egui::Context) {
     egui::TopBottomPanel::top("menu_bar")
         .frame(egui::Frame::none().fill(COLOR_DARK_BG).stroke(egui::Stroke::new(1.0, COLOR_MUTED)))
@@ -42,25 +144,102 @@ fn render_menu_bar(app: &mut CodeEditorApp, ctx: &egui::Context) {
                 egui::menu::bar(ui, |ui| {
                     ui.menu_button("📁 File", |ui| {
                         if ui.button("📂 Open Folder").clicked() {
-                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                                app.root_folder = Some(path.clone());
-                                app.breadcrumb_path = vec![path];
-                                ui.close_menu();
-                            }
+                            file_ops::pick_folder(app);
+                            ui.close_menu();
+                        }
+                        if ui.button("📝 New File").clicked() {
+                            file_ops::new_file(app);
+                            ui.close_menu();
                         }
                         if ui.button("📄 Open File").clicked() {
                             file_ops::open_file(app);
                             ui.close_menu();
                         }
+                        ui.menu_button("🕘 Open Recent", |ui| {
+                            if render_recent(app, ui, usize::MAX) {
+                                ui.close_menu();
+                            }
+                            ui.separator();
+                            if ui.button("🧹 Clear Recently Opened").on_hover_text("Pinned entries are kept").clicked() {
+                                recent::clear(app);
+                                ui.close_menu();
+                            }
+                        });
+                        if ui.add_enabled(app.root_folder.is_some(), egui::Button::new("🔍 Go to File… (Ctrl+P)")).clicked() {
+                            search::open_quick_open(app);
+                            ui.close_menu();
+                        }
                         if ui.button("💾 Save").clicked() {
                             file_ops::save_file(app);
                             ui.close_menu();
                         }
+                        if ui.button("💾 Save As…").clicked() {
+                            file_ops::save_file_as(app);
+                            ui.close_menu();
+                        }
+                        if ui.button("💾 Save All").clicked() {
+                            file_ops::save_all(app);
+                            ui.close_menu();
+                        }
+                        if ui.checkbox(&mut app.config.save.keep_backup, "Keep .bak copy on save").changed() {
+                            save_config(app);
+                        }
+                        ui.menu_button("⏱ Auto Save", |ui| render_auto_save_settings(app, ui));
                         ui.separator();
                         if ui.button("❌ Exit").clicked() {
+                            recovery::exit(app);
+                            session::save(app);
                             std::process::exit(0);
                         }
                     });
+                    ui.menu_button("✏️ Edit", |ui| {
+                        if ui.button("💬 Toggle Line Comment").clicked() {
+                            editor::run_command(app, ui.ctx(), editor::EditorCommand::ToggleLineComment);
+                            ui.close_menu();
+                        }
+                        if ui.button("💬 Toggle Block Comment").clicked() {
+                            editor::run_command(app, ui.ctx(), editor::EditorCommand::ToggleBlockComment);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("✂ Insert Snippet…").clicked() {
+                            app.snippets.picker_open = true;
+                            ui.close_menu();
+                        }
+                        if ui.button("🔄 Reload Snippets").clicked() {
+                            snippets::reload(app);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.button("🔎 Find in Files (Ctrl+Shift+F)").clicked() {
+                            search::open_find_in_files(app);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        ui.menu_button("💡 Word Completion", |ui| {
+                            render_completion_settings(app, ui);
+                        });
+                        ui.separator();
+                        if ui.button("🎯 Go to Definition (F12)").clicked() {
+                            lsp::goto_definition(app);
+                            ui.close_menu();
+                        }
+                        if ui.button("🔗 Find References (Shift+F12)").clicked() {
+                            lsp::find_references(app);
+                            ui.close_menu();
+                        }
+                        if ui.button("✏ Rename Symbol (F2)").clicked() {
+                            lsp::start_rename(app);
+                            ui.close_menu();
+                        }
+                        if ui.button("💡 Code Actions (Alt+Enter)").clicked() {
+                            lsp::request_code_actions(app);
+                            ui.close_menu();
+                        }
+                        if ui.checkbox(&mut app.config.lsp.enabled, "Language servers").changed() {
+                            save_config(app);
+                        }
+                    });
                     ui.menu_button("👁️ View", |ui| {
                         if ui.button(if app.show_sidebar { "👁️ Hide Sidebar" } else { "👁️ Show Sidebar" }).clicked() {
                             app.show_sidebar = !app.show_sidebar;
@@ -70,13 +249,1052 @@ fn render_menu_bar(app: &mut CodeEditorApp, ctx: &egui::Context) {
                             app.show_terminal = !app.show_terminal;
                             ui.close_menu();
                         }
+                        ui.separator();
+                        if ui.button("◫ Split Right").clicked() {
+                            panes::split(app, SplitDirection::Horizontal);
+                            ui.close_menu();
+                        }
+                        if ui.button("⬓ Split Down").clicked() {
+                            panes::split(app, SplitDirection::Vertical);
+                            ui.close_menu();
+                        }
+                        if ui.button("➡ Move Tab to Next Group").clicked() {
+                            panes::move_active_tab(app, true);
+                            ui.close_menu();
+                        }
+                        if ui.button("⬅ Move Tab to Previous Group").clicked() {
+                            panes::move_active_tab(app, false);
+                            ui.close_menu();
+                        }
+                        if ui.add_enabled(app.panes.groups.len() > 1, egui::Button::new("✕ Close Group")).clicked() {
+                            panes::request_close_group(app, app.panes.active_group);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        if ui.add_enabled(app.file_path.is_some(), egui::Button::new("⇄ Compare with Saved")).clicked() {
+                            diff::compare_with_saved(app);
+                            ui.close_menu();
+                        }
+                        if ui.add_enabled(app.panes.active_doc.is_some(), egui::Button::new("⇄ Compare with Clipboard")).clicked() {
+                            diff::compare_with_clipboard(app);
+                            ui.close_menu();
+                        }
+                        ui.separator();
+                        ui.menu_button("🙈 Explorer Excludes", |ui| render_exclude_settings(app, ui));
+                        if ui.add_enabled(app.file_path.is_some() && app.root_folder.is_some(), egui::Button::new("🎯 Reveal Active File")).clicked() {
+                            if let Some(path) = app.file_path.clone() {
+                                app.show_sidebar = true;
+                                file_tree::reveal(app, &path);
+                            }
+                            ui.close_menu();
+                        }
+                    });
+                    ui.menu_button("⏺ Macros", |ui| {
+                        render_macro_menu(app, ui);
                     });
                 });
             });
         });
 }
 
-fn render_status_bar(app: &CodeEditorApp, ctx: &egui::Context) {
+fn render_macro_menu(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let recording = app.macros.recording.is_some();
+    if ui.button(if recording { "⏹ Stop Recording" } else { "⏺ Start Recording" }).clicked() {
+        macros::toggle_recording(app);
+        ui.close_menu();
+    }
+
+    ui.add_enabled_ui(!recording && !app.macros.last.is_empty(), |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("▶ Play Last").clicked() {
+                let steps = app.macros.last.clone();
+                macros::play(app, &steps, app.macros.repeat_count);
+                ui.close_menu();
+            }
+            ui.add(egui::DragValue::new(&mut app.macros.repeat_count).clamp_range(1..=1000).suffix("×"));
+        });
+        if ui.button("↕ Play on Each Selected Line").clicked() {
+            let steps = app.macros.last.clone();
+            macros::play_on_lines(app, &steps);
+            ui.close_menu();
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut app.macros.save_name).hint_text("Macro name").desired_width(120.0));
+            if ui.button("💾 Save").clicked() {
+                macros::save_last(app);
+            }
+        });
+    });
+
+    if app.config.macros.is_empty() {
+        return;
+    }
+    ui.separator();
+    ui.label(egui::RichText::new("Saved Macros").color(COLOR_MUTED));
+
+    let mut play_index = None;
+    let mut bind_index = None;
+    let mut delete_index = None;
+    for (i, m) in app.config.macros.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui.button(format!("▶ {}", m.name)).clicked() {
+                play_index = Some(i);
+            }
+            let binding_text = if app.macros.binding_capture == Some(i) {
+                "Press a key…".to_string()
+            } else {
+                m.binding.map(|b| b.label(ui.ctx())).unwrap_or_else(|| "⌨ Bind".to_string())
+            };
+            if ui.button(egui::RichText::new(binding_text).color(COLOR_ACCENT)).clicked() {
+                bind_index = Some(i);
+            }
+            if ui.button(egui::RichText::new("✕").color(COLOR_ERROR)).clicked() {
+                delete_index = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = play_index {
+        let steps = app.config.macros[i].steps.clone();
+        macros::play(app, &steps, app.macros.repeat_count);
+        ui.close_menu();
+    }
+    if let Some(i) = bind_index {
+        app.macros.binding_capture = Some(i);
+    }
+    if let Some(i) = delete_index {
+        macros::delete(app, i);
+    }
+}
+
+fn render_snippet_picker(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if !app.snippets.picker_open {
+        return;
+    }
+
+    let snippets = snippets::snippets_for(app);
+    let filter = app.snippets.picker_filter.to_lowercase();
+    let mut open = true;
+    let mut chosen = None;
+
+    egui::Window::new("✂ Insert Snippet")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut app.snippets.picker_filter).hint_text("Filter snippets"));
+            response.request_focus();
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                let matches = snippets.iter().filter(|s| {
+                    s.prefix.to_lowercase().contains(&filter) || s.name.to_lowercase().contains(&filter)
+                });
+                for snippet in matches {
+                    let label = ui.button(
+                        egui::RichText::new(format!("{}  —  {}", snippet.prefix, snippet.name)).color(COLOR_TEXT)
+                    );
+                    let label = if snippet.description.is_empty() { label } else { label.on_hover_text(&snippet.description) };
+                    if label.clicked() {
+                        chosen = Some(snippet.clone());
+                    }
+                }
+                if snippets.is_empty() {
+                    ui.colored_label(COLOR_MUTED, "No snippets for this file type");
+                }
+            });
+        });
+
+    if let Some(snippet) = chosen {
+        let (a, b) = app.cursor.unwrap_or_else(|| {
+            let end = app.text.chars().count();
+            (end, end)
+        });
+        snippets::insert(app, ctx, &snippet, a.min(b), a.max(b));
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+        open = false;
+    }
+    if !open {
+        app.snippets.picker_open = false;
+        app.snippets.picker_filter.clear();
+    }
+}
+
+fn render_snippet_choices(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some(choices) = app.snippets.session.as_ref().and_then(|s| s.current_choices()).cloned() else { return };
+    let Some(caret) = app.caret_rect else { return };
+
+    let mut chosen = None;
+    egui::Area::new("snippet_choices")
+        .fixed_pos(caret.left_bottom())
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for choice in &choices {
+                    if ui.selectable_label(false, egui::RichText::new(choice).monospace()).clicked() {
+                        chosen = Some(choice.clone());
+                    }
+                }
+            });
+        });
+
+    if let Some(choice) = chosen {
+        snippets::choose(app, ctx, &choice);
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+    }
+}
+
+enum RecentAction {
+    Open(RecentKind, std::path::PathBuf),
+    Pin(RecentKind, std::path::PathBuf),
+    Remove(RecentKind, std::path::PathBuf),
+}
+
+// Recent folders and files, at most `limit` of each; returns true once one was opened
+fn render_recent(app: &mut CodeEditorApp, ui: &mut egui::Ui, limit: usize) -> bool {
+    if app.recent.folders.is_empty() && app.recent.files.is_empty() {
+        ui.colored_label(COLOR_MUTED, "Nothing opened yet");
+        return false;
+    }
+    let mut action = None;
+    for (kind, title) in [(RecentKind::Folder, "Folders"), (RecentKind::File, "Files")] {
+        let entries = match kind {
+            RecentKind::Folder => &app.recent.folders,
+            RecentKind::File => &app.recent.files,
+        };
+        if entries.is_empty() {
+            continue;
+        }
+        ui.label(egui::RichText::new(title).color(COLOR_MUTED).small());
+        for entry in entries.iter().take(limit) {
+            if let Some(a) = render_recent_entry(ui, kind, entry) {
+                action = Some(a);
+            }
+        }
+    }
+
+    match action {
+        Some(RecentAction::Open(kind, path)) => {
+            if !path.exists() {
+                notifications::notify(app, Level::Warning, format!("{} no longer exists", path.display()), Vec::new());
+                recent::remove(app, kind, &path);
+                return false;
+            }
+            match kind {
+                RecentKind::Folder => file_ops::open_folder(app, path),
+                RecentKind::File => file_ops::open_path(app, path),
+            }
+            return true;
+        }
+        Some(RecentAction::Pin(kind, path)) => recent::toggle_pin(app, kind, &path),
+        Some(RecentAction::Remove(kind, path)) => recent::remove(app, kind, &path),
+        None => {}
+    }
+    false
+}
+
+// Entries whose file or folder is gone are greyed out
+fn render_recent_entry(ui: &mut egui::Ui, kind: RecentKind, entry: &RecentEntry) -> Option<RecentAction> {
+    let mut action = None;
+    let exists = entry.exists;
+    ui.horizontal(|ui| {
+        let (pin_color, pin_hint) = if entry.pinned { (COLOR_ACCENT, "Unpin") } else { (COLOR_MUTED, "Pin") };
+        if ui.small_button(egui::RichText::new("📌").color(pin_color)).on_hover_text(pin_hint).clicked() {
+            action = Some(RecentAction::Pin(kind, entry.path.clone()));
+        }
+        let icon = match kind {
+            RecentKind::Folder => "📁",
+            RecentKind::File => "📄",
+        };
+        let name = entry.path.file_name().map_or_else(|| entry.path.display().to_string(), |n| n.to_string_lossy().to_string());
+        let (color, hover) = if exists {
+            (COLOR_TEXT, entry.path.display().to_string())
+        } else {
+            (COLOR_MUTED, format!("{} (no longer exists)", entry.path.display()))
+        };
+        let button = egui::Button::new(egui::RichText::new(format!("{} {}", icon, name)).color(color)).frame(false);
+        if ui.add(button).on_hover_text(hover).clicked() {
+            action = Some(RecentAction::Open(kind, entry.path.clone()));
+        }
+        if let Some(parent) = entry.path.parent() {
+            ui.label(egui::RichText::new(parent.display().to_string()).color(COLOR_MUTED).small());
+        }
+        if ui.small_button(egui::RichText::new("✕").color(COLOR_MUTED)).on_hover_text("Remove from list").clicked() {
+            action = Some(RecentAction::Remove(kind, entry.path.clone()));
+        }
+    });
+    action
+}
+
+// Shown in place of the editor while no document is open
+fn render_welcome(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    const SHORTCUTS: [(&str, &str); 10] = [
+        ("Ctrl+N", "New file"),
+        ("Ctrl+S", "Save"),
+        ("Ctrl+Shift+S", "Save as"),
+        ("Ctrl+Alt+S", "Save all"),
+        ("Ctrl+/", "Toggle line comment"),
+        ("Ctrl+Space", "Word completion"),
+        ("Ctrl+Shift+J", "Insert snippet"),
+        ("Ctrl+Shift+R", "Record macro"),
+        ("F12", "Go to definition"),
+        ("Ctrl+Alt+→", "Move tab to the next group"),
+    ];
+
+    egui::ScrollArea::vertical().id_source("welcome").auto_shrink([false; 2]).show(ui, |ui| {
+        ui.add_space(40.0);
+        ui.horizontal(|ui| {
+            ui.add_space(60.0);
+            ui.vertical(|ui| {
+                ui.label(egui::RichText::new("🧬 Codonn").color(COLOR_ACCENT).size(32.0));
+                ui.label(egui::RichText::new("Open a folder or a file to get started").color(COLOR_MUTED));
+                ui.add_space(24.0);
+                ui.columns(2, |columns| {
+                    let ui = &mut columns[0];
+                    ui.heading(egui::RichText::new("Start").color(COLOR_TEXT));
+                    if ui.add(egui::Button::new("📝 New File").frame(false)).clicked() {
+                        file_ops::new_file(app);
+                    }
+                    if ui.add(egui::Button::new("📄 Open File…").frame(false)).clicked() {
+                        file_ops::open_file(app);
+                    }
+                    if ui.add(egui::Button::new("📂 Open Folder…").frame(false)).clicked() {
+                        file_ops::pick_folder(app);
+                    }
+                    ui.add_space(16.0);
+                    ui.heading(egui::RichText::new("Recent").color(COLOR_TEXT));
+                    render_recent(app, ui, 8);
+
+                    let ui = &mut columns[1];
+                    ui.heading(egui::RichText::new("Shortcuts").color(COLOR_TEXT));
+                    egui::Grid::new("welcome_shortcuts").num_columns(2).spacing([24.0, 6.0]).show(ui, |ui| {
+                        for (keys, action) in SHORTCUTS {
+                            ui.label(egui::RichText::new(keys).monospace().color(COLOR_ACCENT));
+                            ui.label(action);
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        });
+    });
+}
+
+fn render_completion_settings(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let settings = &mut app.config.completion;
+    let mut changed = ui.checkbox(&mut settings.enabled, "Suggest while typing").changed();
+    changed |= ui.checkbox(&mut settings.include_workspace, "Include workspace words").changed();
+    ui.horizontal(|ui| {
+        ui.label("Trigger after");
+        changed |= ui.add(egui::DragValue::new(&mut settings.min_chars).clamp_range(1..=10).suffix(" chars")).changed();
+    });
+    if changed {
+        save_config(app);
+    }
+}
+
+fn render_exclude_settings(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let settings = &mut app.config.excludes;
+    let mut view_changed = false;
+    let mut rules_changed = false;
+    view_changed |= ui.checkbox(&mut settings.show_ignored, "Show ignored files (dimmed)").changed();
+    view_changed |= ui.checkbox(&mut settings.show_hidden, "Show hidden files").changed();
+    rules_changed |= ui.checkbox(&mut settings.respect_gitignore, "Respect .gitignore").changed();
+
+    ui.separator();
+    ui.label(egui::RichText::new("Exclude patterns, one per line:").color(COLOR_MUTED));
+    let id = egui::Id::new("exclude_globs");
+    let mut draft = ui.data_mut(|d| d.get_temp::<String>(id)).unwrap_or_else(|| settings.globs.join("\n"));
+    let response = ui.add(egui::TextEdit::multiline(&mut draft).desired_rows(4).code_editor());
+    if response.lost_focus() {
+        let globs: Vec<String> = draft.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect();
+        rules_changed |= globs != settings.globs;
+        settings.globs = globs;
+        ui.data_mut(|d| d.remove::<String>(id));
+    } else {
+        ui.data_mut(|d| d.insert_temp(id, draft));
+    }
+
+    if view_changed || rules_changed {
+        file_tree::settings_changed(app, rules_changed);
+        search::settings_changed(app);
+        completion::settings_changed(app);
+        save_config(app);
+    }
+}
+
+fn render_auto_save_settings(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let settings = &mut app.config.save;
+    let mut changed = false;
+    for mode in AutoSave::ALL {
+        changed |= ui.radio_value(&mut settings.auto_save, mode, mode.label()).changed();
+    }
+    ui.add_enabled_ui(settings.auto_save == AutoSave::AfterDelay, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Delay");
+            let delay = egui::DragValue::new(&mut settings.auto_save_delay_ms).clamp_range(100..=60_000).speed(50).suffix(" ms");
+            changed |= ui.add(delay).changed();
+        });
+    });
+    if changed {
+        save_config(app);
+    }
+}
+
+fn save_config(app: &mut CodeEditorApp) {
+    if let Err(e) = app.config.save() {
+        notifications::notify(app, Level::Error, format!("Could not save settings: {}", e), Vec::new());
+    }
+}
+
+fn render_completion_popup(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if !app.completion.active {
+        return;
+    }
+    let Some(caret) = app.caret_rect else { return };
+
+    let mut clicked = None;
+    egui::Area::new("completion_popup")
+        .fixed_pos(caret.left_bottom())
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.set_min_width(220.0);
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    for (i, item) in app.completion.items.iter().enumerate() {
+                        let selected = i == app.completion.selected;
+                        ui.horizontal(|ui| {
+                            let label = ui.selectable_label(selected, egui::RichText::new(&item.label).monospace().color(COLOR_TEXT));
+                            if selected {
+                                label.scroll_to_me(None);
+                            }
+                            if label.clicked() {
+                                clicked = Some(i);
+                            }
+                            ui.label(egui::RichText::new(item.source.label()).size(10.0).color(COLOR_MUTED));
+                            if let Some(detail) = &item.detail {
+                                ui.label(egui::RichText::new(detail).size(10.0).color(COLOR_MUTED));
+                            }
+                        });
+                    }
+                });
+            });
+        });
+
+    if let Some(i) = clicked {
+        completion::accept(app, ctx, i);
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+    }
+}
+
+fn render_signature_help(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some(signature) = &app.lsp.signature else { return };
+    let Some(caret) = app.caret_rect else { return };
+
+    let font = egui::FontId::monospace(12.0);
+    let mut job = egui::text::LayoutJob::default();
+    let (start, end) = signature.active_parameter.unwrap_or((0, 0));
+    let format = |color| egui::TextFormat { font_id: font.clone(), color, ..Default::default() };
+    job.append(&signature.label[..start], 0.0, format(COLOR_TEXT));
+    job.append(&signature.label[start..end], 0.0, format(COLOR_ACCENT));
+    job.append(&signature.label[end..], 0.0, format(COLOR_TEXT));
+
+    egui::Area::new("signature_help")
+        .fixed_pos(caret.left_top())
+        .pivot(egui::Align2::LEFT_BOTTOM)
+        .order(egui::Order::Foreground)
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(job);
+            });
+        });
+}
+
+fn render_code_actions(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if !app.lsp.code_actions_open {
+        return;
+    }
+    let Some(caret) = app.caret_rect else { return };
+
+    let mut chosen = None;
+    egui::Area::new("code_actions")
+        .fixed_pos(caret.left_bottom())
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for (i, action) in app.lsp.code_actions.iter().enumerate() {
+                    if ui.selectable_label(false, egui::RichText::new(format!("💡 {}", action.title)).color(COLOR_TEXT)).clicked() {
+                        chosen = Some(i);
+                    }
+                }
+            });
+        });
+
+    if let Some(i) = chosen {
+        lsp::apply_code_action(app, ctx, i);
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+    } else if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.lsp.code_actions_open = false;
+    }
+}
+
+enum ExternalChoice {
+    Keep,
+    Reload,
+    Compare,
+    Overwrite,
+    Cancel,
+}
+
+// Asks what to do with a modified buffer whose file changed on disk, or with a save
+// that would overwrite such a change; waits while the document is open in the diff view
+fn render_external_changes(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let pending = |docs: &[usize]| docs.iter().copied().find(|&doc| !diff::shows_document(app, doc));
+    let blocked = pending(&app.watcher.blocked_saves);
+    let Some(doc) = blocked.or_else(|| pending(&app.watcher.conflicts)) else { return };
+    let name = app.panes.documents.get(&doc).map(|d| d.name()).unwrap_or_default();
+
+    let mut choice = None;
+    egui::Window::new("⚠ File Changed on Disk")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            if blocked.is_some() {
+                ui.label(format!("'{}' was changed by another program since it was opened.", name));
+                ui.label("Saving now would overwrite those changes.");
+            } else {
+                ui.label(format!("'{}' was changed by another program, and it has unsaved edits here.", name));
+            }
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                if blocked.is_some() {
+                    if ui.button(egui::RichText::new("💾 Overwrite").color(COLOR_ERROR)).clicked() {
+                        choice = Some(ExternalChoice::Overwrite);
+                    }
+                } else if ui.button(egui::RichText::new("✋ Keep Mine").color(COLOR_SUCCESS)).clicked() {
+                    choice = Some(ExternalChoice::Keep);
+                }
+                if ui.button("↻ Reload from Disk").clicked() {
+                    choice = Some(ExternalChoice::Reload);
+                }
+                if ui.button("⇄ Compare").clicked() {
+                    choice = Some(ExternalChoice::Compare);
+                }
+                if blocked.is_some() && ui.button("Cancel").clicked() {
+                    choice = Some(ExternalChoice::Cancel);
+                }
+            });
+        });
+
+    match choice {
+        Some(ExternalChoice::Keep) => watcher::keep_buffer(app, doc),
+        Some(ExternalChoice::Reload) => watcher::reload_from_disk(app, doc),
+        Some(ExternalChoice::Compare) => watcher::compare(app, doc),
+        Some(ExternalChoice::Overwrite) => file_ops::overwrite(app, doc),
+        Some(ExternalChoice::Cancel) => watcher::resolved(app, doc),
+        None => {}
+    }
+}
+
+// Asks whether to save an unsaved document before its last tab closes
+fn render_close_confirmation(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some(&doc) = app.panes.closing.as_ref().and_then(|(_, unsaved)| unsaved.first()) else { return };
+    let name = app.panes.documents.get(&doc).map(|d| d.name()).unwrap_or_default();
+
+    let mut save = false;
+    let mut discard = false;
+    let mut cancel = false;
+    egui::Window::new("💾 Unsaved Changes")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            ui.label(format!("Save the changes to '{}' before closing it?", name));
+            ui.add_space(6.0);
+            ui.horizontal(|ui| {
+                if ui.button(egui::RichText::new("💾 Save").color(COLOR_SUCCESS)).clicked() {
+                    save = true;
+                }
+                if ui.button(egui::RichText::new("Don't Save").color(COLOR_ERROR)).clicked() {
+                    discard = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if save {
+        file_ops::save_document(app, doc);
+        // A cancelled Save As or a failed write keeps the question open
+        if !panes::is_dirty(app, doc) {
+            panes::close_answered(app);
+        }
+    } else if discard {
+        panes::close_answered(app);
+    } else if cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.panes.closing = None;
+    }
+}
+
+fn render_toasts(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if app.notifications.toasts.is_empty() {
+        return;
+    }
+    let mut hovered = Vec::new();
+    let mut clicked = None;
+    egui::Area::new("toasts")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -36.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            for toast in &app.notifications.toasts {
+                let Some(notification) = notifications::get(app, toast.id) else { continue };
+                let frame = egui::Frame::popup(ui.style())
+                    .fill(COLOR_DARK_BG)
+                    .stroke(egui::Stroke::new(1.0, notification.level.color()))
+                    .show(ui, |ui| {
+                        ui.set_width(360.0);
+                        ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(notification.level.icon()).color(notification.level.color()));
+                            ui.label(egui::RichText::new(notification.time.format("%H:%M:%S").to_string()).size(11.0).color(COLOR_MUTED));
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                if ui.small_button("✕").clicked() {
+                                    clicked = Some((toast.id, None));
+                                }
+                            });
+                        });
+                        ui.add(egui::Label::new(egui::RichText::new(&notification.message).color(COLOR_TEXT)).wrap(true));
+                        if let Some(action) = render_notification_actions(ui, notification) {
+                            clicked = Some((toast.id, Some(action)));
+                        }
+                    });
+                if ui.rect_contains_pointer(frame.response.rect) {
+                    hovered.push(toast.id);
+                }
+                ui.add_space(6.0);
+            }
+        });
+
+    for id in hovered {
+        notifications::hold(app, id);
+    }
+    match clicked {
+        Some((id, Some(action))) => notifications::perform(app, id, action),
+        Some((id, None)) => notifications::dismiss(app, id),
+        None => {}
+    }
+}
+
+fn render_notification_actions(ui: &mut egui::Ui, notification: &Notification) -> Option<Action> {
+    let mut clicked = None;
+    if !notification.actions.is_empty() {
+        ui.horizontal(|ui| {
+            for action in &notification.actions {
+                if ui.small_button(action.label()).clicked() {
+                    clicked = Some(action.clone());
+                }
+            }
+        });
+    }
+    clicked
+}
+
+fn render_notifications(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let mut clicked = None;
+    let mut clear = false;
+    if !app.notifications.history.is_empty() && ui.small_button("🧹 Clear All").clicked() {
+        clear = true;
+    }
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            for notification in app.notifications.history.iter().rev() {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(notification.time.format("%H:%M:%S").to_string()).monospace().size(11.0).color(COLOR_MUTED));
+                    ui.label(egui::RichText::new(notification.level.icon()).color(notification.level.color()));
+                    ui.label(egui::RichText::new(&notification.message).size(12.0).color(COLOR_TEXT));
+                    if let Some(action) = render_notification_actions(ui, notification) {
+                        clicked = Some((notification.id, action));
+                    }
+                });
+            }
+            if app.notifications.history.is_empty() {
+                ui.colored_label(COLOR_MUTED, "No notifications");
+            }
+        });
+    if clear {
+        notifications::clear(app);
+    }
+    if let Some((id, action)) = clicked {
+        notifications::perform(app, id, action);
+    }
+}
+
+fn render_rename_dialog(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some(rename) = app.lsp.rename.as_mut() else { return };
+
+    let mut open = true;
+    let mut commit = false;
+    let mut cancel = false;
+    egui::Window::new("✏ Rename Symbol")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let response = ui.text_edit_singleline(&mut rename.new_name);
+            response.request_focus();
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                commit = true;
+            }
+            ui.horizontal(|ui| {
+                if ui.button(egui::RichText::new("Rename").color(COLOR_SUCCESS)).clicked() {
+                    commit = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+    if commit {
+        lsp::commit_rename(app);
+        ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+    } else if !open || cancel || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        app.lsp.rename = None;
+    }
+}
+
+fn render_problems(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let mut target = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            for (path, diagnostics) in &app.lsp.diagnostics {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                ui.label(egui::RichText::new(format!("📄 {} ({})", file_name, diagnostics.len())).color(COLOR_ACCENT));
+                let mut sorted: Vec<_> = diagnostics.iter().collect();
+                sorted.sort_by_key(|d| (d.severity, d.range.start.line));
+                for diagnostic in sorted {
+                    let source = diagnostic.source.as_deref().map(|s| format!("  [{}]", s)).unwrap_or_default();
+                    let text = format!(
+                        "    {} {}:{}  {}{}",
+                        diagnostic.severity.icon(),
+                        diagnostic.range.start.line + 1,
+                        diagnostic.range.start.character + 1,
+                        diagnostic.message.lines().next().unwrap_or_default(),
+                        source,
+                    );
+                    if ui.selectable_label(false, egui::RichText::new(text).monospace().size(11.0).color(diagnostic.severity.color())).clicked() {
+                        target = Some(lsp::Location { path: path.clone(), range: diagnostic.range });
+                    }
+                }
+            }
+            if app.lsp.diagnostics.is_empty() {
+                ui.colored_label(COLOR_MUTED, "No problems detected");
+            }
+        });
+    if let Some(location) = target {
+        lsp::open_location(app, ui.ctx(), &location);
+    }
+}
+
+fn render_references(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let mut target = None;
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            for location in &app.lsp.references {
+                let text = format!(
+                    "🔗 {}:{}:{}",
+                    location.path.display(),
+                    location.range.start.line + 1,
+                    location.range.start.character + 1,
+                );
+                if ui.selectable_label(false, egui::RichText::new(text).monospace().size(11.0).color(COLOR_TEXT)).clicked() {
+                    target = Some(location.clone());
+                }
+            }
+            if app.lsp.references.is_empty() {
+                ui.colored_label(COLOR_MUTED, "No references (Shift+F12 on a symbol)");
+            }
+        });
+    if let Some(location) = target {
+        lsp::open_location(app, ui.ctx(), &location);
+    }
+}
+
+fn render_search(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    let mut run = false;
+    ui.horizontal(|ui| {
+        let response = ui.add(egui::TextEdit::singleline(&mut app.search.query).hint_text("Find in files").desired_width(320.0));
+        if app.search.focus_query {
+            response.request_focus();
+            app.search.focus_query = false;
+        }
+        run |= response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        run |= ui.checkbox(&mut app.search.case_sensitive, "Aa").on_hover_text("Match case").changed();
+        run |= ui.button("🔎 Search").clicked();
+        if app.search.searching() {
+            ui.spinner();
+            if ui.button("⏹ Stop").clicked() {
+                search::cancel_search(app);
+            }
+        }
+        let files = app.search.matches.windows(2).filter(|w| w[0].path != w[1].path).count() + usize::from(!app.search.matches.is_empty());
+        let mut status = format!("{} results in {} files", app.search.matches.len(), files);
+        if app.search.truncated {
+            status.push_str(" (stopped at the limit)");
+        }
+        ui.colored_label(COLOR_MUTED, status);
+    });
+    if run {
+        search::start_search(app, ui.ctx());
+    }
+    ui.separator();
+
+    if app.root_folder.is_none() {
+        ui.colored_label(COLOR_MUTED, "Open a folder to search its files");
+        return;
+    }
+    // A header row before each file's matches: (is header, match index)
+    let mut rows: Vec<(bool, usize)> = Vec::new();
+    for (i, found) in app.search.matches.iter().enumerate() {
+        if i == 0 || app.search.matches[i - 1].path != found.path {
+            rows.push((true, i));
+        }
+        rows.push((false, i));
+    }
+    let root = app.root_folder.clone().unwrap_or_default();
+    let mut target = None;
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    egui::ScrollArea::vertical()
+        .auto_shrink([false; 2])
+        .show_rows(ui, row_height, rows.len(), |ui, range| {
+            for &(header, i) in &rows[range] {
+                let found = &app.search.matches[i];
+                if header {
+                    let relative = found.path.strip_prefix(&root).unwrap_or(&found.path);
+                    ui.label(egui::RichText::new(format!("📄 {}", relative.display())).color(COLOR_ACCENT));
+                    continue;
+                }
+                let preview: String = found.text.trim().chars().take(200).collect();
+                let text = format!("    {:>5}  {}", found.line + 1, preview);
+                if ui.selectable_label(false, egui::RichText::new(text).monospace().size(11.0).color(COLOR_TEXT)).clicked() {
+                    target = Some(found.clone());
+                }
+            }
+        });
+    if rows.is_empty() && !app.search.searching() && !app.search.query.is_empty() {
+        ui.colored_label(COLOR_MUTED, "No results (Enter to search)");
+    }
+    if let Some(found) = target {
+        search::open_match(app, ui.ctx(), &found);
+    }
+}
+
+fn render_quick_open(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let Some(root) = app.root_folder.clone() else { return };
+    let listed = search::workspace_index(app).is_some();
+    let Some(quick_open) = app.search.quick_open.as_mut() else { return };
+
+    let up = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp));
+    let down = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown));
+    let enter = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter));
+    let escape = ctx.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
+    if up {
+        quick_open.selected = quick_open.selected.saturating_sub(1);
+    }
+    if down && quick_open.selected + 1 < quick_open.matches.len() {
+        quick_open.selected += 1;
+    }
+
+    let mut open = true;
+    let mut query_changed = false;
+    let mut chosen = None;
+    egui::Window::new("🔍 Go to File")
+        .open(&mut open)
+        .collapsible(false)
+        .default_width(480.0)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+        .show(ctx, |ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut quick_open.query).hint_text("File name").desired_width(f32::INFINITY));
+            response.request_focus();
+            query_changed = response.changed();
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                for (i, path) in quick_open.matches.iter().enumerate() {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let folder = path.parent().and_then(|p| p.strip_prefix(&root).ok()).unwrap_or(std::path::Path::new(""));
+                    let text = format!("{}   {}", name, folder.display());
+                    let response = ui.selectable_label(i == quick_open.selected, egui::RichText::new(text).color(COLOR_TEXT));
+                    if i == quick_open.selected && (up || down) {
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() {
+                        chosen = Some(path.clone());
+                    }
+                }
+                if !listed {
+                    ui.colored_label(COLOR_MUTED, "Listing files…");
+                } else if quick_open.matches.is_empty() {
+                    ui.colored_label(COLOR_MUTED, "No matching files");
+                }
+            });
+        });
+
+    if enter {
+        chosen = chosen.or_else(|| quick_open.matches.get(quick_open.selected).cloned());
+    }
+    if query_changed {
+        search::refresh_quick_open(app);
+    }
+    if let Some(path) = chosen {
+        search::open_file(app, ctx, path);
+    } else if escape || !open {
+        app.search.quick_open = None;
+    }
+}
+
+fn render_diff_view(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if app.diff.view.is_none() {
+        return;
+    }
+    diff::refresh(app, false);
+    let Some(view) = app.diff.view.as_mut() else { return };
+
+    let mut open = true;
+    let mut revert = None;
+    let mut reload = false;
+    egui::Window::new("⇄ Diff")
+        .open(&mut open)
+        .default_size([1000.0, 600.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let was_side_by_side = view.side_by_side;
+                ui.selectable_value(&mut view.side_by_side, true, "◫ Side by Side");
+                ui.selectable_value(&mut view.side_by_side, false, "☰ Inline");
+                if view.side_by_side != was_side_by_side {
+                    view.go_to_hunk(view.current);
+                }
+                ui.separator();
+                let count = view.hunks.len();
+                if ui.add_enabled(view.current > 0, egui::Button::new("⬆ Previous")).clicked() {
+                    view.go_to_hunk(view.current - 1);
+                }
+                if ui.add_enabled(view.current + 1 < count, egui::Button::new("⬇ Next")).clicked() {
+                    view.go_to_hunk(view.current + 1);
+                }
+                let position = if count == 0 { "No differences".to_string() } else { format!("Change {} of {}", view.current + 1, count) };
+                ui.label(egui::RichText::new(position).color(COLOR_MUTED));
+                ui.separator();
+                if ui.add_enabled(count > 0, egui::Button::new("↺ Revert Change")).clicked() {
+                    revert = Some(view.current);
+                }
+                if ui.button("🔄 Refresh").clicked() {
+                    reload = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(format!("− {}", view.left_title)).color(COLOR_ERROR));
+                ui.label(egui::RichText::new("→").color(COLOR_MUTED));
+                ui.label(egui::RichText::new(format!("+ {}", view.right_title)).color(COLOR_SUCCESS));
+            });
+            ui.separator();
+
+            let font = egui::FontId::monospace(12.0);
+            let row_height = ui.fonts(|f| f.row_height(&font)) + 2.0;
+            let mut scroll = egui::ScrollArea::vertical().id_source("diff_rows").auto_shrink([false; 2]);
+            if let Some(row) = view.scroll_to.take() {
+                scroll = scroll.vertical_scroll_offset((row as f32 - 3.0).max(0.0) * row_height);
+            }
+            let rows = view.visible_rows().len();
+            scroll.show_rows(ui, row_height, rows, |ui, range| {
+                for index in range {
+                    let row = view.visible_rows()[index].clone();
+                    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), row_height), egui::Sense::hover());
+                    if row.hunk.is_some() && view.hunk_start(index) {
+                        let button = ui.put(egui::Rect::from_min_size(rect.min, egui::vec2(20.0, row_height)), egui::Button::new("↺").small());
+                        if button.on_hover_text("Revert this change").clicked() {
+                            revert = row.hunk;
+                        }
+                    }
+                    if row.hunk == Some(view.current) {
+                        ui.painter().rect_filled(egui::Rect::from_min_size(rect.min + egui::vec2(22.0, 0.0), egui::vec2(2.0, row_height)), 0.0, COLOR_ACCENT);
+                    }
+                    let body = egui::Rect::from_min_max(rect.min + egui::vec2(26.0, 0.0), rect.max);
+                    if view.side_by_side {
+                        let half = body.width() / 2.0;
+                        let left = egui::Rect::from_min_size(body.min, egui::vec2(half - 2.0, row_height));
+                        let right = egui::Rect::from_min_size(body.min + egui::vec2(half + 2.0, 0.0), egui::vec2(half - 2.0, row_height));
+                        paint_diff_line(ui, view, left, &row, true, &font);
+                        paint_diff_line(ui, view, right, &row, false, &font);
+                    } else {
+                        let old_side = row.kind != RowKind::Insert;
+                        paint_diff_line(ui, view, body, &row, old_side, &font);
+                    }
+                }
+            });
+        });
+
+    if reload {
+        diff::refresh(app, true);
+    }
+    if let Some(hunk) = revert {
+        diff::revert_hunk(app, hunk);
+    }
+    if !open {
+        app.diff.view = None;
+    }
+}
+
+// One side of a diff row: line number, background by change kind and highlighted characters
+fn paint_diff_line(ui: &egui::Ui, view: &diff::DiffView, rect: egui::Rect, row: &diff::Row, old_side: bool, font: &egui::FontId) {
+    let (line, other) = if old_side { (row.left, row.right) } else { (row.right, row.left) };
+    let lines = if old_side { &view.old_lines } else { &view.new_lines };
+    let other_lines = if old_side { &view.new_lines } else { &view.old_lines };
+    let painter = ui.painter_at(rect);
+    let Some(line) = line else {
+        painter.rect_filled(rect, 0.0, COLOR_DARK_BG);
+        return;
+    };
+
+    let (background, highlight) = match (row.kind, old_side) {
+        (RowKind::Equal, _) => (egui::Color32::TRANSPARENT, egui::Color32::TRANSPARENT),
+        (_, true) => (egui::Color32::from_rgba_unmultiplied(255, 85, 85, 35), egui::Color32::from_rgba_unmultiplied(255, 85, 85, 110)),
+        (_, false) => (egui::Color32::from_rgba_unmultiplied(80, 250, 123, 30), egui::Color32::from_rgba_unmultiplied(80, 250, 123, 100)),
+    };
+    painter.rect_filled(rect, 0.0, background);
+
+    let marker = match (row.kind, old_side) {
+        (RowKind::Equal, _) => ' ',
+        (_, true) => '−',
+        (_, false) => '+',
+    };
+    painter.text(rect.left_center(), egui::Align2::LEFT_CENTER, format!("{:>5} {}", line + 1, marker), font.clone(), COLOR_MUTED);
+
+    let text = &lines[line];
+    let spans = match other {
+        Some(other) if row.kind != RowKind::Equal => diff::changed_spans(text, &other_lines[other], old_side),
+        _ => vec![(text.clone(), false)],
+    };
+    let mut job = egui::text::LayoutJob::default();
+    for (span, changed) in spans {
+        let format = egui::TextFormat {
+            font_id: font.clone(),
+            color: COLOR_TEXT,
+            background: if changed { highlight } else { egui::Color32::TRANSPARENT },
+            ..Default::default()
+        };
+        job.append(&span, 0.0, format);
+    }
+    let galley = ui.fonts(|f| f.layout_job(job));
+    let gutter = ui.fonts(|f| f.glyph_width(font, '0')) * 8.0;
+    painter.galley(rect.left_top() + egui::vec2(gutter, 1.0), galley);
+}
+
+fn render_status_bar(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    let mut open_history = false;
     egui::TopBottomPanel::bottom("status_bar")
         .frame(egui::Frame::none().fill(COLOR_DARK_BG).stroke(egui::Stroke::new(1.0, COLOR_MUTED)))
         .show(ctx, |ui| {
@@ -84,15 +1302,52 @@ fn render_status_bar(app: &CodeEditorApp, ctx: &egui::Context) {
                 ui.label(egui::RichText::new("✓").color(COLOR_SUCCESS));
                 ui.label(egui::RichText::new("Ready").color(COLOR_TEXT));
                 ui.separator();
+                if app.macros.recording.is_some() {
+                    ui.label(egui::RichText::new("⏺ REC").color(COLOR_ERROR));
+                    ui.separator();
+                } else if macros::is_playing(app) {
+                    ui.label(egui::RichText::new("▶ Playing macro").color(COLOR_ACCENT));
+                    ui.separator();
+                }
                 ui.label(egui::RichText::new(format!("Lines: {}", app.text.lines().count())).color(COLOR_ACCENT));
                 ui.separator();
                 ui.label(egui::RichText::new("UTF-8").color(COLOR_MUTED));
                 ui.separator();
+                if app.config.save.auto_save != AutoSave::Off {
+                    let text = match app.autosave.last_saved {
+                        Some(time) => format!("💾 Auto-saved {}", time.format("%H:%M:%S")),
+                        None => "💾 Auto-save".to_string(),
+                    };
+                    ui.label(egui::RichText::new(text).color(COLOR_MUTED))
+                        .on_hover_text(format!("Auto save: {}", app.config.save.auto_save.label()));
+                    ui.separator();
+                }
+                let (errors, warnings) = app.lsp.diagnostics.values().flatten().fold((0, 0), |(e, w), d| match d.severity {
+                    lsp::Severity::Error => (e + 1, w),
+                    lsp::Severity::Warning => (e, w + 1),
+                    _ => (e, w),
+                });
+                ui.label(egui::RichText::new(format!("❌ {}  ⚠ {}", errors, warnings)).color(COLOR_MUTED));
+                ui.separator();
+                let unread = app.notifications.unread;
+                let (bell, color) = if unread > 0 { (format!("🔔 {}", unread), COLOR_ACCENT) } else { ("🔔".to_string(), COLOR_MUTED) };
+                let bell = ui.add(egui::Label::new(egui::RichText::new(bell).color(color)).sense(egui::Sense::click()));
+                if bell.on_hover_text("Notifications").clicked() {
+                    open_history = true;
+                }
+                ui.separator();
+                if let Some(status) = lsp::status(app) {
+                    ui.label(egui::RichText::new(format!("🧠 {}", status)).color(COLOR_MUTED));
+                    ui.separator();
+                }
                 if let Some(path) = &app.file_path {
                     ui.label(egui::RichText::new(format!("📄 {}", path.file_name().unwrap().to_string_lossy())).color(COLOR_SUCCESS));
                 }
             });
         });
+    if open_history {
+        notifications::show_history(app);
+    }
 }
 
 fn render_breadcrumb(app: &mut CodeEditorApp, ctx: &egui::Context) {
@@ -120,19 +1375,33 @@ fn render_breadcrumb(app: &mut CodeEditorApp, ctx: &egui::Context) {
 
 fn render_sidebar(app: &mut CodeEditorApp, ctx: &egui::Context) {
     if app.show_sidebar {
-        egui::SidePanel::left("sidebar")
+        let panel = egui::SidePanel::left("sidebar")
             .min_width(300.0)
+            .default_width(app.session.sidebar_width.unwrap_or(300.0))
             .frame(egui::Frame::none().fill(COLOR_DARK_BG).stroke(egui::Stroke::new(1.0, COLOR_MUTED)))
             .show(ctx, |ui| {
                 ui.heading(egui::RichText::new("📂 File Explorer").color(COLOR_ACCENT).size(16.0));
                 ui.separator();
                 
                 if let Some(root) = &app.root_folder.clone() {
-                    egui::ScrollArea::vertical()
-                        .max_height(400.0)
-                        .show(ui, |ui| {
-                            file_tree::render_tree(app, ui, root);
-                        });
+                    let mut filter = app.file_tree.filter.clone();
+                    let response = ui.add(egui::TextEdit::singleline(&mut filter).hint_text("🔍 Filter files").desired_width(f32::INFINITY));
+                    if response.changed() {
+                        file_tree::set_filter(app, filter);
+                    }
+                    // ↓ or Enter hands the keyboard to the tree; Escape clears the filter
+                    if response.has_focus() && ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
+                        response.surrender_focus();
+                        file_tree::focus_tree(app);
+                    }
+                    if response.lost_focus() {
+                        if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            file_tree::focus_tree(app);
+                        } else if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                            file_tree::set_filter(app, String::new());
+                        }
+                    }
+                    file_tree::render_tree(app, ui, root);
                 } else {
                     ui.colored_label(COLOR_MUTED, "📂 Open a folder to see files");
                 }
@@ -140,18 +1409,20 @@ fn render_sidebar(app: &mut CodeEditorApp, ctx: &egui::Context) {
                 ui.separator();
                 ui.heading(egui::RichText::new("📋 Open Files").color(COLOR_ACCENT).size(16.0));
                 
-                let open_files = app.open_files.clone();
-                let current_index = app.current_file_index;
+                let open_files: Vec<(usize, String)> = app.panes.documents.keys()
+                    .map(|id| (*id, tab_label(app, *id)))
+                    .collect();
+                let current_doc = app.panes.active_doc;
                 let mut selected_index = None;
                 let mut close_index = None;
                 
                 egui::ScrollArea::vertical()
                     .max_height(300.0)
                     .show(ui, |ui| {
-                        for (i, path) in open_files.iter().enumerate() {
+                        for (i, file_name) in open_files.iter() {
+                            let i = *i;
                             ui.horizontal(|ui| {
-                                let file_name = path.file_name().unwrap().to_string_lossy();
-                                let is_active = i == current_index;
+                                let is_active = Some(i) == current_doc;
                                 
                                 let label_color = if is_active { COLOR_SUCCESS } else { COLOR_TEXT };
                                 
@@ -170,29 +1441,99 @@ fn render_sidebar(app: &mut CodeEditorApp, ctx: &egui::Context) {
                     });
                 
                 if let Some(i) = selected_index {
-                    app.current_file_index = i;
-                    file_ops::load_file(app, open_files[i].clone());
+                    panes::show(app, i);
                 }
                 
                 if let Some(i) = close_index {
-                    app.open_files.remove(i);
-                    if app.current_file_index >= app.open_files.len() {
-                        app.current_file_index = app.open_files.len().saturating_sub(1);
-                    }
+                    panes::request_close(app, panes::CloseTarget::Document(i));
                 }
+
+                ui.separator();
+                ui.heading(egui::RichText::new("🧭 Outline").color(COLOR_ACCENT).size(16.0));
+                render_outline(app, ui);
             });
+        app.session.sidebar_width = Some(panel.response.rect.width());
+    }
+}
+
+fn render_outline(app: &mut CodeEditorApp, ui: &mut egui::Ui) {
+    if app.file_path.is_none() {
+        ui.colored_label(COLOR_MUTED, "Open a file to see its symbols");
+        return;
+    }
+    ui.add(egui::TextEdit::singleline(&mut app.outline.filter).hint_text("Filter symbols").desired_width(f32::INFINITY));
+
+    let symbols = outline::symbols(app);
+    let current = app.cursor.and_then(|(primary, _)| outline::symbol_at(&symbols, primary)).map(|s| s.start);
+    let filter = app.outline.filter.to_lowercase();
+    let mut target = None;
+
+    egui::ScrollArea::vertical()
+        .id_source("outline")
+        .max_height(300.0)
+        .show(ui, |ui| {
+            for symbol in &symbols {
+                render_symbol(ui, symbol, 0, current, &filter, &mut target);
+            }
+            if symbols.is_empty() {
+                ui.colored_label(COLOR_MUTED, "No symbols in this file");
+            }
+        });
+
+    if let Some(index) = target {
+        editor::jump_to(app, ui.ctx(), index);
+    }
+}
+
+fn render_symbol(ui: &mut egui::Ui, symbol: &Symbol, depth: usize, current: Option<usize>, filter: &str, target: &mut Option<usize>) {
+    fn matches(symbol: &Symbol, filter: &str) -> bool {
+        symbol.name.to_lowercase().contains(filter) || symbol.children.iter().any(|c| matches(c, filter))
+    }
+    if !filter.is_empty() && !matches(symbol, filter) {
+        return;
+    }
+
+    let is_current = current == Some(symbol.start);
+    let color = if is_current { COLOR_SUCCESS } else { COLOR_TEXT };
+    let text = format!("{}{} {}", "    ".repeat(depth), symbol.kind.icon(), symbol.name);
+    let label = ui.selectable_label(is_current, egui::RichText::new(text).color(color));
+    if label.clicked() {
+        *target = Some(symbol.index);
+    }
+    for child in &symbol.children {
+        render_symbol(ui, child, depth + 1, current, filter, target);
     }
 }
 
 fn render_terminal(app: &mut CodeEditorApp, ctx: &egui::Context) {
     if app.show_terminal {
-        egui::TopBottomPanel::bottom("terminal")
+        let panel = egui::TopBottomPanel::bottom("terminal")
             .min_height(280.0)
+            .default_height(app.session.terminal_height.unwrap_or(280.0))
+            .resizable(true)
             .frame(egui::Frame::none().fill(COLOR_DARK_BG).stroke(egui::Stroke::new(1.0, COLOR_MUTED)))
             .show(ctx, |ui| {
                 // Header with buttons
                 ui.horizontal(|ui| {
-                    ui.heading(egui::RichText::new("🖥️ Terminal").color(COLOR_ACCENT).size(16.0));
+                    let problem_count: usize = app.lsp.diagnostics.values().map(|d| d.len()).sum();
+                    let tabs = [
+                        (BottomPanel::Terminal, "🖥️ Terminal".to_string()),
+                        (BottomPanel::Problems, format!("⚠ Problems ({})", problem_count)),
+                        (BottomPanel::References, format!("🔗 References ({})", app.lsp.references.len())),
+                        (BottomPanel::Search, format!("🔎 Search ({})", app.search.matches.len())),
+                        (BottomPanel::Notifications, format!("🔔 Notifications ({})", app.notifications.history.len())),
+                    ];
+                    for (panel, title) in tabs {
+                        let selected = app.bottom_panel == panel;
+                        let color = if selected { COLOR_ACCENT } else { COLOR_MUTED };
+                        if ui.selectable_label(selected, egui::RichText::new(title).color(color).size(16.0)).clicked() {
+                            app.bottom_panel = panel;
+                        }
+                    }
+                    if app.bottom_panel != BottomPanel::Terminal {
+                        return;
+                    }
+                    ui.separator();
                     if ui.button(egui::RichText::new("⚡ PowerShell").color(COLOR_SUCCESS)).clicked() {
                         terminal::open_powershell(app);
                     }
@@ -206,11 +1547,19 @@ fn render_terminal(app: &mut CodeEditorApp, ctx: &egui::Context) {
                 });
                 
                 ui.separator();
+
+                match app.bottom_panel {
+                    BottomPanel::Problems => return render_problems(app, ui),
+                    BottomPanel::References => return render_references(app, ui),
+                    BottomPanel::Search => return render_search(app, ui),
+                    BottomPanel::Notifications => return render_notifications(app, ui),
+                    BottomPanel::Terminal => {}
+                }
                 
-                // Terminal output display
+                // Terminal output display, growing with the panel above the input rows
                 let output = app.terminal_output.lock().unwrap().clone();
                 egui::ScrollArea::vertical()
-                    .max_height(130.0)
+                    .max_height((ui.available_height() - 80.0).max(130.0))
                     .auto_shrink([false; 2])
                     .stick_to_bottom(true)
                     .show(ui, |ui| {
@@ -225,8 +1574,19 @@ fn render_terminal(app: &mut CodeEditorApp, ctx: &egui::Context) {
                 // Terminal input section
                 ui.label(egui::RichText::new("Command Input:").color(COLOR_ACCENT));
                 ui.horizontal(|ui| {
-                    ui.label(egui::RichText::new("$ ").color(COLOR_ACCENT).size(14.0));
-                    ui.text_edit_singleline(&mut app.terminal_input);
+                    let prompt = match &app.terminal_dir {
+                        Some(dir) => format!("{} $ ", dir.file_name().unwrap_or_default().to_string_lossy()),
+                        None => "$ ".to_string(),
+                    };
+                    ui.label(egui::RichText::new(prompt).color(COLOR_ACCENT).size(14.0));
+                    let input = ui.text_edit_singleline(&mut app.terminal_input);
+                    if input.has_focus() {
+                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+                            terminal::recall(app, true);
+                        } else if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+                            terminal::recall(app, false);
+                        }
+                    }
                     
                     if ui.button(egui::RichText::new("Execute ▶").size(13.0).color(COLOR_TEXT)).clicked() {
                         if !app.terminal_input.is_empty() {
@@ -242,6 +1602,7 @@ fn render_terminal(app: &mut CodeEditorApp, ctx: &egui::Context) {
                     ui.label(egui::RichText::new("dir | ls | echo test | python script.py").color(COLOR_MUTED).size(10.0).italics());
                 });
             });
+        app.session.terminal_height = Some(panel.response.rect.height());
     }
 }
 
@@ -253,46 +1614,9 @@ fn render_editor(app: &mut CodeEditorApp, ctx: &egui::Context) {
     egui::CentralPanel::default()
         .frame(egui::Frame::none().fill(COLOR_BG))
         .show(ctx, |ui| {
-            if !app.open_files.is_empty() {
-                ui.horizontal(|ui| {
-                    let open_files = app.open_files.clone();
-                    let current_index = app.current_file_index;
-                    let mut selected_index = None;
-                    let mut close_index = None;
-                    
-                    for (i, path) in open_files.iter().enumerate() {
-                        let tab_name = path.file_name().unwrap().to_string_lossy();
-                        let is_active = i == current_index;
-                        let tab_color = if is_active { COLOR_ACCENT } else { COLOR_MUTED };
-                        
-                        ui.horizontal(|ui| {
-                            if ui.selectable_label(
-                                is_active,
-                                egui::RichText::new(format!("  {} ", tab_name)).color(tab_color)
-                            ).clicked() {
-                                selected_index = Some(i);
-                            }
-                            if ui.button(egui::RichText::new("✕").color(COLOR_ERROR).size(12.0)).clicked() {
-                                close_index = Some(i);
-                            }
-                        });
-                    }
-                    
-                    if let Some(i) = selected_index {
-                        app.current_file_index = i;
-                        file_ops::load_file(app, open_files[i].clone());
-                    }
-                    
-                    if let Some(i) = close_index {
-                        app.open_files.remove(i);
-                        if app.current_file_index >= app.open_files.len() {
-                            app.current_file_index = app.open_files.len().saturating_sub(1);
-                        }
-                    }
-                });
-                ui.separator();
+            if app.panes.documents.is_empty() {
+                return render_welcome(app, ui);
             }
-
             if let Some(path) = &app.file_path.clone() {
                 ui.horizontal(|ui| {
                     if ui.button(egui::RichText::new("▶️ Run").color(COLOR_SUCCESS).size(14.0)).clicked() {
@@ -305,6 +1629,134 @@ fn render_editor(app: &mut CodeEditorApp, ctx: &egui::Context) {
                 ui.separator();
             }
 
-            editor::render_editor(app, ui);
+            let count = app.panes.groups.len();
+            let gap = 6.0;
+            let available = ui.available_size();
+            let (size, layout) = match app.panes.direction {
+                SplitDirection::Horizontal => (
+                    egui::vec2((available.x - gap * (count - 1) as f32) / count as f32, available.y),
+                    egui::Layout::left_to_right(egui::Align::Min),
+                ),
+                SplitDirection::Vertical => (
+                    egui::vec2(available.x, (available.y - gap * (count - 1) as f32) / count as f32),
+                    egui::Layout::top_down(egui::Align::Min),
+                ),
+            };
+
+            let mut rects = Vec::new();
+            let mut action = None;
+            ui.with_layout(layout, |ui| {
+                ui.spacing_mut().item_spacing = egui::vec2(gap, gap);
+                for group in 0..count {
+                    let response = ui.allocate_ui_with_layout(size, egui::Layout::top_down(egui::Align::Min), |ui| {
+                        ui.set_min_size(size);
+                        ui.spacing_mut().item_spacing = egui::vec2(8.0, 3.0);
+                        if let Some(a) = render_tab_strip(app, ui, group) {
+                            action = Some(a);
+                        }
+                        let binary = app.panes.groups[group].current().filter(|&doc| panes::is_binary(app, doc));
+                        if let Some(doc) = binary {
+                            hex::render(app, ui, group, doc);
+                        } else if group == app.panes.active_group {
+                            editor::render_editor(app, ui);
+                        } else {
+                            editor::render_pane(app, ui, group);
+                        }
+                    }).response;
+                    if count > 1 && group == app.panes.active_group {
+                        ui.painter().rect_stroke(response.rect, 2.0, egui::Stroke::new(1.0, COLOR_MUTED));
+                    }
+                    rects.push(response.rect);
+                }
+            });
+
+            match action {
+                Some(TabAction::Select(group, tab)) => {
+                    panes::select_tab(app, group, tab);
+                    ctx.memory_mut(|m| m.request_focus(editor::editor_id(app)));
+                }
+                Some(TabAction::Close(group, tab)) => panes::request_close_tab(app, group, tab),
+                None => {}
+            }
+            handle_tab_drop(app, ctx, &rects);
         });
 }
+
+enum TabAction {
+    Select(usize, usize),
+    Close(usize, usize),
+}
+
+// "● name" for unsaved edits, with a note when the file was deleted from disk
+fn tab_label(app: &CodeEditorApp, doc: usize) -> String {
+    let Some(document) = app.panes.documents.get(&doc) else { return String::new() };
+    let marker = if panes::is_dirty(app, doc) { "● " } else { "" };
+    let deleted = if document.missing { " (deleted)" } else { "" };
+    let recovered = if app.recovery.recovered.contains(&doc) { " (recovered)" } else { "" };
+    let lossy = if document.lossy { " (converted)" } else { "" };
+    format!("{}{}{}{}{}", marker, document.name(), deleted, recovered, lossy)
+}
+
+fn render_tab_strip(app: &mut CodeEditorApp, ui: &mut egui::Ui, group: usize) -> Option<TabAction> {
+    let tabs = app.panes.groups[group].tabs.clone();
+    if tabs.is_empty() {
+        return None;
+    }
+    let current = app.panes.groups[group].active;
+    let focused = group == app.panes.active_group;
+    let mut action = None;
+
+    ui.horizontal_wrapped(|ui| {
+        for (i, doc) in tabs.iter().enumerate() {
+            let tab_name = tab_label(app, *doc);
+            let is_active = i == current;
+            let tab_color = if is_active && focused { COLOR_ACCENT } else if is_active { COLOR_TEXT } else { COLOR_MUTED };
+            
+            ui.horizontal(|ui| {
+                let tab = ui.selectable_label(
+                    is_active,
+                    egui::RichText::new(format!("  {} ", tab_name)).color(tab_color)
+                ).interact(egui::Sense::drag());
+                if tab.clicked() {
+                    action = Some(TabAction::Select(group, i));
+                }
+                if tab.drag_started() {
+                    app.panes.dragged_tab = Some((group, i));
+                }
+                if ui.button(egui::RichText::new("✕").color(COLOR_ERROR).size(12.0)).clicked() {
+                    action = Some(TabAction::Close(group, i));
+                }
+            });
+        }
+    });
+    ui.separator();
+    action
+}
+
+// Dropping a dragged tab on another group moves it there
+fn handle_tab_drop(app: &mut CodeEditorApp, ctx: &egui::Context, rects: &[egui::Rect]) {
+    let Some((from, tab)) = app.panes.dragged_tab else { return };
+    let pointer = ctx.input(|i| i.pointer.hover_pos());
+    let target = pointer.and_then(|p| rects.iter().position(|r| r.contains(p)));
+
+    if ctx.input(|i| i.pointer.any_released()) {
+        app.panes.dragged_tab = None;
+        if let Some(to) = target {
+            panes::move_tab(app, from, tab, to);
+        }
+        return;
+    }
+
+    let name = app.panes.groups.get(from)
+        .and_then(|g| g.tabs.get(tab))
+        .and_then(|doc| app.panes.documents.get(doc))
+        .map(|d| d.name())
+        .unwrap_or_default();
+    if let Some(rect) = target.filter(|&to| to != from).map(|to| rects[to]) {
+        ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("tab_drop")))
+            .rect_stroke(rect, 2.0, egui::Stroke::new(2.0, COLOR_ACCENT));
+    }
+    egui::show_tooltip_at_pointer(ctx, egui::Id::new("dragged_tab"), |ui| {
+        ui.label(format!("📄 {}", name));
+    });
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+use eframe::egui;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use crate::app::CodeEditorApp;
+use crate::completion;
+use crate::diff;
+use crate::file_tree;
+use crate::hex;
+use crate::notifications::{self, Level};
+use crate::panes;
+use crate::recent;
+use crate::search;
+
+#[derive(Default)]
+pub struct WatchState {
+    debouncer: Option<Debouncer<RecommendedWatcher>>,
+    events: Option<Receiver<DebounceEventResult>>,
+    // Folders of the open files; a folder is watched rather than the file itself
+    // because tools like git and formatters replace files by renaming over them
+    watched: BTreeSet<PathBuf>,
+    // The explorer's folder, watched recursively; open files inside it need no watch of their own
+    watched_root: Option<PathBuf>,
+    failed: bool,
+    // Modified documents whose file changed on disk, waiting for keep/reload
+    pub conflicts: Vec<usize>,
+    // Documents whose save was stopped because the file changed since it was loaded
+    pub blocked_saves: Vec<usize>,
+}
+
+pub fn sync(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    update_watches(app, ctx);
+
+    let mut changed = BTreeSet::new();
+    let mut errors = Vec::new();
+    if let Some(events) = &app.watcher.events {
+        while let Ok(result) = events.try_recv() {
+            match result {
+                Ok(batch) => changed.extend(batch.into_iter().map(|e| e.path)),
+                Err(e) => errors.push(format!("File watcher: {}", e)),
+            }
+        }
+    }
+    for message in errors {
+        notifications::notify(app, Level::Error, message, Vec::new());
+    }
+    if changed.is_empty() {
+        return;
+    }
+    file_tree::apply_changes(app, &changed);
+    search::files_changed(app, &changed);
+    completion::files_changed(app, &changed);
+    recent::paths_changed(app, &changed);
+    // A removed folder reports only its own path, so match open files underneath it too
+    let affected: Vec<usize> = app.panes.documents.iter()
+        .filter(|(_, d)| d.path.as_ref().is_some_and(|p| changed.iter().any(|c| p.starts_with(c))))
+        .map(|(id, _)| *id)
+        .collect();
+    for doc in affected {
+        check(app, doc);
+    }
+
+    let documents = &app.panes.documents;
+    app.watcher.conflicts.retain(|doc| documents.contains_key(doc));
+    app.watcher.blocked_saves.retain(|doc| documents.contains_key(doc));
+}
+
+fn update_watches(app: &mut CodeEditorApp, ctx: &egui::Context) {
+    if app.watcher.failed {
+        return;
+    }
+    let root = app.root_folder.clone();
+    let wanted: BTreeSet<PathBuf> = app.panes.paths().iter()
+        .filter_map(|p| p.parent().map(Path::to_path_buf))
+        .filter(|dir| !root.as_ref().is_some_and(|r| dir.starts_with(r)))
+        .collect();
+    if wanted == app.watcher.watched && root == app.watcher.watched_root {
+        return;
+    }
+
+    if app.watcher.debouncer.is_none() {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        let handler = move |result: DebounceEventResult| {
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        };
+        match new_debouncer(Duration::from_millis(250), handler) {
+            Ok(debouncer) => {
+                app.watcher.debouncer = Some(debouncer);
+                app.watcher.events = Some(receiver);
+            }
+            Err(e) => {
+                app.watcher.failed = true;
+                notifications::notify(app, Level::Error, format!("Could not watch files for changes: {}", e), Vec::new());
+                return;
+            }
+        }
+    }
+
+    // Folder watches are dropped before the root changes so that unwatching one
+    // never removes a watch the recursive root watch relies on
+    let Some(debouncer) = app.watcher.debouncer.as_mut() else { return };
+    let mut failures = Vec::new();
+    for dir in app.watcher.watched.difference(&wanted) {
+        let _ = debouncer.watcher().unwatch(dir);
+    }
+    if root != app.watcher.watched_root {
+        if let Some(old) = &app.watcher.watched_root {
+            let _ = debouncer.watcher().unwatch(old);
+        }
+        if let Some(root) = &root {
+            if let Err(e) = debouncer.watcher().watch(root, RecursiveMode::Recursive) {
+                failures.push((root.clone(), e));
+            }
+        }
+    }
+    for dir in wanted.difference(&app.watcher.watched) {
+        if let Err(e) = debouncer.watcher().watch(dir, RecursiveMode::NonRecursive) {
+            failures.push((dir.clone(), e));
+        }
+    }
+    app.watcher.watched = wanted;
+    app.watcher.watched_root = root;
+
+    for (path, e) in failures {
+        notifications::notify(app, Level::Error, format!("Could not watch {}: {}", path.display(), e), Vec::new());
+    }
+}
+
+// New contents of the document's file if it no longer matches what was last loaded or saved
+fn external_change(app: &mut CodeEditorApp, doc: usize) -> Option<Vec<u8>> {
+    let document = app.panes.documents.get_mut(&doc)?;
+    let path = document.path.as_ref()?;
+    let modified = panes::modified_time(path);
+    if modified.is_none() || modified == document.disk_modified {
+        return None;
+    }
+    let content = fs::read(path).ok()?;
+    let hash = panes::hash_file_contents(document, &content)?;
+    if Some(hash) == document.saved_hash {
+        // Only the timestamp moved, e.g. a formatter that found nothing to change
+        document.disk_modified = modified;
+        return None;
+    }
+    Some(content)
+}
+
+pub fn changed_on_disk(app: &mut CodeEditorApp, doc: usize) -> bool {
+    external_change(app, doc).is_some()
+}
+
+// Reloads an unmodified buffer straight away; a modified one waits for the user
+fn check(app: &mut CodeEditorApp, doc: usize) {
+    let Some(document) = app.panes.documents.get_mut(&doc) else { return };
+    let missing = document.path.as_ref().is_some_and(|p| !p.exists());
+    if missing != document.missing {
+        document.missing = missing;
+        if missing {
+            let name = document.name();
+            notifications::notify(app, Level::Warning, format!("{} was deleted from disk", name), Vec::new());
+        }
+    }
+    if missing {
+        return;
+    }
+    let Some(content) = external_change(app, doc) else { return };
+    if panes::is_dirty(app, doc) {
+        if !app.watcher.conflicts.contains(&doc) {
+            app.watcher.conflicts.push(doc);
+        }
+        return;
+    }
+    reload(app, doc, content);
+    let name = app.panes.documents.get(&doc).map(|d| d.name()).unwrap_or_default();
+    notifications::notify(app, Level::Info, format!("Reloaded {} (changed on disk)", name), Vec::new());
+}
+
+fn reload(app: &mut CodeEditorApp, doc: usize, content: Vec<u8>) {
+    let Some(document) = app.panes.documents.get_mut(&doc) else { return };
+    document.disk_modified = document.path.as_deref().and_then(panes::modified_time);
+    if document.bytes.is_some() {
+        document.saved_hash = Some(panes::hash_bytes(&content));
+        document.bytes = Some(content);
+        panes::mark_edited(app, doc);
+        hex::reloaded(app, doc);
+        return resolved(app, doc);
+    }
+    document.lossy = std::str::from_utf8(&content).is_err();
+    let content = String::from_utf8_lossy(&content).into_owned();
+    document.saved_hash = Some(panes::hash_text(&content));
+    if app.panes.active_doc == Some(doc) {
+        app.text = content;
+    } else {
+        document.text = content;
+    }
+    panes::mark_edited(app, doc);
+    resolved(app, doc);
+}
+
+// Drops any pending prompt for the document
+pub fn resolved(app: &mut CodeEditorApp, doc: usize) {
+    app.watcher.conflicts.retain(|&d| d != doc);
+    app.watcher.blocked_saves.retain(|&d| d != doc);
+}
+
+pub fn reload_from_disk(app: &mut CodeEditorApp, doc: usize) {
+    let Some(path) = app.panes.documents.get(&doc).and_then(|d| d.path.clone()) else { return };
+    match fs::read(&path) {
+        Ok(content) => reload(app, doc, content),
+        Err(e) => notifications::notify(app, Level::Error, format!("Could not reload {}: {}", path.display(), e), Vec::new()),
+    }
+}
+
+// Keeps the buffer and takes the new file as its baseline, so it still shows as modified
+// and the next save overwrites the file without asking again
+pub fn keep_buffer(app: &mut CodeEditorApp, doc: usize) {
+    let Some(document) = app.panes.documents.get_mut(&doc) else { return };
+    if let Some(path) = &document.path {
+        if let Some(hash) = fs::read(path).ok().and_then(|content| panes::hash_file_contents(document, &content)) {
+            document.saved_hash = Some(hash);
+        }
+        document.disk_modified = panes::modified_time(path);
+    }
+    resolved(app, doc);
+}
+
+// Shows the buffer next to the file on disk
+pub fn compare(app: &mut CodeEditorApp, doc: usize) {
+    panes::show(app, doc);
+    diff::compare_with_saved(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Sender;
+    use std::time::SystemTime;
+    use notify_debouncer_mini::{DebouncedEvent, DebouncedEventKind};
+
+    // An app whose watcher events come from the test instead of the file system
+    fn app_with_events(root: &Path) -> (CodeEditorApp, Sender<DebounceEventResult>) {
+        let (sender, receiver) = channel();
+        let mut app = CodeEditorApp { root_folder: Some(root.to_path_buf()), ..Default::default() };
+        app.watcher.failed = true;
+        app.watcher.events = Some(receiver);
+        (app, sender)
+    }
+
+    fn report(sender: &Sender<DebounceEventResult>, paths: &[&Path]) {
+        let events = paths.iter().map(|path| DebouncedEvent { path: path.to_path_buf(), kind: DebouncedEventKind::Any }).collect();
+        sender.send(Ok(events)).unwrap();
+    }
+
+    // Later than the load, however coarse the file system's timestamps are
+    fn touch(path: &Path, contents: &str) {
+        fs::write(path, contents).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        fs::File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+    }
+
+    #[test]
+    fn changed_files_reload_or_wait_for_the_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let (clean_path, edited_path) = (dir.path().join("clean.rs"), dir.path().join("edited.rs"));
+        fs::write(&clean_path, "old").unwrap();
+        fs::write(&edited_path, "old").unwrap();
+        let (mut app, sender) = app_with_events(dir.path());
+        let clean = panes::add_document(&mut app, Some(clean_path.clone()), "old".to_string());
+        let edited = panes::add_document(&mut app, Some(edited_path.clone()), "old".to_string());
+        app.panes.documents.get_mut(&edited).unwrap().text = "mine".to_string();
+        panes::mark_edited(&mut app, edited);
+
+        touch(&clean_path, "theirs");
+        touch(&edited_path, "theirs");
+        report(&sender, &[&clean_path, &edited_path]);
+        sync(&mut app, &egui::Context::default());
+        assert_eq!(app.panes.documents[&clean].text, "theirs");
+        assert!(!panes::is_dirty(&app, clean));
+        assert_eq!(app.panes.documents[&edited].text, "mine");
+        assert_eq!(app.watcher.conflicts, vec![edited]);
+    }
+
+    #[test]
+    fn deleted_folders_mark_the_files_below_them_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src").join("main.rs");
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(&path, "").unwrap();
+        let (mut app, sender) = app_with_events(dir.path());
+        let doc = panes::add_document(&mut app, Some(path), String::new());
+
+        fs::remove_dir_all(dir.path().join("src")).unwrap();
+        report(&sender, &[&dir.path().join("src")]);
+        sync(&mut app, &egui::Context::default());
+        assert!(app.panes.documents[&doc].missing);
+        assert!(app.notifications.history.last().unwrap().message.contains("was deleted"));
+    }
+}